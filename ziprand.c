@@ -3,6 +3,23 @@
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <time.h>
+
+#ifdef ZIPRAND_HAVE_ZLIB
+#include <zlib.h>
+#endif
+#ifdef ZIPRAND_HAVE_BZIP2
+#include <bzlib.h>
+#endif
+#ifdef ZIPRAND_HAVE_ZSTD
+#include <zstd.h>
+#endif
+#ifdef ZIPRAND_HAVE_LZMA
+#include <lzma.h>
+#endif
+#ifdef ZIPRAND_HAVE_OPENSSL
+#include <openssl/evp.h>
+#endif
 
 /* ZIP signatures */
 #define EOCD_SIGNATURE               0x06054b50
@@ -11,18 +28,229 @@
 #define CENTRAL_DIR_SIGNATURE        0x02014b50
 #define LOCAL_HEADER_SIGNATURE       0x04034b50
 
+/* central directory read sizes */
+#define CD_BULK_READ_MAX (16 * 1024 * 1024)
+#define CD_CHUNK_SIZE    (1024 * 1024)
+#define CD_MIN_READ      (64 * 1024)
+
+/* EOCD record (22 bytes) plus the maximum comment length */
+#define EOCD_MAX_SEARCH (22 + 0xFFFF)
+
+/* saved index format */
+#define INDEX_MAGIC       "ZRINDEX\0"
+#define INDEX_VERSION     7
+#define INDEX_HEADER_SIZE 104
+#define INDEX_RECORD_SIZE 56
+
+/* room for the local extra field when estimating an entry's range before its header is read */
+#define PREFETCH_EXTRA_SLACK 256
+
+/* largest coalesced local header read; always fits one header with a maximum length name */
+#define LOCAL_HEADER_WINDOW (128 * 1024)
+
+/* capability defaults (ziprand_io_capabilities) */
+#define CAPS_BLOCK_SIZE          (64 * 1024)
+#define CAPS_REMOTE_BLOCK_SIZE   (1024 * 1024)
+#define CAPS_LOCAL_CONCURRENCY   4
+#define CAPS_REMOTE_CONCURRENCY  8
+
+/* largest batch of ranges handed to a multi-range read at once */
+#define RANGES_BATCH_SIZE (16 * 1024 * 1024)
+
+/* largest gap read through when coalescing reads of a local backend */
+#define LOCAL_COALESCE_GAP (4 * 1024)
+
+/* default chunk size for ziprand_fcopy and chunk iterators */
+#define COPY_CHUNK_SIZE (64 * 1024)
+
+/* compressed bytes read from the archive at a time when decompressing */
+#define DECODE_INPUT_SIZE (64 * 1024)
+
+/* DEFLATE history a decoder needs to restart mid-stream, and default checkpoint spacing */
+#define DEFLATE_WINDOW_SIZE        32768
+#define DEFLATE_INDEX_DEFAULT_SPAN (1024 * 1024)
+
+/* compression methods */
+#define METHOD_STORED  0
+#define METHOD_DEFLATE 8
+#define METHOD_BZIP2   12
+#define METHOD_LZMA    14
+#define METHOD_ZSTD    93
+#define METHOD_XZ      95
+#define METHOD_AES     99 /* WinZip AES, the real method is in the AES extra field */
+
+/* general purpose bit flags */
+#define FLAG_ENCRYPTED        0x0001
+#define FLAG_DATA_DESCRIPTOR  0x0008
+#define FLAG_STRONG_ENCRYPTED 0x0040
+
+/* traditional PKWARE encryption header in front of the data */
+#define ZIPCRYPTO_HEADER_SIZE 12
+
+/* WinZip AES: extra field, salt and password verifier before the data, authentication code
+ * after it, and keys derived with PBKDF2-HMAC-SHA1 */
+#define EXTRA_AES             0x9901
+#define AES_VERIFIER_SIZE     2
+#define AES_MAC_SIZE          10
+#define AES_BLOCK_SIZE        16
+#define AES_MAX_KEY_SIZE      32
+#define AES_PBKDF2_ITERATIONS 1000
+
+/* longest password a password provider can give, including the terminator */
+#define PASSWORD_BUFFER_SIZE 256
+
+/* ZIP's LZMA header: version (2 bytes), properties size (2 bytes), properties (5 bytes) */
+#define LZMA_HEADER_SIZE 9
+
+/* seekable Zstandard: seek table in a skippable frame at the end of the data */
+#define ZSTD_SKIPPABLE_MAGIC    0x184D2A5E
+#define ZSTD_SEEKABLE_MAGIC     0x8F92EAB1
+#define ZSTD_SEEK_FOOTER_SIZE   9
+#define ZSTD_SEEK_CHECKSUM_FLAG 0x80
+
+/* name arena block size */
+#define NAME_BLOCK_SIZE (64 * 1024)
+
+/* names up to this long are glob matched without an allocation */
+#define GLOB_STACK_SIZE 1024
+
+/* host system and file type bits from "version made by" / external attributes */
+#define HOST_UNIX    3
+#define HOST_MACOS   19
+#define UNIX_S_IFMT  0170000
+#define UNIX_S_IFLNK 0120000
+
 /* internal structures */
+
+/* entry names are packed into large blocks instead of one allocation per entry */
+typedef struct name_block {
+    struct name_block* next;
+    size_t used;
+    size_t capacity;
+    char data[];
+} name_block_t;
+
 struct ziprand_archive {
     ziprand_io_t io;
+    ziprand_options_t options;
     ziprand_entry_t* entries;
     size_t entry_count;
+    name_block_t* names; /* storage for entries[i].name */
     uint64_t total_size;
+    ziprand_archive_info_t info;
+    uint64_t eocd_hash; /* fingerprint of the EOCD record, stored in saved indexes */
+    size_t* name_index; /* open-addressing table of entry index + 1, built on first lookup */
+    size_t name_index_mask;
+    size_t* nocase_index; /* same, keyed on case-folded names (ZIPRAND_MATCH_IGNORE_CASE) */
+    size_t nocase_index_mask;
+    uint32_t* sorted_index; /* entry indices sorted by name, built on first lookup */
+    int sorted_index_borrowed; /* sorted_index points into a caller-owned saved index */
+    ziprand_tree_node_t* tree; /* directory tree root, built on first use */
+    name_block_t* tree_nodes;  /* storage for tree nodes */
+    name_block_t* tree_names;  /* storage for tree node names */
+};
+
+/* buffered window over a byte range (central directory, saved index) parsed from memory */
+typedef struct {
+    const ziprand_io_t* io;
+    uint8_t* buf;
+    size_t capacity;
+    uint64_t start; /* source offset of buf[0] */
+    size_t len;     /* valid bytes in buf */
+    uint64_t end;   /* end of the range, reads are not extended past it */
+} range_reader_t;
+
+struct ziprand_iter {
+    ziprand_io_t io;
+    ziprand_archive_info_t info;
+    range_reader_t reader;
+    uint64_t offset;    /* offset of the next central directory record */
+    uint64_t remaining; /* records left to parse */
+    ziprand_entry_t current;
+};
+
+/* traditional PKWARE encryption (ZipCrypto) keys */
+typedef struct {
+    uint32_t keys[3];
+} zipcrypto_t;
+
+/* decompression state of an open compressed entry, created on its first read */
+typedef struct {
+    void* state;
+    uint8_t* input; /* compressed bytes, input[input_pos, input_len) not yet decoded */
+    size_t input_pos;
+    size_t input_len;
+    uint64_t source_offset; /* compressed bytes of the entry read so far */
+    uint64_t offset;        /* uncompressed offset of the next byte decoded */
+    int finished;
+    zipcrypto_t cipher; /* keys for decrypting the next compressed byte */
+#ifdef ZIPRAND_HAVE_OPENSSL
+    EVP_CIPHER_CTX* aes; /* WinZip AES keystream, or NULL */
+    EVP_MD_CTX* mac;     /* HMAC-SHA1 of the AES data, NULL once checked or given up on */
+    uint64_t mac_offset; /* compressed offset the HMAC has reached */
+#endif
+    /* independently compressed frames (seekable Zstandard), or frame_count 0 */
+    uint64_t* frame_offsets; /* uncompressed start of each frame */
+    uint64_t* frame_sources; /* compressed start of each frame */
+    size_t frame_count;
+} decoder_t;
+
+/* place a DEFLATE stream can be decoded from without what came before */
+typedef struct {
+    uint64_t offset;        /* uncompressed offset */
+    uint64_t source_offset; /* compressed bytes consumed up to here */
+    uint8_t bits;           /* unused low bits of the last consumed byte, 0-7 */
+    uint8_t byte;           /* that byte */
+    uint8_t window[DEFLATE_WINDOW_SIZE]; /* the uncompressed bytes before offset */
+} deflate_point_t;
+
+struct ziprand_deflate_index {
+    /* the entry the index was built for */
+    uint64_t entry_offset;
+    uint64_t compressed_size;
+    uint64_t uncompressed_size;
+    uint32_t crc32;
+    deflate_point_t* points; /* in offset order */
+    size_t count;
 };
 
 struct ziprand_file {
     ziprand_archive_t* archive;
     const ziprand_entry_t* entry;
+    const ziprand_decompressor_t* codec; /* NULL for stored entries */
+    decoder_t* decoder;
+    const ziprand_deflate_index_t* index; /* checkpoints for compressed reads, or NULL */
+    uint64_t size; /* uncompressed size, or compressed size for raw handles */
+    int encrypted;         /* decrypted as it is read */
+    zipcrypto_t cipher;    /* keys after the encryption header */
+    uint8_t key[AES_MAX_KEY_SIZE];
+    int key_size;          /* WinZip AES key size in bytes, 0 for other encryption */
+    uint8_t auth_key[AES_MAX_KEY_SIZE]; /* WinZip AES authentication key */
+    int authenticate;      /* read data skipped by seeks to check the authentication code */
+    uint64_t source_start; /* compressed bytes before the data: the encryption header */
+    uint64_t source_end;   /* end of the data, before any authentication code */
     uint64_t position;
+    int verify;            /* keep a running CRC-32 over sequential reads */
+    uint32_t crc;          /* CRC-32 of bytes [0, crc_offset) */
+    uint64_t crc_offset;
+    ziprand_error_t error; /* sticky error, reads fail once set */
+    uint8_t* readahead;    /* optional buffer for ziprand_fread (ziprand_fsetbuf) */
+    size_t readahead_capacity;
+    uint64_t readahead_start; /* file offset of readahead[0] */
+    size_t readahead_len;
+};
+
+struct ziprand_chunk_iter {
+    ziprand_file_t* file;
+    uint64_t offset;
+    uint8_t* buffer;
+    size_t chunk_size;
+};
+
+struct ziprand_concat {
+    ziprand_file_t** files;
+    uint64_t* starts; /* starts[i] is where piece i begins, starts[count] the total size */
+    size_t count;
 };
 
 /* utility functions */
@@ -43,62 +271,306 @@ static inline uint64_t read_u64_le(const uint8_t* p)
            ((uint64_t)p[6] << 48) | ((uint64_t)p[7] << 56);
 }
 
-/* find End of Central Directory record */
-static ziprand_error_t
-find_eocd(ziprand_archive_t* archive, uint64_t* eocd_offset, uint16_t* num_entries)
+/* allocate from the name arena, starting a new block when the current one is full */
+static char* arena_alloc(name_block_t** arena, size_t size)
 {
-    uint8_t buffer[8192];
-    uint64_t file_size = archive->total_size;
-    uint64_t max_search = file_size < 65557 ? file_size : 65557;
-    uint64_t search_pos = file_size;
+    name_block_t* block = *arena;
+    if (!block || block->capacity - block->used < size) {
+        size_t capacity = size > NAME_BLOCK_SIZE ? size : NAME_BLOCK_SIZE;
+        block = malloc(sizeof(name_block_t) + capacity);
+        if (!block)
+            return NULL;
+        block->next = *arena;
+        block->used = 0;
+        block->capacity = capacity;
+        *arena = block;
+    }
 
-    while (search_pos > file_size - max_search) {
-        size_t chunk_size = search_pos - (file_size - max_search);
-        if (chunk_size > sizeof(buffer))
-            chunk_size = sizeof(buffer);
+    char* p = block->data + block->used;
+    block->used += size;
+    return p;
+}
 
-        uint64_t read_pos = search_pos - chunk_size;
-        int64_t bytes_read = archive->io.read(archive->io.ctx, read_pos, buffer, chunk_size);
+static void arena_free(name_block_t* arena)
+{
+    while (arena) {
+        name_block_t* next = arena->next;
+        free(arena);
+        arena = next;
+    }
+}
 
-        if (bytes_read <= 0)
-            return ZIPRAND_ERR_IO;
+/* FNV-1a hash over raw bytes */
+static uint64_t hash_bytes(const void* name, size_t len)
+{
+    const uint8_t* p = name;
+    uint64_t hash = 0xcbf29ce484222325ULL;
+    for (size_t i = 0; i < len; i++) {
+        hash ^= p[i];
+        hash *= 0x100000001b3ULL;
+    }
+    return hash;
+}
 
-        for (int64_t i = bytes_read - 4; i >= 0; i--) {
-            uint32_t sig = read_u32_le(&buffer[i]);
-            if (sig == EOCD_SIGNATURE) {
-                *eocd_offset = read_pos + i;
-                if (i + 10 < bytes_read) {
-                    *num_entries = read_u16_le(&buffer[i + 10]);
-                } else {
-                    uint8_t entry_buf[2];
-                    if (archive->io.read(archive->io.ctx, *eocd_offset + 10, entry_buf, 2) != 2)
-                        return ZIPRAND_ERR_IO;
-                    *num_entries = read_u16_le(entry_buf);
-                }
-                return ZIPRAND_OK;
-            }
+static inline char ascii_tolower(char c)
+{
+    return c >= 'A' && c <= 'Z' ? c + ('a' - 'A') : c;
+}
+
+/* FNV-1a hash over bytes with ASCII letters folded to lower case */
+static uint64_t hash_bytes_nocase(const void* name, size_t len)
+{
+    const char* p = name;
+    uint64_t hash = 0xcbf29ce484222325ULL;
+    for (size_t i = 0; i < len; i++) {
+        hash ^= (uint8_t)ascii_tolower(p[i]);
+        hash *= 0x100000001b3ULL;
+    }
+    return hash;
+}
+
+/* find the last occurrence of a 4-byte little-endian signature in buf */
+static int64_t find_last_signature(const uint8_t* buf, size_t len, uint32_t signature)
+{
+    uint8_t first = signature & 0xFF;
+    for (size_t i = len >= 4 ? len - 4 + 1 : 0; i > 0; i--) {
+        if (buf[i - 1] == first && read_u32_le(&buf[i - 1]) == signature)
+            return (int64_t)(i - 1);
+    }
+    return -1;
+}
+
+#ifdef ZIPRAND_TRACE
+/* process-wide trace callback (ziprand_set_trace) */
+static ziprand_trace_fn trace_fn;
+static void* trace_ctx;
+#endif
+
+/* start time of a traced operation, or 0 when tracing is off */
+static inline uint64_t trace_begin(void)
+{
+#ifdef ZIPRAND_TRACE
+    struct timespec now;
+    if (trace_fn && timespec_get(&now, TIME_UTC) == TIME_UTC)
+        return (uint64_t)now.tv_sec * 1000000 + (uint64_t)now.tv_nsec / 1000;
+#endif
+    return 0;
+}
+
+/* report a completed operation; compiled out without ZIPRAND_TRACE */
+static inline void trace_end(ziprand_trace_op_t op,
+                             const void* source,
+                             const char* name,
+                             uint64_t offset,
+                             uint64_t size,
+                             int64_t result,
+                             uint64_t start)
+{
+#ifdef ZIPRAND_TRACE
+    ziprand_trace_fn fn = trace_fn;
+    if (!fn || start == 0)
+        return;
+
+    ziprand_trace_event_t event;
+    event.op = op;
+    event.source = source;
+    event.name = name;
+    event.offset = offset;
+    event.size = size;
+    event.result = result;
+    uint64_t end = trace_begin();
+    event.duration_us = end > start ? end - start : 0; /* the wall clock may step backwards */
+    fn(trace_ctx, &event);
+#else
+    (void)op;
+    (void)source;
+    (void)name;
+    (void)offset;
+    (void)size;
+    (void)result;
+    (void)start;
+#endif
+}
+
+ziprand_error_t ziprand_set_trace(ziprand_trace_fn fn, void* ctx)
+{
+#ifdef ZIPRAND_TRACE
+    trace_fn = fn;
+    trace_ctx = ctx;
+    return ZIPRAND_OK;
+#else
+    (void)fn;
+    (void)ctx;
+    return ZIPRAND_ERR_INVALID_PARAM;
+#endif
+}
+
+/* backend calls, traced */
+static int64_t io_read(const ziprand_io_t* io, uint64_t offset, void* buffer, size_t size)
+{
+    uint64_t start = trace_begin();
+    int64_t result = io->read(io->ctx, offset, buffer, size);
+    trace_end(ZIPRAND_TRACE_READ, io->ctx, NULL, offset, size, result, start);
+    return result;
+}
+
+static int64_t
+io_read_vectored(const ziprand_io_t* io, uint64_t offset, const ziprand_iovec_t* iov, size_t count)
+{
+    uint64_t total = 0;
+    for (size_t i = 0; i < count; i++)
+        total += iov[i].len;
+    uint64_t start = trace_begin();
+    int64_t result = io->read_vectored(io->ctx, offset, iov, count);
+    trace_end(ZIPRAND_TRACE_READ, io->ctx, NULL, offset, total, result, start);
+    return result;
+}
+
+static ziprand_error_t
+io_read_buffer(const ziprand_io_t* io, uint64_t offset, size_t size, ziprand_buffer_t* buffer)
+{
+    uint64_t start = trace_begin();
+    ziprand_error_t err = io->read_buffer(io->ctx, offset, size, buffer);
+    trace_end(ZIPRAND_TRACE_READ,
+              io->ctx,
+              NULL,
+              offset,
+              size,
+              err == ZIPRAND_OK ? (int64_t)buffer->size : err,
+              start);
+    return err;
+}
+
+static void io_prefetch(const ziprand_io_t* io, const ziprand_range_t* ranges, size_t count)
+{
+    uint64_t start = trace_begin();
+    io->prefetch(io->ctx, ranges, count);
+    trace_end(ZIPRAND_TRACE_PREFETCH, io->ctx, NULL, ranges[0].offset, count, 0, start);
+}
+
+/* fill every range completely, one read at a time or with one multi-range read */
+static ziprand_error_t
+io_read_ranges(const ziprand_io_t* io, const ziprand_read_range_t* ranges, size_t count)
+{
+    if (io->read_ranges) {
+        uint64_t total = 0;
+        for (size_t i = 0; i < count; i++)
+            total += ranges[i].size;
+        uint64_t start = trace_begin();
+        ziprand_error_t err = io->read_ranges(io->ctx, ranges, count);
+        trace_end(ZIPRAND_TRACE_READ,
+                  io->ctx,
+                  NULL,
+                  count > 0 ? ranges[0].offset : 0,
+                  total,
+                  err == ZIPRAND_OK ? (int64_t)total : err,
+                  start);
+        return err;
+    }
+
+    /* the I/O callback may return short reads */
+    for (size_t i = 0; i < count; i++) {
+        uint8_t* buffer = ranges[i].buffer;
+        size_t size = ranges[i].size;
+        size_t done = 0;
+        while (done < size) {
+            int64_t got = io_read(io, ranges[i].offset + done, buffer + done, size - done);
+            if (got < 0)
+                return (ziprand_error_t)got;
+            if (got == 0)
+                return ZIPRAND_ERR_IO;
+            done += got;
         }
+    }
+    return ZIPRAND_OK;
+}
+
+static int64_t io_get_size(const ziprand_io_t* io)
+{
+    uint64_t start = trace_begin();
+    int64_t result = io->get_size(io->ctx);
+    trace_end(ZIPRAND_TRACE_GET_SIZE, io->ctx, NULL, 0, 0, result, start);
+    return result;
+}
+
+ziprand_error_t ziprand_io_capabilities(const ziprand_io_t* io, ziprand_io_caps_t* caps)
+{
+    if (!io || !caps)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    memset(caps, 0, sizeof(*caps));
+    caps->latency = io->mapped ? ZIPRAND_LATENCY_MEMORY : ZIPRAND_LATENCY_LOCAL;
+    if (io->capabilities)
+        io->capabilities(io->ctx, caps);
 
-        search_pos = read_pos;
-        if (search_pos < 3)
+    if (caps->block_size == 0)
+        caps->block_size =
+            caps->latency == ZIPRAND_LATENCY_REMOTE ? CAPS_REMOTE_BLOCK_SIZE : CAPS_BLOCK_SIZE;
+    if (caps->max_concurrency == 0) {
+        switch (caps->latency) {
+        case ZIPRAND_LATENCY_MEMORY:
+            caps->max_concurrency = 1;
             break;
+        case ZIPRAND_LATENCY_REMOTE:
+            caps->max_concurrency = CAPS_REMOTE_CONCURRENCY;
+            break;
+        default:
+            caps->max_concurrency = CAPS_LOCAL_CONCURRENCY;
+            break;
+        }
+    }
+    return ZIPRAND_OK;
+}
+
+ziprand_error_t
+ziprand_io_read_ranges(const ziprand_io_t* io, const ziprand_read_range_t* ranges, size_t count)
+{
+    if (!io || !io->read || (!ranges && count > 0))
+        return ZIPRAND_ERR_INVALID_PARAM;
+    return io_read_ranges(io, ranges, count);
+}
+
+/* find End of Central Directory record with a single read of the archive tail */
+static ziprand_error_t
+find_eocd(const ziprand_io_t* io, uint64_t file_size, uint64_t* eocd_offset, uint8_t eocd[22])
+{
+    size_t tail_size = file_size < EOCD_MAX_SEARCH ? (size_t)file_size : EOCD_MAX_SEARCH;
+    if (tail_size < 22)
+        return ZIPRAND_ERR_INVALID_ZIP;
+
+    uint8_t* tail = malloc(tail_size);
+    if (!tail)
+        return ZIPRAND_ERR_NOMEM;
+
+    uint64_t tail_start = file_size - tail_size;
+    if (io_read(io, tail_start, tail, tail_size) != (int64_t)tail_size) {
+        free(tail);
+        return ZIPRAND_ERR_IO;
+    }
+
+    /* search backwards; the record (without comment) must fit in the tail */
+    int64_t pos = find_last_signature(tail, tail_size - 22 + 4, EOCD_SIGNATURE);
+    if (pos < 0) {
+        free(tail);
+        return ZIPRAND_ERR_INVALID_ZIP;
     }
 
-    return ZIPRAND_ERR_INVALID_ZIP;
+    *eocd_offset = tail_start + pos;
+    memcpy(eocd, &tail[pos], 22);
+    free(tail);
+    return ZIPRAND_OK;
 }
 
 /* read ZIP64 EOCD */
-static ziprand_error_t read_zip64_eocd(ziprand_archive_t* archive,
-                                       uint64_t eocd_offset,
-                                       uint64_t* cd_offset,
-                                       uint64_t* num_entries)
+static ziprand_error_t
+read_zip64_eocd(const ziprand_io_t* io, uint64_t eocd_offset, ziprand_archive_info_t* info)
 {
     uint8_t buffer[56];
     uint64_t search_start = eocd_offset > 20 ? eocd_offset - 20 : 0;
 
     /* find ZIP64 EOCD locator */
     uint8_t search_buf[20];
-    if (archive->io.read(archive->io.ctx, search_start, search_buf, 20) != 20)
+    if (io_read(io, search_start, search_buf, 20) != 20)
         return ZIPRAND_ERR_IO;
 
     uint64_t zip64_eocd_offset = 0;
@@ -113,117 +585,255 @@ static ziprand_error_t read_zip64_eocd(ziprand_archive_t* archive,
         return ZIPRAND_ERR_INVALID_ZIP;
 
     /* read ZIP64 EOCD */
-    if (archive->io.read(archive->io.ctx, zip64_eocd_offset, buffer, 56) != 56)
+    if (io_read(io, zip64_eocd_offset, buffer, 56) != 56)
         return ZIPRAND_ERR_IO;
 
     if (read_u32_le(buffer) != ZIP64_EOCD_SIGNATURE)
         return ZIPRAND_ERR_INVALID_ZIP;
 
-    *cd_offset = read_u64_le(&buffer[48]);
-    *num_entries = read_u64_le(&buffer[32]);
+    info->zip64_eocd_offset = zip64_eocd_offset;
+    info->disk_number = read_u32_le(&buffer[16]);
+    info->cd_disk_number = read_u32_le(&buffer[20]);
+    info->total_entries = read_u64_le(&buffer[32]);
+    info->cd_size = read_u64_le(&buffer[40]);
+    info->cd_offset = read_u64_le(&buffer[48]);
+    info->is_zip64 = 1;
 
     return ZIPRAND_OK;
 }
 
-/* get central directory info */
-static ziprand_error_t
-get_cd_info(ziprand_archive_t* archive, uint64_t* cd_offset, uint64_t* num_entries)
+/* get central directory info, optionally with a hash of the EOCD record */
+static ziprand_error_t get_cd_info(const ziprand_io_t* io,
+                                   uint64_t file_size,
+                                   ziprand_archive_info_t* info,
+                                   uint64_t* eocd_hash)
 {
     uint64_t eocd_offset;
-    uint16_t entries_16;
-    ziprand_error_t err = find_eocd(archive, &eocd_offset, &entries_16);
+    uint8_t eocd_buf[22];
+    uint64_t start = trace_begin();
+    ziprand_error_t err = find_eocd(io, file_size, &eocd_offset, eocd_buf);
+    trace_end(ZIPRAND_TRACE_FIND_EOCD,
+              io->ctx,
+              NULL,
+              err == ZIPRAND_OK ? eocd_offset : 0,
+              file_size,
+              err,
+              start);
     if (err != ZIPRAND_OK)
         return err;
 
-    uint8_t eocd_buf[22];
-    if (archive->io.read(archive->io.ctx, eocd_offset, eocd_buf, 22) != 22)
-        return ZIPRAND_ERR_IO;
+    if (eocd_hash)
+        *eocd_hash = hash_bytes(eocd_buf, sizeof(eocd_buf));
 
-    uint32_t cd_offset_32 = read_u32_le(&eocd_buf[16]);
+    memset(info, 0, sizeof(*info));
+    info->eocd_offset = eocd_offset;
+    info->disk_number = read_u16_le(&eocd_buf[4]);
+    info->cd_disk_number = read_u16_le(&eocd_buf[6]);
+    info->total_entries = read_u16_le(&eocd_buf[10]);
+    info->cd_size = read_u32_le(&eocd_buf[12]);
+    info->cd_offset = read_u32_le(&eocd_buf[16]);
+    info->comment_length = read_u16_le(&eocd_buf[20]);
 
-    if (cd_offset_32 == 0xFFFFFFFF) {
-        return read_zip64_eocd(archive, eocd_offset, cd_offset, num_entries);
-    } else {
-        *cd_offset = cd_offset_32;
-        *num_entries = entries_16;
-        return ZIPRAND_OK;
+    /* any saturated field means the real values live in the ZIP64 EOCD */
+    if (info->cd_offset == 0xFFFFFFFF || info->cd_size == 0xFFFFFFFF ||
+        info->total_entries == 0xFFFF) {
+        err = read_zip64_eocd(io, eocd_offset, info);
+        if (err == ZIPRAND_ERR_INVALID_ZIP && info->cd_offset != 0xFFFFFFFF &&
+            info->cd_size != 0xFFFFFFFF)
+            return ZIPRAND_OK; /* genuine 65535-entry archive without ZIP64 records */
+        return err;
     }
+
+    return ZIPRAND_OK;
+}
+
+static ziprand_error_t range_reader_init(
+    range_reader_t* reader, const ziprand_io_t* io, uint64_t start, uint64_t size, size_t max_bulk)
+{
+    memset(reader, 0, sizeof(*reader));
+    reader->io = io;
+    reader->end = start + size;
+
+    /* read the range in one go when it fits max_bulk, otherwise in chunks of at least the
+     * backend's block size */
+    ziprand_io_caps_t caps;
+    ziprand_io_capabilities(io, &caps);
+    size_t chunk = caps.block_size > CD_CHUNK_SIZE ? caps.block_size : CD_CHUNK_SIZE;
+    reader->capacity = size <= max_bulk ? (size_t)size : chunk;
+    if (reader->capacity < CD_MIN_READ)
+        reader->capacity = CD_MIN_READ;
+
+    reader->buf = malloc(reader->capacity);
+    return reader->buf ? ZIPRAND_OK : ZIPRAND_ERR_NOMEM;
 }
 
-/* read central directory entry */
+static void range_reader_free(range_reader_t* reader)
+{
+    free(reader->buf);
+    reader->buf = NULL;
+}
+
+/* make [offset, offset + size) available in memory */
 static ziprand_error_t
-read_cd_entry(ziprand_archive_t* archive, uint64_t* offset, ziprand_entry_t* entry)
+range_reader_fetch(range_reader_t* reader, uint64_t offset, size_t size, const uint8_t** out)
 {
-    uint8_t header[46];
-    if (archive->io.read(archive->io.ctx, *offset, header, 46) != 46)
+    if (offset >= reader->start && offset + size <= reader->start + reader->len) {
+        *out = reader->buf + (offset - reader->start);
+        return ZIPRAND_OK;
+    }
+
+    if (size > reader->capacity) {
+        uint8_t* buf = realloc(reader->buf, size);
+        if (!buf)
+            return ZIPRAND_ERR_NOMEM;
+        reader->buf = buf;
+        reader->capacity = size;
+    }
+
+    size_t want = reader->capacity;
+    if (offset < reader->end && reader->end - offset < want)
+        want = reader->end - offset;
+    if (want < size)
+        want = size;
+
+    reader->start = offset;
+    reader->len = 0;
+    int64_t bytes_read = io_read(reader->io, offset, reader->buf, want);
+    if (bytes_read < (int64_t)size)
         return ZIPRAND_ERR_IO;
 
+    reader->len = bytes_read;
+    *out = reader->buf;
+    return ZIPRAND_OK;
+}
+
+/* data of the first extra field with the given ID, or NULL */
+static const uint8_t*
+find_extra_field(const uint8_t* extra, size_t extra_len, uint16_t id, uint16_t* size)
+{
+    size_t pos = 0;
+    while (pos + 4 <= extra_len) {
+        uint16_t data_size = read_u16_le(&extra[pos + 2]);
+        if (pos + 4 + data_size > extra_len)
+            return NULL;
+        if (read_u16_le(&extra[pos]) == id) {
+            *size = data_size;
+            return extra + pos + 4;
+        }
+        pos += 4 + data_size;
+    }
+    return NULL;
+}
+
+/* read central directory entry; the name comes from arena, or malloc() when arena is NULL */
+/* options == NULL parses leniently without limits */
+static ziprand_error_t read_cd_entry(range_reader_t* reader,
+                                     uint64_t* offset,
+                                     ziprand_entry_t* entry,
+                                     name_block_t** arena,
+                                     const ziprand_options_t* options)
+{
+    const ziprand_limits_t* limits = options ? &options->limits : NULL;
+    int strict = options && options->parse_mode == ZIPRAND_PARSE_STRICT;
+
+    const uint8_t* header;
+    ziprand_error_t err = range_reader_fetch(reader, *offset, 46, &header);
+    if (err != ZIPRAND_OK)
+        return err;
+
     if (read_u32_le(header) != CENTRAL_DIR_SIGNATURE)
         return ZIPRAND_ERR_INVALID_ZIP;
 
-    entry->compression_method = read_u16_le(&header[10]);
     uint16_t filename_len = read_u16_le(&header[28]);
     uint16_t extra_len = read_u16_le(&header[30]);
     uint16_t comment_len = read_u16_le(&header[32]);
 
+    if (limits && ((limits->max_name_length && filename_len > limits->max_name_length) ||
+                   (limits->max_extra_length && extra_len > limits->max_extra_length) ||
+                   (limits->max_comment_length && comment_len > limits->max_comment_length)))
+        return ZIPRAND_ERR_LIMIT_EXCEEDED;
+
+    /* fetch the whole record; this may move the window, so re-point header */
+    err = range_reader_fetch(reader, *offset, 46 + filename_len + extra_len, &header);
+    if (err != ZIPRAND_OK)
+        return err;
+
+    const uint8_t* filename = header + 46;
+    const uint8_t* extra = filename + filename_len;
+
+    entry->version_made_by = read_u16_le(&header[4]);
+    entry->flags = read_u16_le(&header[8]);
+    entry->compression_method = read_u16_le(&header[10]);
+    entry->mod_time = read_u16_le(&header[12]);
+    entry->mod_date = read_u16_le(&header[14]);
+
     uint64_t compressed_size = read_u32_le(&header[20]);
     uint64_t uncompressed_size = read_u32_le(&header[24]);
     uint64_t local_offset = read_u32_le(&header[42]);
+    entry->external_attr = read_u32_le(&header[38]);
+    entry->crc32 = read_u32_le(&header[16]);
 
-    /* Read filename */
-    entry->name = malloc(filename_len + 1);
+    /* copy filename */
+    entry->name = arena ? arena_alloc(arena, filename_len + 1) : malloc(filename_len + 1);
     if (!entry->name)
         return ZIPRAND_ERR_NOMEM;
 
-    if (archive->io.read(archive->io.ctx, *offset + 46, entry->name, filename_len) !=
-        filename_len) {
-        free(entry->name);
-        return ZIPRAND_ERR_IO;
-    }
+    memcpy(entry->name, filename, filename_len);
     entry->name[filename_len] = '\0';
+    entry->name_len = filename_len;
 
-    /* read extra field for ZIP64 */
-    if (extra_len > 0) {
-        uint8_t* extra = malloc(extra_len);
-        if (!extra) {
-            free(entry->name);
-            return ZIPRAND_ERR_NOMEM;
-        }
-
-        if (archive->io.read(archive->io.ctx, *offset + 46 + filename_len, extra, extra_len) !=
-            extra_len) {
-            free(extra);
-            free(entry->name);
-            return ZIPRAND_ERR_IO;
-        }
+    /* parse ZIP64 extra field */
+    if (uncompressed_size == 0xFFFFFFFF || compressed_size == 0xFFFFFFFF ||
+        local_offset == 0xFFFFFFFF) {
+        size_t pos = 0;
+        while (pos + 4 <= extra_len) {
+            uint16_t header_id = read_u16_le(&extra[pos]);
+            uint16_t data_size = read_u16_le(&extra[pos + 2]);
+            size_t field_end = pos + 4 + data_size;
+            if (field_end > extra_len) {
+                if (strict)
+                    return ZIPRAND_ERR_INVALID_ZIP;
+                break;
+            }
 
-        /* parse ZIP64 extra field */
-        if (uncompressed_size == 0xFFFFFFFF || compressed_size == 0xFFFFFFFF ||
-            local_offset == 0xFFFFFFFF) {
-            size_t pos = 0;
-            while (pos + 4 <= extra_len) {
-                uint16_t header_id = read_u16_le(&extra[pos]);
-                uint16_t data_size = read_u16_le(&extra[pos + 2]);
-
-                if (header_id == 0x0001) {
-                    size_t field_pos = pos + 4;
-                    if (uncompressed_size == 0xFFFFFFFF && field_pos + 8 <= pos + 4 + data_size) {
-                        uncompressed_size = read_u64_le(&extra[field_pos]);
-                        field_pos += 8;
-                    }
-                    if (compressed_size == 0xFFFFFFFF && field_pos + 8 <= pos + 4 + data_size) {
-                        compressed_size = read_u64_le(&extra[field_pos]);
-                        field_pos += 8;
-                    }
-                    if (local_offset == 0xFFFFFFFF && field_pos + 8 <= pos + 4 + data_size) {
-                        local_offset = read_u64_le(&extra[field_pos]);
-                    }
-                    break;
+            if (header_id == 0x0001) {
+                size_t field_pos = pos + 4;
+                if (uncompressed_size == 0xFFFFFFFF && field_pos + 8 <= field_end) {
+                    uncompressed_size = read_u64_le(&extra[field_pos]);
+                    field_pos += 8;
+                }
+                if (compressed_size == 0xFFFFFFFF && field_pos + 8 <= field_end) {
+                    compressed_size = read_u64_le(&extra[field_pos]);
+                    field_pos += 8;
+                }
+                if (local_offset == 0xFFFFFFFF && field_pos + 8 <= field_end) {
+                    local_offset = read_u64_le(&extra[field_pos]);
                 }
-                pos += 4 + data_size;
+                break;
             }
+            pos = field_end;
+        }
+
+        /* saturated fields must be resolved by the ZIP64 extra field */
+        if (strict && (uncompressed_size == 0xFFFFFFFF || compressed_size == 0xFFFFFFFF ||
+                       local_offset == 0xFFFFFFFF))
+            return ZIPRAND_ERR_INVALID_ZIP;
+    }
+
+    /* WinZip AES: version, vendor "AE", key strength and the real compression method */
+    entry->aes_strength = 0;
+    entry->aes_version = 0;
+    if (entry->compression_method == METHOD_AES) {
+        uint16_t size;
+        const uint8_t* field = find_extra_field(extra, extra_len, EXTRA_AES, &size);
+        if (field && size >= 7 && field[2] == 'A' && field[3] == 'E' && field[4] >= 1 &&
+            field[4] <= 3) {
+            entry->aes_strength = field[4];
+            entry->aes_version = (uint8_t)read_u16_le(field);
+            entry->compression_method = read_u16_le(&field[5]);
+        } else if (strict) {
+            return ZIPRAND_ERR_INVALID_ZIP;
         }
-        free(extra);
     }
 
     entry->compressed_size = compressed_size;
@@ -235,166 +845,4098 @@ read_cd_entry(ziprand_archive_t* archive, uint64_t* offset, ziprand_entry_t* ent
     return ZIPRAND_OK;
 }
 
-/* calculate data offset for an entry */
-static ziprand_error_t get_data_offset(ziprand_archive_t* archive, ziprand_entry_t* entry)
+/* bytes of a local header needed to compute the data offset; strict mode also compares the name */
+static size_t local_header_need(const ziprand_entry_t* entry, int strict)
 {
-    uint8_t local_header[30];
-    if (archive->io.read(archive->io.ctx, entry->offset, local_header, 30) != 30)
-        return ZIPRAND_ERR_IO;
+    return 30 + (strict ? entry->name_len : 0);
+}
 
+/* calculate data offset for an entry from its local header (local_header_need bytes) */
+/* in strict mode the local header must also agree with the central directory */
+static ziprand_error_t
+parse_local_header(const uint8_t* local_header, ziprand_entry_t* entry, int strict)
+{
     if (read_u32_le(local_header) != LOCAL_HEADER_SIGNATURE)
         return ZIPRAND_ERR_INVALID_ZIP;
 
     uint16_t filename_len = read_u16_le(&local_header[26]);
     uint16_t extra_len = read_u16_le(&local_header[28]);
 
+    uint16_t method = entry->aes_strength ? METHOD_AES : entry->compression_method;
+    if (strict && (read_u16_le(&local_header[8]) != method ||
+                   filename_len != entry->name_len ||
+                   memcmp(local_header + 30, entry->name, filename_len) != 0))
+        return ZIPRAND_ERR_INVALID_ZIP;
+
     entry->data_offset = entry->offset + 30 + filename_len + extra_len;
     return ZIPRAND_OK;
 }
 
+static ziprand_error_t get_data_offset(const ziprand_io_t* io, ziprand_entry_t* entry, int strict)
+{
+    uint8_t stack_buffer[30 + 256];
+    size_t need = local_header_need(entry, strict);
+    uint8_t* local_header = need <= sizeof(stack_buffer) ? stack_buffer : malloc(need);
+    if (!local_header)
+        return ZIPRAND_ERR_NOMEM;
+
+    ziprand_error_t err = ZIPRAND_ERR_IO;
+    if (io_read(io, entry->offset, local_header, need) == (int64_t)need)
+        err = parse_local_header(local_header, entry, strict);
+
+    if (local_header != stack_buffer)
+        free(local_header);
+    return err;
+}
+
 /* public API implementation */
 
-ziprand_archive_t* ziprand_open(const ziprand_io_t* io)
+/* order entries by raw name, used to detect duplicates */
+static int compare_names(const void* a, size_t a_len, const void* b, size_t b_len)
 {
-    if (!io || !io->read || !io->get_size)
-        return NULL;
-
-    ziprand_archive_t* archive = calloc(1, sizeof(ziprand_archive_t));
-    if (!archive)
-        return NULL;
+    size_t len = a_len < b_len ? a_len : b_len;
+    int cmp = memcmp(a, b, len);
+    if (cmp != 0)
+        return cmp;
+    return (a_len > b_len) - (a_len < b_len);
+}
 
-    archive->io = *io;
+static int compare_entry_names(const void* a, const void* b)
+{
+    const ziprand_entry_t* ea = *(const ziprand_entry_t* const*)a;
+    const ziprand_entry_t* eb = *(const ziprand_entry_t* const*)b;
+    return compare_names(ea->name, ea->name_len, eb->name, eb->name_len);
+}
 
-    int64_t size = archive->io.get_size(archive->io.ctx);
-    if (size < 0) {
-        free(archive);
-        return NULL;
-    }
-    archive->total_size = size;
+static ziprand_error_t check_duplicates(const ziprand_entry_t* entries, size_t count)
+{
+    if (count < 2)
+        return ZIPRAND_OK;
 
-    uint64_t cd_offset, num_entries;
-    if (get_cd_info(archive, &cd_offset, &num_entries) != ZIPRAND_OK) {
-        free(archive);
-        return NULL;
-    }
+    const ziprand_entry_t** sorted = malloc(count * sizeof(*sorted));
+    if (!sorted)
+        return ZIPRAND_ERR_NOMEM;
 
-    archive->entries = calloc(num_entries, sizeof(ziprand_entry_t));
-    if (!archive->entries) {
-        free(archive);
-        return NULL;
-    }
+    for (size_t i = 0; i < count; i++)
+        sorted[i] = &entries[i];
+    qsort(sorted, count, sizeof(*sorted), compare_entry_names);
 
-    uint64_t offset = cd_offset;
-    for (size_t i = 0; i < num_entries; i++) {
-        if (read_cd_entry(archive, &offset, &archive->entries[i]) != ZIPRAND_OK) {
-            for (size_t j = 0; j < i; j++)
-                free(archive->entries[j].name);
-            free(archive->entries);
-            free(archive);
-            return NULL;
+    ziprand_error_t err = ZIPRAND_OK;
+    for (size_t i = 1; i < count; i++) {
+        if (compare_entry_names(&sorted[i - 1], &sorted[i]) == 0) {
+            err = ZIPRAND_ERR_DUPLICATE_ENTRY;
+            break;
         }
     }
 
-    archive->entry_count = num_entries;
-    return archive;
+    free(sorted);
+    return err;
 }
 
-void ziprand_close(ziprand_archive_t* archive)
+/* order entry indices by (name, index) so duplicates keep central directory order */
+static int sorted_index_less(const ziprand_entry_t* entries, uint32_t a, uint32_t b)
 {
-    if (!archive)
-        return;
+    int cmp =
+        compare_names(entries[a].name, entries[a].name_len, entries[b].name, entries[b].name_len);
+    return cmp < 0 || (cmp == 0 && a < b);
+}
 
-    if (archive->io.close)
-        archive->io.close(archive->io.ctx);
+static void sift_down(const ziprand_entry_t* entries, uint32_t* index, size_t root, size_t count)
+{
+    for (;;) {
+        size_t child = root * 2 + 1;
+        if (child >= count)
+            return;
+        if (child + 1 < count && sorted_index_less(entries, index[child], index[child + 1]))
+            child++;
+        if (!sorted_index_less(entries, index[root], index[child]))
+            return;
+        uint32_t tmp = index[root];
+        index[root] = index[child];
+        index[child] = tmp;
+        root = child;
+    }
+}
 
-    for (size_t i = 0; i < archive->entry_count; i++)
-        free(archive->entries[i].name);
+/* build a compact array of entry indices sorted by name (heapsort, no extra memory) */
+static ziprand_error_t
+sort_entry_indices(const ziprand_entry_t* entries, size_t count, uint32_t** sorted)
+{
+    if (count > UINT32_MAX)
+        return ZIPRAND_ERR_INVALID_PARAM;
 
-    free(archive->entries);
-    free(archive);
+    uint32_t* index = malloc((count ? count : 1) * sizeof(uint32_t));
+    if (!index)
+        return ZIPRAND_ERR_NOMEM;
+
+    for (size_t i = 0; i < count; i++)
+        index[i] = (uint32_t)i;
+
+    for (size_t i = count / 2; i > 0; i--)
+        sift_down(entries, index, i - 1, count);
+    for (size_t end = count; end > 1; end--) {
+        uint32_t tmp = index[0];
+        index[0] = index[end - 1];
+        index[end - 1] = tmp;
+        sift_down(entries, index, 0, end - 1);
+    }
+
+    *sorted = index;
+    return ZIPRAND_OK;
 }
 
-int64_t ziprand_get_entry_count(ziprand_archive_t* archive)
+/* free the directory tree, which is rebuilt on next use */
+static void free_tree(ziprand_archive_t* archive)
 {
-    return archive ? (int64_t)archive->entry_count : -1;
+    arena_free(archive->tree_nodes);
+    arena_free(archive->tree_names);
+    archive->tree = NULL;
+    archive->tree_nodes = NULL;
+    archive->tree_names = NULL;
 }
 
-const ziprand_entry_t* ziprand_get_entry_by_index(ziprand_archive_t* archive, size_t index)
+/* replace the archive's entries, dropping lookup indexes built over the old ones */
+static void install_entries(ziprand_archive_t* archive,
+                            ziprand_entry_t* entries,
+                            size_t count,
+                            name_block_t* names,
+                            uint64_t total_size,
+                            const ziprand_archive_info_t* info)
 {
-    if (!archive || index >= archive->entry_count)
-        return NULL;
-    return &archive->entries[index];
+    arena_free(archive->names);
+    free(archive->entries);
+    free(archive->name_index);
+    archive->name_index = NULL;
+    free(archive->nocase_index);
+    archive->nocase_index = NULL;
+    if (!archive->sorted_index_borrowed)
+        free(archive->sorted_index);
+    archive->sorted_index = NULL;
+    archive->sorted_index_borrowed = 0;
+    free_tree(archive);
+    archive->entries = entries;
+    archive->entry_count = count;
+    archive->names = names;
+    archive->total_size = total_size;
+    archive->info = *info;
 }
 
-const ziprand_entry_t* ziprand_find_entry(ziprand_archive_t* archive, const char* name)
+/* strict mode: single-disk archive whose structures are in order and fill the file tail */
+static ziprand_error_t check_strict_layout(const ziprand_archive_info_t* info, uint64_t file_size)
 {
-    if (!archive || !name)
-        return NULL;
+    uint64_t cd_limit = info->is_zip64 ? info->zip64_eocd_offset : info->eocd_offset;
+    if (info->disk_number != 0 || info->cd_disk_number != 0 || info->cd_offset > cd_limit ||
+        info->cd_size > cd_limit - info->cd_offset ||
+        info->eocd_offset + 22 + info->comment_length != file_size)
+        return ZIPRAND_ERR_INVALID_ZIP;
+    return ZIPRAND_OK;
+}
+
+/* read EOCD and central directory; the archive is only updated on success */
+static ziprand_error_t load_central_directory(ziprand_archive_t* archive)
+{
+    int64_t size = io_get_size(&archive->io);
+    if (size < 0)
+        return ZIPRAND_ERR_IO;
+
+    ziprand_archive_info_t info;
+    uint64_t eocd_hash;
+    ziprand_error_t err = get_cd_info(&archive->io, size, &info, &eocd_hash);
+    if (err != ZIPRAND_OK)
+        return err;
+
+    const ziprand_limits_t* limits = &archive->options.limits;
+    if ((limits->max_entries && info.total_entries > limits->max_entries) ||
+        (limits->max_metadata_size && info.cd_size > limits->max_metadata_size) ||
+        (limits->max_comment_length && info.comment_length > limits->max_comment_length))
+        return ZIPRAND_ERR_LIMIT_EXCEEDED;
+
+    if (archive->options.parse_mode == ZIPRAND_PARSE_STRICT) {
+        err = check_strict_layout(&info, size);
+        if (err != ZIPRAND_OK)
+            return err;
+    }
+
+    /* every entry takes at least a 46-byte central header, so a count the directory can't
+     * hold is rejected before it sizes the entry array */
+    uint64_t num_entries = info.total_entries;
+    if (num_entries > info.cd_size / 46)
+        return ZIPRAND_ERR_INVALID_ZIP;
+
+    ziprand_entry_t* entries = calloc(num_entries, sizeof(ziprand_entry_t));
+    if (!entries && num_entries > 0)
+        return ZIPRAND_ERR_NOMEM;
+
+    uint64_t start = trace_begin();
+    range_reader_t reader;
+    err = range_reader_init(
+        &reader, &archive->io, info.cd_offset, info.cd_size, CD_BULK_READ_MAX);
+    if (err != ZIPRAND_OK) {
+        free(entries);
+        return err;
+    }
+
+    name_block_t* names = NULL;
+    uint64_t offset = info.cd_offset;
+    for (size_t i = 0; i < num_entries && err == ZIPRAND_OK; i++) {
+        err = read_cd_entry(&reader, &offset, &entries[i], &names, &archive->options);
+        /* the records themselves may run past the size claimed by the EOCD */
+        if (err == ZIPRAND_OK && limits->max_metadata_size &&
+            offset - info.cd_offset > limits->max_metadata_size)
+            err = ZIPRAND_ERR_LIMIT_EXCEEDED;
+    }
+    range_reader_free(&reader);
+
+    /* strict: the records must fill exactly the size the EOCD claims */
+    if (err == ZIPRAND_OK && archive->options.parse_mode == ZIPRAND_PARSE_STRICT &&
+        offset != info.cd_offset + info.cd_size)
+        err = ZIPRAND_ERR_INVALID_ZIP;
+
+    if (err == ZIPRAND_OK && archive->options.duplicate_policy == ZIPRAND_DUPLICATE_ERROR)
+        err = check_duplicates(entries, num_entries);
+
+    trace_end(
+        ZIPRAND_TRACE_PARSE_CD, archive->io.ctx, NULL, info.cd_offset, info.cd_size, err, start);
+    if (err != ZIPRAND_OK) {
+        arena_free(names);
+        free(entries);
+        return err;
+    }
+
+    install_entries(archive, entries, num_entries, names, size, &info);
+    archive->eocd_hash = eocd_hash;
+    return ZIPRAND_OK;
+}
+
+void ziprand_options_init(ziprand_options_t* options)
+{
+    if (!options)
+        return;
+    memset(options, 0, sizeof(*options));
+    options->duplicate_policy = ZIPRAND_DUPLICATE_FIRST_WINS;
+    options->index_mode = ZIPRAND_INDEX_HASH;
+    options->name_match = ZIPRAND_MATCH_EXACT;
+    options->parse_mode = ZIPRAND_PARSE_LENIENT;
+}
+
+ziprand_error_t ziprand_options_register_method(ziprand_options_t* options,
+                                                uint16_t method,
+                                                const ziprand_decompressor_t* decompressor)
+{
+    if (!options || method == METHOD_STORED || !decompressor || !decompressor->create ||
+        !decompressor->reset || !decompressor->decode || !decompressor->destroy)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    size_t i = 0;
+    while (i < options->method_count && options->methods[i].method != method)
+        i++;
+    if (i == ZIPRAND_MAX_METHODS)
+        return ZIPRAND_ERR_LIMIT_EXCEEDED;
+
+    options->methods[i].method = method;
+    options->methods[i].decompressor = *decompressor;
+    if (i == options->method_count)
+        options->method_count++;
+    return ZIPRAND_OK;
+}
+
+ziprand_archive_t* ziprand_open(const ziprand_io_t* io)
+{
+    return ziprand_open_ex(io, NULL, NULL);
+}
+
+static ziprand_archive_t*
+archive_alloc(const ziprand_io_t* io, const ziprand_options_t* options, ziprand_error_t* error)
+{
+    if (!io || !io->read || !io->get_size) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    ziprand_archive_t* archive = calloc(1, sizeof(ziprand_archive_t));
+    if (!archive) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    archive->io = *io;
+    if (options)
+        archive->options = *options;
+    else
+        ziprand_options_init(&archive->options);
+
+    return archive;
+}
+
+ziprand_archive_t*
+ziprand_open_ex(const ziprand_io_t* io, const ziprand_options_t* options, ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    ziprand_archive_t* archive = archive_alloc(io, options, error);
+    if (!archive)
+        return NULL;
+
+    *error = load_central_directory(archive);
+    if (*error != ZIPRAND_OK) {
+        free(archive);
+        return NULL;
+    }
+
+    return archive;
+}
+
+/* saved index (sidecar) format, all integers little-endian:
+ * header (INDEX_HEADER_SIZE bytes), entry_count fixed-size records, entry_count uint32 entry
+ * indices sorted by name, then every name followed by a NUL byte. The layout lets a
+ * memory-mapped index be used in place (see ziprand_open_with_index_memory). */
+static inline void write_u16_le(uint8_t* p, uint16_t v)
+{
+    p[0] = v & 0xFF;
+    p[1] = (v >> 8) & 0xFF;
+}
+
+static inline void write_u32_le(uint8_t* p, uint32_t v)
+{
+    write_u16_le(p, v & 0xFFFF);
+    write_u16_le(p + 2, v >> 16);
+}
+
+static inline void write_u64_le(uint8_t* p, uint64_t v)
+{
+    write_u32_le(p, v & 0xFFFFFFFF);
+    write_u32_le(p + 4, v >> 32);
+}
+
+static int host_is_little_endian(void)
+{
+    const uint16_t probe = 1;
+    return *(const uint8_t*)&probe == 1;
+}
+
+static ziprand_error_t write_all(ziprand_write_fn write, void* ctx, const void* buffer, size_t size)
+{
+    const uint8_t* p = buffer;
+    while (size > 0) {
+        int64_t written = write(ctx, p, size);
+        if (written <= 0)
+            return ZIPRAND_ERR_IO;
+        p += written;
+        size -= written;
+    }
+    return ZIPRAND_OK;
+}
+
+ziprand_error_t ziprand_index_save(ziprand_archive_t* archive, ziprand_write_fn write, void* ctx)
+{
+    if (!archive || !write)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    uint64_t names_size = 0;
+    for (size_t i = 0; i < archive->entry_count; i++)
+        names_size += archive->entries[i].name_len + 1;
+
+    uint32_t* sorted = archive->sorted_index;
+    if (!sorted) {
+        ziprand_error_t err = sort_entry_indices(archive->entries, archive->entry_count, &sorted);
+        if (err != ZIPRAND_OK)
+            return err;
+    }
+
+    uint8_t header[INDEX_HEADER_SIZE] = {0};
+    memcpy(header, INDEX_MAGIC, 8);
+    write_u32_le(&header[8], INDEX_VERSION);
+    write_u64_le(&header[16], archive->entry_count);
+    write_u64_le(&header[24], names_size);
+    write_u64_le(&header[32], archive->total_size);
+    write_u64_le(&header[40], archive->info.eocd_offset);
+    write_u64_le(&header[48], archive->info.zip64_eocd_offset);
+    write_u64_le(&header[56], archive->info.cd_offset);
+    write_u64_le(&header[64], archive->info.cd_size);
+    write_u64_le(&header[72], archive->info.total_entries);
+    write_u32_le(&header[80], archive->info.disk_number);
+    write_u32_le(&header[84], archive->info.cd_disk_number);
+    write_u16_le(&header[88], archive->info.comment_length);
+    write_u16_le(&header[90], archive->info.is_zip64 ? 1 : 0);
+    write_u64_le(&header[96], archive->eocd_hash);
+
+    ziprand_error_t err = write_all(write, ctx, header, sizeof(header));
+
+    /* records, batched */
+    uint8_t batch[INDEX_RECORD_SIZE * 256];
+    size_t used = 0;
+    for (size_t i = 0; i < archive->entry_count && err == ZIPRAND_OK; i++) {
+        const ziprand_entry_t* entry = &archive->entries[i];
+        uint8_t* record = &batch[used];
+        memset(record, 0, INDEX_RECORD_SIZE);
+        write_u64_le(&record[0], entry->compressed_size);
+        write_u64_le(&record[8], entry->uncompressed_size);
+        write_u64_le(&record[16], entry->offset);
+        write_u64_le(&record[24], entry->data_offset);
+        write_u32_le(&record[32], (uint32_t)entry->name_len);
+        write_u32_le(&record[36], entry->external_attr);
+        write_u16_le(&record[40], entry->version_made_by);
+        write_u16_le(&record[42], entry->compression_method);
+        write_u32_le(&record[44], entry->crc32);
+        write_u16_le(&record[48], entry->flags);
+        write_u16_le(&record[50], entry->mod_time);
+        write_u16_le(&record[52], entry->mod_date);
+        record[54] = entry->aes_strength;
+        record[55] = entry->aes_version;
+        used += INDEX_RECORD_SIZE;
+
+        if (used == sizeof(batch) || i + 1 == archive->entry_count) {
+            err = write_all(write, ctx, batch, used);
+            used = 0;
+        }
+    }
+
+    /* sorted lookup table */
+    for (size_t i = 0; i < archive->entry_count && err == ZIPRAND_OK; i++) {
+        write_u32_le(&batch[used], sorted[i]);
+        used += 4;
+        if (used == sizeof(batch) || i + 1 == archive->entry_count) {
+            err = write_all(write, ctx, batch, used);
+            used = 0;
+        }
+    }
+
+    if (sorted != archive->sorted_index)
+        free(sorted);
+
+    /* names, including their terminators */
+    for (size_t i = 0; i < archive->entry_count && err == ZIPRAND_OK; i++)
+        err = write_all(write, ctx, archive->entries[i].name, archive->entries[i].name_len + 1);
+
+    return err;
+}
+
+/* decoded saved index header */
+typedef struct {
+    uint64_t count;
+    uint64_t names_size;
+    uint64_t archive_size;
+    uint64_t eocd_hash;
+    ziprand_archive_info_t info;
+} index_header_t;
+
+/* validate the header against the index size and decode it */
+static ziprand_error_t
+parse_index_header(const uint8_t* header, uint64_t index_size, index_header_t* out)
+{
+    if (memcmp(header, INDEX_MAGIC, 8) != 0 || read_u32_le(&header[8]) != INDEX_VERSION)
+        return ZIPRAND_ERR_INVALID_INDEX;
+
+    out->count = read_u64_le(&header[16]);
+    out->names_size = read_u64_le(&header[24]);
+
+    uint64_t body_size = index_size - INDEX_HEADER_SIZE;
+    if (out->count > body_size / (INDEX_RECORD_SIZE + 4) ||
+        out->names_size != body_size - out->count * (INDEX_RECORD_SIZE + 4) ||
+        out->count > UINT32_MAX)
+        return ZIPRAND_ERR_INVALID_INDEX;
+
+    ziprand_archive_info_t* info = &out->info;
+    memset(info, 0, sizeof(*info));
+    out->archive_size = read_u64_le(&header[32]);
+    info->eocd_offset = read_u64_le(&header[40]);
+    info->zip64_eocd_offset = read_u64_le(&header[48]);
+    info->cd_offset = read_u64_le(&header[56]);
+    info->cd_size = read_u64_le(&header[64]);
+    info->total_entries = read_u64_le(&header[72]);
+    info->disk_number = read_u32_le(&header[80]);
+    info->cd_disk_number = read_u32_le(&header[84]);
+    info->comment_length = read_u16_le(&header[88]);
+    info->is_zip64 = read_u16_le(&header[90]);
+    out->eocd_hash = read_u64_le(&header[96]);
+    return ZIPRAND_OK;
+}
+
+/* check that the archive is still the one the index was saved from */
+static ziprand_error_t
+check_index_fingerprint(const ziprand_io_t* io, uint64_t size, const index_header_t* header)
+{
+    if (size != header->archive_size)
+        return ZIPRAND_ERR_STALE_INDEX;
+
+    ziprand_archive_info_t info;
+    uint64_t eocd_hash;
+    ziprand_error_t err = get_cd_info(io, size, &info, &eocd_hash);
+    if (err == ZIPRAND_ERR_INVALID_ZIP)
+        return ZIPRAND_ERR_STALE_INDEX;
+    if (err != ZIPRAND_OK)
+        return err;
+
+    if (eocd_hash != header->eocd_hash || info.eocd_offset != header->info.eocd_offset ||
+        info.cd_offset != header->info.cd_offset || info.cd_size != header->info.cd_size ||
+        info.total_entries != header->info.total_entries)
+        return ZIPRAND_ERR_STALE_INDEX;
+
+    return ZIPRAND_OK;
+}
+
+static void parse_index_record(const uint8_t* record, ziprand_entry_t* entry)
+{
+    entry->compressed_size = read_u64_le(&record[0]);
+    entry->uncompressed_size = read_u64_le(&record[8]);
+    entry->offset = read_u64_le(&record[16]);
+    entry->data_offset = read_u64_le(&record[24]);
+    entry->name_len = read_u32_le(&record[32]);
+    entry->external_attr = read_u32_le(&record[36]);
+    entry->version_made_by = read_u16_le(&record[40]);
+    entry->compression_method = read_u16_le(&record[42]);
+    entry->crc32 = read_u32_le(&record[44]);
+    entry->flags = read_u16_le(&record[48]);
+    entry->mod_time = read_u16_le(&record[50]);
+    entry->mod_date = read_u16_le(&record[52]);
+    entry->aes_strength = record[54];
+    entry->aes_version = record[55];
+}
+
+/* decode the sorted lookup table, rejecting out-of-range indices */
+static ziprand_error_t
+parse_sorted_table(const uint8_t* table, size_t count, uint32_t* sorted, size_t first)
+{
+    for (size_t i = 0; i < count; i++) {
+        sorted[i] = read_u32_le(&table[i * 4]);
+        if (sorted[i] >= first)
+            return ZIPRAND_ERR_INVALID_INDEX;
+    }
+    return ZIPRAND_OK;
+}
+
+/* read a saved index through an I/O interface; the archive is only updated on success */
+static ziprand_error_t load_index(ziprand_archive_t* archive, const ziprand_io_t* index_io)
+{
+    int64_t size = io_get_size(&archive->io);
+    int64_t index_size = io_get_size(index_io);
+    if (size < 0 || index_size < 0)
+        return ZIPRAND_ERR_IO;
+    if (index_size < INDEX_HEADER_SIZE)
+        return ZIPRAND_ERR_INVALID_INDEX;
+
+    range_reader_t reader;
+    ziprand_error_t err = range_reader_init(&reader, index_io, 0, index_size, CD_BULK_READ_MAX);
+    if (err != ZIPRAND_OK)
+        return err;
+
+    const uint8_t* raw_header;
+    index_header_t header;
+    err = range_reader_fetch(&reader, 0, INDEX_HEADER_SIZE, &raw_header);
+    if (err == ZIPRAND_OK)
+        err = parse_index_header(raw_header, index_size, &header);
+    if (err == ZIPRAND_OK)
+        err = check_index_fingerprint(&archive->io, size, &header);
+    if (err != ZIPRAND_OK) {
+        range_reader_free(&reader);
+        return err;
+    }
+
+    size_t count = header.count;
+
+    ziprand_entry_t* entries = calloc(count, sizeof(ziprand_entry_t));
+    uint32_t* sorted = NULL;
+    if (archive->options.index_mode == ZIPRAND_INDEX_SORTED)
+        sorted = malloc((count ? count : 1) * sizeof(uint32_t));
+    if ((!entries && count > 0) ||
+        (!sorted && archive->options.index_mode == ZIPRAND_INDEX_SORTED)) {
+        free(entries);
+        range_reader_free(&reader);
+        return ZIPRAND_ERR_NOMEM;
+    }
+
+    /* records, sorted table and names are each read in one sequential pass */
+    uint64_t offset = INDEX_HEADER_SIZE;
+    uint64_t names_total = 0;
+    for (size_t i = 0; i < count && err == ZIPRAND_OK; i++) {
+        const uint8_t* record;
+        err = range_reader_fetch(&reader, offset, INDEX_RECORD_SIZE, &record);
+        if (err != ZIPRAND_OK)
+            break;
+        parse_index_record(record, &entries[i]);
+        names_total += entries[i].name_len + 1;
+        offset += INDEX_RECORD_SIZE;
+    }
+
+    if (err == ZIPRAND_OK && names_total != header.names_size)
+        err = ZIPRAND_ERR_INVALID_INDEX;
+
+    for (size_t i = 0; sorted && i < count && err == ZIPRAND_OK; i++) {
+        const uint8_t* value;
+        err = range_reader_fetch(&reader, offset + i * 4, 4, &value);
+        if (err == ZIPRAND_OK)
+            err = parse_sorted_table(value, 1, &sorted[i], count);
+    }
+    offset += count * 4;
+
+    name_block_t* names = NULL;
+    for (size_t i = 0; i < count && err == ZIPRAND_OK; i++) {
+        ziprand_entry_t* entry = &entries[i];
+        const uint8_t* name;
+        err = range_reader_fetch(&reader, offset, entry->name_len + 1, &name);
+        if (err != ZIPRAND_OK)
+            break;
+        if (name[entry->name_len] != '\0') {
+            err = ZIPRAND_ERR_INVALID_INDEX;
+            break;
+        }
+
+        entry->name = arena_alloc(&names, entry->name_len + 1);
+        if (!entry->name) {
+            err = ZIPRAND_ERR_NOMEM;
+            break;
+        }
+        memcpy(entry->name, name, entry->name_len + 1);
+        offset += entry->name_len + 1;
+    }
+    range_reader_free(&reader);
+
+    if (err == ZIPRAND_OK && archive->options.duplicate_policy == ZIPRAND_DUPLICATE_ERROR)
+        err = check_duplicates(entries, count);
+
+    if (err != ZIPRAND_OK) {
+        arena_free(names);
+        free(entries);
+        free(sorted);
+        return err;
+    }
+
+    install_entries(archive, entries, count, names, size, &header.info);
+    archive->eocd_hash = header.eocd_hash;
+    archive->sorted_index = sorted;
+    return ZIPRAND_OK;
+}
+
+/* use an in-memory index in place: names and (when possible) the sorted table are borrowed */
+static ziprand_error_t
+load_index_memory(ziprand_archive_t* archive, const uint8_t* data, uint64_t index_size)
+{
+    int64_t size = io_get_size(&archive->io);
+    if (size < 0)
+        return ZIPRAND_ERR_IO;
+    if (index_size < INDEX_HEADER_SIZE)
+        return ZIPRAND_ERR_INVALID_INDEX;
+
+    index_header_t header;
+    ziprand_error_t err = parse_index_header(data, index_size, &header);
+    if (err == ZIPRAND_OK)
+        err = check_index_fingerprint(&archive->io, size, &header);
+    if (err != ZIPRAND_OK)
+        return err;
+
+    size_t count = header.count;
+    uint64_t names_size = header.names_size;
+
+    const uint8_t* records = data + INDEX_HEADER_SIZE;
+    const uint8_t* table = records + count * INDEX_RECORD_SIZE;
+    const uint8_t* names = table + count * 4;
+
+    ziprand_entry_t* entries = calloc(count, sizeof(ziprand_entry_t));
+    if (!entries && count > 0)
+        return ZIPRAND_ERR_NOMEM;
+
+    uint64_t names_offset = 0;
+    for (size_t i = 0; i < count; i++) {
+        ziprand_entry_t* entry = &entries[i];
+        parse_index_record(records + i * INDEX_RECORD_SIZE, entry);
+        if (entry->name_len >= names_size - names_offset ||
+            names[names_offset + entry->name_len] != '\0') {
+            free(entries);
+            return ZIPRAND_ERR_INVALID_INDEX;
+        }
+        entry->name = (char*)names + names_offset;
+        names_offset += entry->name_len + 1;
+    }
+
+    if (archive->options.duplicate_policy == ZIPRAND_DUPLICATE_ERROR) {
+        err = check_duplicates(entries, count);
+        if (err != ZIPRAND_OK) {
+            free(entries);
+            return err;
+        }
+    }
+
+    /* the stored table can be used directly on little-endian hosts when it is aligned */
+    uint32_t* sorted = NULL;
+    int borrow_sorted = 0;
+    if (archive->options.index_mode == ZIPRAND_INDEX_SORTED) {
+        if (host_is_little_endian() && ((uintptr_t)table % sizeof(uint32_t)) == 0) {
+            sorted = (uint32_t*)table;
+            borrow_sorted = 1;
+            for (size_t i = 0; i < count && err == ZIPRAND_OK; i++) {
+                if (sorted[i] >= count)
+                    err = ZIPRAND_ERR_INVALID_INDEX;
+            }
+        } else {
+            sorted = malloc((count ? count : 1) * sizeof(uint32_t));
+            err = sorted ? parse_sorted_table(table, count, sorted, count) : ZIPRAND_ERR_NOMEM;
+        }
+
+        if (err != ZIPRAND_OK) {
+            if (!borrow_sorted)
+                free(sorted);
+            free(entries);
+            return err;
+        }
+    }
+
+    install_entries(archive, entries, count, NULL, size, &header.info);
+    archive->eocd_hash = header.eocd_hash;
+    archive->sorted_index = sorted;
+    archive->sorted_index_borrowed = borrow_sorted;
+    return ZIPRAND_OK;
+}
+
+ziprand_archive_t* ziprand_open_with_index(const ziprand_io_t* io,
+                                           const ziprand_io_t* index_io,
+                                           const ziprand_options_t* options,
+                                           ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!index_io || !index_io->read || !index_io->get_size) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    ziprand_archive_t* archive = archive_alloc(io, options, error);
+    if (!archive)
+        return NULL;
+
+    *error = load_index(archive, index_io);
+    if (*error != ZIPRAND_OK) {
+        free(archive);
+        return NULL;
+    }
+
+    return archive;
+}
+
+ziprand_archive_t* ziprand_open_with_index_memory(const ziprand_io_t* io,
+                                                  const void* index_data,
+                                                  size_t index_size,
+                                                  const ziprand_options_t* options,
+                                                  ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!index_data) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    ziprand_archive_t* archive = archive_alloc(io, options, error);
+    if (!archive)
+        return NULL;
+
+    *error = load_index_memory(archive, index_data, index_size);
+    if (*error != ZIPRAND_OK) {
+        free(archive);
+        return NULL;
+    }
+
+    return archive;
+}
+
+/* I/O over a window of the parent archive's source, used by ziprand_open_nested */
+typedef struct {
+    ziprand_io_t parent;
+    uint64_t base;
+    uint64_t size;
+} nested_io_ctx_t;
+
+static int64_t nested_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    nested_io_ctx_t* nctx = ctx;
+    if (offset >= nctx->size)
+        return 0;
+    uint64_t remaining = nctx->size - offset;
+    size_t to_read = size < remaining ? size : remaining;
+    return io_read(&nctx->parent, nctx->base + offset, buffer, to_read);
+}
+
+static int64_t
+nested_read_vectored(void* ctx, uint64_t offset, const ziprand_iovec_t* iov, size_t iovcnt)
+{
+    nested_io_ctx_t* nctx = ctx;
+    if (offset >= nctx->size)
+        return 0;
+
+    /* keep the read inside the window */
+    uint64_t remaining = nctx->size - offset;
+    ziprand_iovec_t* trimmed = NULL;
+    uint64_t total = 0;
+    size_t count = 0;
+    while (count < iovcnt && total < remaining) {
+        total += iov[count].len;
+        count++;
+    }
+    if (total > remaining) {
+        trimmed = malloc(count * sizeof(ziprand_iovec_t));
+        if (!trimmed)
+            return -1;
+        memcpy(trimmed, iov, count * sizeof(ziprand_iovec_t));
+        trimmed[count - 1].len -= total - remaining;
+        iov = trimmed;
+    }
+
+    int64_t result = io_read_vectored(&nctx->parent, nctx->base + offset, iov, count);
+    free(trimmed);
+    return result;
+}
+
+static int64_t nested_size(void* ctx)
+{
+    nested_io_ctx_t* nctx = ctx;
+    return nctx->size;
+}
+
+static ziprand_error_t
+nested_read_buffer(void* ctx, uint64_t offset, size_t size, ziprand_buffer_t* buffer)
+{
+    nested_io_ctx_t* nctx = ctx;
+    uint64_t remaining = offset < nctx->size ? nctx->size - offset : 0;
+    if (size > remaining)
+        size = remaining;
+    return io_read_buffer(&nctx->parent, nctx->base + offset, size, buffer);
+}
+
+static ziprand_error_t
+nested_read_ranges(void* ctx, const ziprand_read_range_t* ranges, size_t count)
+{
+    nested_io_ctx_t* nctx = ctx;
+    ziprand_read_range_t* shifted = malloc((count ? count : 1) * sizeof(ziprand_read_range_t));
+    if (!shifted)
+        return ZIPRAND_ERR_NOMEM;
+
+    for (size_t i = 0; i < count; i++) {
+        if (ranges[i].offset > nctx->size || ranges[i].size > nctx->size - ranges[i].offset) {
+            free(shifted);
+            return ZIPRAND_ERR_IO;
+        }
+        shifted[i] = ranges[i];
+        shifted[i].offset += nctx->base;
+    }
+
+    ziprand_error_t err = io_read_ranges(&nctx->parent, shifted, count);
+    free(shifted);
+    return err;
+}
+
+static void nested_prefetch(void* ctx, const ziprand_range_t* ranges, size_t count)
+{
+    nested_io_ctx_t* nctx = ctx;
+    ziprand_range_t* shifted = malloc((count ? count : 1) * sizeof(ziprand_range_t));
+    if (!shifted)
+        return;
+
+    /* hints outside the window are dropped */
+    size_t used = 0;
+    for (size_t i = 0; i < count; i++) {
+        if (ranges[i].offset >= nctx->size)
+            continue;
+        shifted[used].offset = nctx->base + ranges[i].offset;
+        shifted[used].size = ranges[i].size < nctx->size - ranges[i].offset
+                                 ? ranges[i].size
+                                 : nctx->size - ranges[i].offset;
+        used++;
+    }
+    if (used > 0)
+        io_prefetch(&nctx->parent, shifted, used);
+    free(shifted);
+}
+
+static void nested_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    nested_io_ctx_t* nctx = ctx;
+    ziprand_io_capabilities(&nctx->parent, caps);
+}
+
+static void nested_close(void* ctx)
+{
+    free(ctx);
+}
+
+ziprand_archive_t* ziprand_open_nested(ziprand_archive_t* parent,
+                                       const ziprand_entry_t* entry,
+                                       const ziprand_options_t* options,
+                                       ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!parent || !entry) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+    if (entry->flags & FLAG_ENCRYPTED) {
+        *error = ZIPRAND_ERR_ENCRYPTED;
+        return NULL;
+    }
+    if (entry->compression_method != 0) {
+        *error = ZIPRAND_ERR_COMPRESSED;
+        return NULL;
+    }
+
+    ziprand_entry_t* mutable_entry = (ziprand_entry_t*)entry;
+    if (mutable_entry->data_offset == 0) {
+        int strict = parent->options.parse_mode == ZIPRAND_PARSE_STRICT;
+        *error = get_data_offset(&parent->io, mutable_entry, strict);
+        if (*error != ZIPRAND_OK)
+            return NULL;
+    }
+
+    /* the window and the mapped pointer come from the parent's directory, so they must
+     * describe stored data that lies within the parent */
+    if (entry->compressed_size != entry->uncompressed_size ||
+        entry->uncompressed_size > parent->total_size ||
+        entry->data_offset > parent->total_size - entry->uncompressed_size) {
+        *error = ZIPRAND_ERR_INVALID_ZIP;
+        return NULL;
+    }
+
+    nested_io_ctx_t* nctx = malloc(sizeof(nested_io_ctx_t));
+    if (!nctx) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+    nctx->parent = parent->io;
+    nctx->base = entry->data_offset;
+    nctx->size = entry->uncompressed_size;
+
+    ziprand_io_t io = {
+        .ctx = nctx,
+        .read = nested_read,
+        .get_size = nested_size,
+        .close = nested_close,
+        .prefetch = parent->io.prefetch ? nested_prefetch : NULL,
+        .read_vectored = parent->io.read_vectored ? nested_read_vectored : NULL,
+        .read_buffer = parent->io.read_buffer ? nested_read_buffer : NULL,
+        .mapped = parent->io.mapped ? (const uint8_t*)parent->io.mapped + nctx->base : NULL,
+        .capabilities = nested_capabilities,
+        .read_ranges = parent->io.read_ranges ? nested_read_ranges : NULL,
+    };
+
+    ziprand_archive_t* archive = ziprand_open_ex(&io, options, error);
+    if (!archive)
+        free(nctx);
+    return archive;
+}
+
+ziprand_error_t ziprand_refresh(ziprand_archive_t* archive)
+{
+    if (!archive)
+        return ZIPRAND_ERR_INVALID_PARAM;
+    return load_central_directory(archive);
+}
+
+void ziprand_close(ziprand_archive_t* archive)
+{
+    if (!archive)
+        return;
+
+    if (archive->io.close)
+        archive->io.close(archive->io.ctx);
+
+    arena_free(archive->names);
+    free(archive->entries);
+    free(archive->name_index);
+    free(archive->nocase_index);
+    if (!archive->sorted_index_borrowed)
+        free(archive->sorted_index);
+    free_tree(archive);
+    free(archive);
+}
+
+ziprand_error_t ziprand_get_archive_info(ziprand_archive_t* archive, ziprand_archive_info_t* info)
+{
+    if (!archive || !info)
+        return ZIPRAND_ERR_INVALID_PARAM;
+    *info = archive->info;
+    return ZIPRAND_OK;
+}
+
+ziprand_error_t ziprand_read_archive_info(const ziprand_io_t* io, ziprand_archive_info_t* info)
+{
+    if (!io || !io->read || !io->get_size || !info)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    int64_t size = io_get_size(io);
+    if (size < 0)
+        return ZIPRAND_ERR_IO;
+
+    return get_cd_info(io, size, info, NULL);
+}
+
+int64_t ziprand_peek_entry_count(const ziprand_io_t* io)
+{
+    ziprand_archive_info_t info;
+    ziprand_error_t err = ziprand_read_archive_info(io, &info);
+    if (err != ZIPRAND_OK)
+        return err;
+    return info.total_entries > INT64_MAX ? ZIPRAND_ERR_INVALID_ZIP : (int64_t)info.total_entries;
+}
+
+ziprand_iter_t* ziprand_iter_open(const ziprand_io_t* io, ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!io || !io->read || !io->get_size) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    int64_t size = io_get_size(io);
+    if (size < 0) {
+        *error = ZIPRAND_ERR_IO;
+        return NULL;
+    }
+
+    ziprand_iter_t* iter = calloc(1, sizeof(ziprand_iter_t));
+    if (!iter) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    iter->io = *io;
+    *error = get_cd_info(&iter->io, size, &iter->info, NULL);
+    if (*error != ZIPRAND_OK) {
+        free(iter);
+        return NULL;
+    }
+
+    *error = range_reader_init(
+        &iter->reader, &iter->io, iter->info.cd_offset, iter->info.cd_size, CD_CHUNK_SIZE);
+    if (*error != ZIPRAND_OK) {
+        free(iter);
+        return NULL;
+    }
+
+    iter->offset = iter->info.cd_offset;
+    iter->remaining = iter->info.total_entries;
+    return iter;
+}
+
+ziprand_error_t ziprand_iter_next(ziprand_iter_t* iter, const ziprand_entry_t** entry)
+{
+    if (!iter || !entry)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    free(iter->current.name);
+    memset(&iter->current, 0, sizeof(iter->current));
+    *entry = NULL;
+
+    if (iter->remaining == 0)
+        return ZIPRAND_OK;
+
+    ziprand_error_t err = read_cd_entry(&iter->reader, &iter->offset, &iter->current, NULL, NULL);
+    if (err != ZIPRAND_OK) {
+        memset(&iter->current, 0, sizeof(iter->current));
+        iter->remaining = 0;
+        return err;
+    }
+
+    iter->remaining--;
+    *entry = &iter->current;
+    return ZIPRAND_OK;
+}
+
+void ziprand_iter_close(ziprand_iter_t* iter)
+{
+    if (!iter)
+        return;
+    free(iter->current.name);
+    range_reader_free(&iter->reader);
+    free(iter);
+}
+
+int64_t ziprand_get_entry_count(ziprand_archive_t* archive)
+{
+    return archive ? (int64_t)archive->entry_count : -1;
+}
+
+const ziprand_entry_t* ziprand_get_entry_by_index(ziprand_archive_t* archive, size_t index)
+{
+    if (!archive || index >= archive->entry_count)
+        return NULL;
+    return &archive->entries[index];
+}
+
+const ziprand_entry_t* ziprand_find_entry(ziprand_archive_t* archive, const char* name)
+{
+    if (!name)
+        return NULL;
+    return ziprand_find_entry_raw(archive, name, strlen(name));
+}
+
+static int entry_name_equals(const ziprand_entry_t* entry, const void* name, size_t name_len)
+{
+    return entry->name_len == name_len && memcmp(entry->name, name, name_len) == 0;
+}
+
+/* build the name -> entry hash table, honouring the duplicate policy */
+static ziprand_error_t build_name_index(ziprand_archive_t* archive)
+{
+    size_t capacity = 16;
+    while (capacity < archive->entry_count * 2)
+        capacity <<= 1;
+
+    size_t* slots = calloc(capacity, sizeof(size_t));
+    if (!slots)
+        return ZIPRAND_ERR_NOMEM;
+
+    size_t mask = capacity - 1;
+    int last_wins = archive->options.duplicate_policy == ZIPRAND_DUPLICATE_LAST_WINS;
+
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        const ziprand_entry_t* entry = &archive->entries[i];
+        size_t slot = hash_bytes(entry->name, entry->name_len) & mask;
+
+        while (slots[slot] != 0) {
+            if (entry_name_equals(&archive->entries[slots[slot] - 1], entry->name, entry->name_len))
+                break;
+            slot = (slot + 1) & mask;
+        }
+
+        if (slots[slot] == 0 || last_wins)
+            slots[slot] = i + 1;
+    }
+
+    archive->name_index = slots;
+    archive->name_index_mask = mask;
+    return ZIPRAND_OK;
+}
+
+static const ziprand_entry_t*
+lookup_hashed(ziprand_archive_t* archive, const void* name, size_t name_len)
+{
+    size_t slot = hash_bytes(name, name_len) & archive->name_index_mask;
+    while (archive->name_index[slot] != 0) {
+        const ziprand_entry_t* entry = &archive->entries[archive->name_index[slot] - 1];
+        if (entry_name_equals(entry, name, name_len))
+            return entry;
+        slot = (slot + 1) & archive->name_index_mask;
+    }
+    return NULL;
+}
+
+static int entry_name_equals_nocase(const ziprand_entry_t* entry, const void* name, size_t name_len)
+{
+    if (entry->name_len != name_len)
+        return 0;
+    const char* p = name;
+    for (size_t i = 0; i < name_len; i++) {
+        if (ascii_tolower(entry->name[i]) != ascii_tolower(p[i]))
+            return 0;
+    }
+    return 1;
+}
+
+/* secondary index for case-insensitive lookups, built on the first miss of an exact lookup */
+static ziprand_error_t build_nocase_index(ziprand_archive_t* archive)
+{
+    size_t capacity = 16;
+    while (capacity < archive->entry_count * 2)
+        capacity <<= 1;
+
+    size_t* slots = calloc(capacity, sizeof(size_t));
+    if (!slots)
+        return ZIPRAND_ERR_NOMEM;
+
+    size_t mask = capacity - 1;
+    int last_wins = archive->options.duplicate_policy == ZIPRAND_DUPLICATE_LAST_WINS;
+
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        const ziprand_entry_t* entry = &archive->entries[i];
+        size_t slot = hash_bytes_nocase(entry->name, entry->name_len) & mask;
+
+        while (slots[slot] != 0) {
+            const ziprand_entry_t* other = &archive->entries[slots[slot] - 1];
+            if (entry_name_equals_nocase(other, entry->name, entry->name_len))
+                break;
+            slot = (slot + 1) & mask;
+        }
+
+        if (slots[slot] == 0 || last_wins)
+            slots[slot] = i + 1;
+    }
+
+    archive->nocase_index = slots;
+    archive->nocase_index_mask = mask;
+    return ZIPRAND_OK;
+}
+
+static const ziprand_entry_t*
+lookup_nocase(ziprand_archive_t* archive, const void* name, size_t name_len)
+{
+    if (!archive->nocase_index && archive->options.index_mode != ZIPRAND_INDEX_NONE)
+        build_nocase_index(archive);
+
+    if (!archive->nocase_index) {
+        int last_wins = archive->options.duplicate_policy == ZIPRAND_DUPLICATE_LAST_WINS;
+        for (size_t n = 0; n < archive->entry_count; n++) {
+            size_t i = last_wins ? archive->entry_count - 1 - n : n;
+            if (entry_name_equals_nocase(&archive->entries[i], name, name_len))
+                return &archive->entries[i];
+        }
+        return NULL;
+    }
+
+    size_t slot = hash_bytes_nocase(name, name_len) & archive->nocase_index_mask;
+    while (archive->nocase_index[slot] != 0) {
+        const ziprand_entry_t* entry = &archive->entries[archive->nocase_index[slot] - 1];
+        if (entry_name_equals_nocase(entry, name, name_len))
+            return entry;
+        slot = (slot + 1) & archive->nocase_index_mask;
+    }
+    return NULL;
+}
+
+static const ziprand_entry_t*
+lookup_sorted(ziprand_archive_t* archive, const void* name, size_t name_len)
+{
+    int last_wins = archive->options.duplicate_policy == ZIPRAND_DUPLICATE_LAST_WINS;
+    size_t lo = 0;
+    size_t hi = archive->entry_count;
+
+    /* first position whose name is greater than (last wins) or not less than (first wins) */
+    while (lo < hi) {
+        size_t mid = lo + (hi - lo) / 2;
+        const ziprand_entry_t* entry = &archive->entries[archive->sorted_index[mid]];
+        int cmp = compare_names(entry->name, entry->name_len, name, name_len);
+        if (cmp < 0 || (last_wins && cmp == 0))
+            lo = mid + 1;
+        else
+            hi = mid;
+    }
+
+    if (last_wins) {
+        if (lo == 0)
+            return NULL;
+        lo--;
+    }
+    if (lo >= archive->entry_count)
+        return NULL;
+
+    const ziprand_entry_t* entry = &archive->entries[archive->sorted_index[lo]];
+    return entry_name_equals(entry, name, name_len) ? entry : NULL;
+}
+
+static const ziprand_entry_t*
+lookup_linear(ziprand_archive_t* archive, const void* name, size_t name_len)
+{
+    if (archive->options.duplicate_policy == ZIPRAND_DUPLICATE_LAST_WINS) {
+        for (size_t i = archive->entry_count; i > 0; i--) {
+            if (entry_name_equals(&archive->entries[i - 1], name, name_len))
+                return &archive->entries[i - 1];
+        }
+        return NULL;
+    }
+
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        if (entry_name_equals(&archive->entries[i], name, name_len))
+            return &archive->entries[i];
+    }
+    return NULL;
+}
+
+static const ziprand_entry_t*
+lookup_exact(ziprand_archive_t* archive, const void* name, size_t name_len)
+{
+    switch (archive->options.index_mode) {
+    case ZIPRAND_INDEX_HASH:
+        if (archive->name_index || build_name_index(archive) == ZIPRAND_OK)
+            return lookup_hashed(archive, name, name_len);
+        break;
+    case ZIPRAND_INDEX_SORTED:
+        if (!archive->sorted_index)
+            sort_entry_indices(archive->entries, archive->entry_count, &archive->sorted_index);
+        if (archive->sorted_index)
+            return lookup_sorted(archive, name, name_len);
+        break;
+    default:
+        break;
+    }
+
+    /* no index (disabled or out of memory): fall back to a linear scan */
+    return lookup_linear(archive, name, name_len);
+}
+
+const ziprand_entry_t*
+ziprand_find_entry_raw(ziprand_archive_t* archive, const void* name, size_t name_len)
+{
+    if (!archive || !name)
+        return NULL;
+
+    /* an exact match always wins over one that differs only in case */
+    const ziprand_entry_t* entry = lookup_exact(archive, name, name_len);
+    if (!entry && archive->options.name_match == ZIPRAND_MATCH_IGNORE_CASE)
+        entry = lookup_nocase(archive, name, name_len);
+    return entry;
+}
+
+int ziprand_contains(ziprand_archive_t* archive, const char* name)
+{
+    if (!archive || !name)
+        return ZIPRAND_ERR_INVALID_PARAM;
+    return ziprand_find_entry_raw(archive, name, strlen(name)) != NULL;
+}
+
+size_t ziprand_find_all_by_name(ziprand_archive_t* archive,
+                                const char* name,
+                                const ziprand_entry_t** entries,
+                                size_t max_entries)
+{
+    if (!archive || !name)
+        return 0;
+
+    size_t name_len = strlen(name);
+    size_t found = 0;
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        if (!entry_name_equals(&archive->entries[i], name, name_len))
+            continue;
+        if (entries && found < max_entries)
+            entries[found] = &archive->entries[i];
+        found++;
+    }
+    return found;
+}
+
+static int ascii_casecmp(const char* a, size_t a_len, const char* b)
+{
+    size_t b_len = strlen(b);
+    if (a_len != b_len)
+        return 1;
+    for (size_t i = 0; i < a_len; i++) {
+        if (ascii_tolower(a[i]) != ascii_tolower(b[i]))
+            return 1;
+    }
+    return 0;
+}
+
+int ziprand_entry_matches_filter(const ziprand_entry_t* entry,
+                                 const ziprand_entry_filter_t* filter)
+{
+    if (!entry)
+        return 0;
+    if (!filter)
+        return 1;
+
+    const char* name = entry->name;
+    size_t len = entry->name_len;
+    int is_dir = len > 0 && name[len - 1] == '/';
+
+    /* last path component, ignoring a trailing slash */
+    size_t end = is_dir ? len - 1 : len;
+    size_t base = end;
+    while (base > 0 && name[base - 1] != '/')
+        base--;
+    const char* basename = name + base;
+    size_t basename_len = end - base;
+
+    if (filter->flags & ZIPRAND_FILTER_MACOSX) {
+        if ((len >= 8 && memcmp(name, "__MACOSX", 8) == 0 && (len == 8 || name[8] == '/')) ||
+            (basename_len >= 2 && basename[0] == '.' && basename[1] == '_'))
+            return 0;
+    }
+
+    if ((filter->flags & ZIPRAND_FILTER_DS_STORE) && basename_len == 9 &&
+        memcmp(basename, ".DS_Store", 9) == 0)
+        return 0;
+
+    if ((filter->flags & ZIPRAND_FILTER_THUMBS_DB) &&
+        (ascii_casecmp(basename, basename_len, "thumbs.db") == 0 ||
+         ascii_casecmp(basename, basename_len, "desktop.ini") == 0))
+        return 0;
+
+    if ((filter->flags & ZIPRAND_FILTER_DIRECTORIES) && is_dir && entry->uncompressed_size == 0)
+        return 0;
+
+    if (filter->predicate && !filter->predicate(entry, filter->predicate_ctx))
+        return 0;
+
+    return 1;
+}
+
+size_t ziprand_list_entries_filtered(ziprand_archive_t* archive,
+                                     const ziprand_entry_filter_t* filter,
+                                     const ziprand_entry_t** entries,
+                                     size_t max_entries)
+{
+    if (!archive)
+        return 0;
+
+    size_t found = 0;
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        if (!ziprand_entry_matches_filter(&archive->entries[i], filter))
+            continue;
+        if (entries && found < max_entries)
+            entries[found] = &archive->entries[i];
+        found++;
+    }
+    return found;
+}
+
+static int compare_entry_offsets(const void* a, const void* b)
+{
+    const ziprand_entry_t* ea = *(const ziprand_entry_t* const*)a;
+    const ziprand_entry_t* eb = *(const ziprand_entry_t* const*)b;
+    return (ea->offset > eb->offset) - (ea->offset < eb->offset);
+}
+
+ziprand_error_t ziprand_get_stats(ziprand_archive_t* archive, ziprand_stats_t* stats)
+{
+    if (!archive || !stats)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    memset(stats, 0, sizeof(*stats));
+    stats->entry_count = archive->entry_count;
+
+    ziprand_entry_t** by_offset = malloc((archive->entry_count + 1) * sizeof(ziprand_entry_t*));
+    if (!by_offset)
+        return ZIPRAND_ERR_NOMEM;
+
+    int strict = archive->options.parse_mode == ZIPRAND_PARSE_STRICT;
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        ziprand_entry_t* entry = &archive->entries[i];
+        if (entry->data_offset == 0) {
+            ziprand_error_t err = get_data_offset(&archive->io, entry, strict);
+            if (err != ZIPRAND_OK) {
+                free(by_offset);
+                return err;
+            }
+        }
+        by_offset[i] = entry;
+
+        if (entry->name_len > 0 && entry->name[entry->name_len - 1] == '/')
+            stats->directory_count++;
+        if (entry->compression_method == 0)
+            stats->stored_count++;
+        else if (entry->compression_method == 8)
+            stats->deflated_count++;
+        else
+            stats->other_method_count++;
+        if (entry->flags & FLAG_ENCRYPTED)
+            stats->encrypted_count++;
+
+        stats->total_compressed_size += entry->compressed_size;
+        stats->total_uncompressed_size += entry->uncompressed_size;
+        if (!stats->largest_entry ||
+            entry->uncompressed_size > stats->largest_entry->uncompressed_size)
+            stats->largest_entry = entry;
+    }
+
+    /* bytes between the end of one entry and the start of the next, up to the CD */
+    qsort(by_offset, archive->entry_count, sizeof(ziprand_entry_t*), compare_entry_offsets);
+    uint64_t covered = 0;
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        const ziprand_entry_t* entry = by_offset[i];
+        uint64_t end = entry->data_offset + entry->compressed_size;
+        if (entry->offset > covered)
+            stats->gap_bytes += entry->offset - covered;
+        if (end > covered)
+            covered = end;
+    }
+    if (archive->info.cd_offset > covered)
+        stats->gap_bytes += archive->info.cd_offset - covered;
+
+    free(by_offset);
+    return ZIPRAND_OK;
+}
+
+/* byte range of an entry (entry != NULL) or ZIP structure, for overlap detection */
+typedef struct {
+    uint64_t start;
+    uint64_t end;
+    const ziprand_entry_t* entry;
+    ziprand_region_t region;
+} region_range_t;
+
+static int compare_region_ranges(const void* a, const void* b)
+{
+    const region_range_t* ra = a;
+    const region_range_t* rb = b;
+    if (ra->start != rb->start)
+        return ra->start < rb->start ? -1 : 1;
+    return (ra->end > rb->end) - (ra->end < rb->end);
+}
+
+int64_t ziprand_detect_overlaps(ziprand_archive_t* archive,
+                                ziprand_overlap_t* overlaps,
+                                size_t max_overlaps)
+{
+    if (!archive)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    int strict = archive->options.parse_mode == ZIPRAND_PARSE_STRICT;
+    size_t count = archive->entry_count + 2;
+    region_range_t* ranges = malloc(count * sizeof(region_range_t));
+    if (!ranges)
+        return ZIPRAND_ERR_NOMEM;
+
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        ziprand_entry_t* entry = &archive->entries[i];
+        if (entry->data_offset == 0) {
+            ziprand_error_t err = get_data_offset(&archive->io, entry, strict);
+            if (err != ZIPRAND_OK) {
+                free(ranges);
+                return err;
+            }
+        }
+        ranges[i].start = entry->offset;
+        ranges[i].end = entry->data_offset + entry->compressed_size;
+        ranges[i].entry = entry;
+        ranges[i].region = ZIPRAND_REGION_ENTRY;
+    }
+
+    const ziprand_archive_info_t* info = &archive->info;
+    region_range_t* cd = &ranges[archive->entry_count];
+    cd->start = info->cd_offset;
+    cd->end = info->cd_offset + info->cd_size;
+    cd->entry = NULL;
+    cd->region = ZIPRAND_REGION_CENTRAL_DIRECTORY;
+
+    region_range_t* eocd = &ranges[archive->entry_count + 1];
+    eocd->start = info->is_zip64 ? info->zip64_eocd_offset : info->eocd_offset;
+    eocd->end = info->eocd_offset + 22 + info->comment_length;
+    eocd->entry = NULL;
+    eocd->region = ZIPRAND_REGION_EOCD;
+
+    qsort(ranges, count, sizeof(region_range_t), compare_region_ranges);
+
+    /* sweep in start order, comparing against the range that reaches furthest so far */
+    int64_t found = 0;
+    const region_range_t* reach = NULL;
+    for (size_t i = 0; i < count; i++) {
+        const region_range_t* range = &ranges[i];
+        if (reach && range->start < reach->end && (range->entry || reach->entry)) {
+            if (overlaps && (size_t)found < max_overlaps) {
+                ziprand_overlap_t* overlap = &overlaps[found];
+                overlap->entry = range->entry ? range->entry : reach->entry;
+                overlap->region = range->entry ? reach->region : range->region;
+                overlap->other = range->entry ? reach->entry : NULL;
+            }
+            found++;
+        }
+        if (!reach || range->end > reach->end)
+            reach = range;
+    }
+
+    free(ranges);
+    return found;
+}
+
+/* match a [...] class at p against ch; returns the position after the class, or NULL if the
+ * class is unterminated */
+static const char* glob_match_class(const char* p, const char* pe, unsigned char ch, int* matched)
+{
+    int negate = p < pe && (*p == '!' || *p == '^');
+    if (negate)
+        p++;
+
+    *matched = 0;
+    const char* start = p;
+    while (p < pe && (*p != ']' || p == start)) {
+        unsigned char lo = *p++;
+        if (lo == '\\' && p < pe)
+            lo = *p++;
+        unsigned char hi = lo;
+        if (p + 1 < pe && *p == '-' && p[1] != ']') {
+            hi = p[1];
+            p += 2;
+            if (hi == '\\' && p < pe)
+                hi = *p++;
+        }
+        if (ch >= lo && ch <= hi)
+            *matched = 1;
+    }
+
+    if (p >= pe)
+        return NULL;
+    if (negate)
+        *matched = !*matched;
+    return p + 1;
+}
+
+/* match the single-character token at p ('?', a class or a literal) against ch; returns the
+ * position after the token */
+static const char* glob_match_char(const char* p, const char* pe, unsigned char ch, int* matched)
+{
+    if (*p == '?') {
+        *matched = ch != '/';
+        return p + 1;
+    }
+    if (*p == '[') {
+        const char* next = glob_match_class(p + 1, pe, ch, matched);
+        if (next) {
+            *matched = *matched && ch != '/';
+            return next;
+        }
+        /* unterminated class: '[' is a literal */
+        *matched = ch == '[';
+        return p + 1;
+    }
+    if (*p == '\\' && p + 1 < pe)
+        p++;
+    *matched = ch == (unsigned char)*p;
+    return p + 1;
+}
+
+/* glob matching: '*', '?' and classes stay within a path component, '**' crosses them.
+ * Names come from the archive, so instead of backtracking the pattern is applied a token at a
+ * time to the set of name positions it can have reached, which bounds the work by the pattern
+ * length times the name length */
+static int glob_match(const char* p, const char* pe, const char* s, const char* se)
+{
+    size_t n = se - s;
+    uint8_t small[GLOB_STACK_SIZE];
+    uint8_t* reach = n < sizeof(small) ? small : malloc(n + 1);
+    if (!reach)
+        return 0;
+
+    /* reach[i]: the pattern so far can match the first i bytes of the name */
+    memset(reach, 0, n + 1);
+    reach[0] = 1;
+    int any = 1;
+    while (p < pe && any) {
+        int seen = 0;
+        if (*p == '*' && p + 1 < pe && p[1] == '*' && p + 2 < pe && p[2] == '/') {
+            /* "**" + "/" matches zero or more whole components */
+            for (size_t i = 0; i <= n; i++) {
+                int here = reach[i];
+                if (i > 0 && s[i - 1] == '/' && seen)
+                    reach[i] = 1;
+                seen |= here;
+            }
+            p += 3;
+        } else if (*p == '*' && p + 1 < pe && p[1] == '*') {
+            for (size_t i = 0; i <= n; i++) {
+                seen |= reach[i];
+                reach[i] = seen;
+            }
+            p += 2;
+        } else if (*p == '*') {
+            for (size_t i = 0; i <= n; i++) {
+                if (i > 0 && s[i - 1] == '/')
+                    seen = 0;
+                seen |= reach[i];
+                reach[i] = seen;
+            }
+            p++;
+        } else {
+            int matched;
+            const char* next = glob_match_char(p, pe, 0, &matched);
+            any = 0;
+            for (size_t i = n; i > 0; i--) {
+                matched = 0;
+                if (reach[i - 1])
+                    glob_match_char(p, pe, s[i - 1], &matched);
+                reach[i] = matched;
+                any |= matched;
+            }
+            reach[0] = 0;
+            p = next;
+        }
+    }
+
+    int result = reach[n];
+    if (reach != small)
+        free(reach);
+    return result;
+}
+
+int ziprand_entry_matches_glob(const ziprand_entry_t* entry, const char* pattern)
+{
+    if (!entry || !pattern)
+        return 0;
+    return glob_match(
+        pattern, pattern + strlen(pattern), entry->name, entry->name + entry->name_len);
+}
+
+size_t ziprand_find_glob(ziprand_archive_t* archive,
+                         const char* pattern,
+                         const ziprand_entry_t** entries,
+                         size_t max_entries)
+{
+    if (!archive || !pattern)
+        return 0;
+
+    const char* pattern_end = pattern + strlen(pattern);
+    size_t found = 0;
+    for (size_t i = 0; i < archive->entry_count; i++) {
+        const ziprand_entry_t* entry = &archive->entries[i];
+        if (!glob_match(pattern, pattern_end, entry->name, entry->name + entry->name_len))
+            continue;
+        if (entries && found < max_entries)
+            entries[found] = entry;
+        found++;
+    }
+    return found;
+}
+
+/* nodes are fixed-size arena allocations; the size keeps them pointer-aligned */
+static ziprand_tree_node_t*
+tree_new_node(ziprand_archive_t* archive, ziprand_tree_node_t* parent, const char* name, size_t len)
+{
+    ziprand_tree_node_t* node =
+        (ziprand_tree_node_t*)arena_alloc(&archive->tree_nodes, sizeof(ziprand_tree_node_t));
+    char* copy = arena_alloc(&archive->tree_names, len + 1);
+    if (!node || !copy)
+        return NULL;
+
+    memcpy(copy, name, len);
+    copy[len] = '\0';
+    memset(node, 0, sizeof(*node));
+    node->name = copy;
+    node->parent = parent;
+    return node;
+}
+
+/* build the tree from names in sorted order: every directory's contents are then contiguous,
+ * so the path of the previous entry (kept in stack) is all that is needed to place the next */
+static ziprand_error_t build_tree(ziprand_archive_t* archive)
+{
+    uint32_t* sorted = archive->sorted_index;
+    if (!sorted) {
+        ziprand_error_t err = sort_entry_indices(archive->entries, archive->entry_count, &sorted);
+        if (err != ZIPRAND_OK)
+            return err;
+    }
+
+    ziprand_error_t err = ZIPRAND_OK;
+    size_t stack_capacity = 16;
+    ziprand_tree_node_t** stack = calloc(stack_capacity, sizeof(*stack));
+    ziprand_tree_node_t* root = tree_new_node(archive, NULL, "", 0);
+    if (!stack || !root)
+        err = ZIPRAND_ERR_NOMEM;
+    else
+        root->is_dir = 1;
+
+    for (size_t i = 0; i < archive->entry_count && err == ZIPRAND_OK; i++) {
+        const ziprand_entry_t* entry = &archive->entries[sorted[i]];
+        size_t len = entry->name_len;
+        int is_dir = len > 0 && entry->name[len - 1] == '/';
+        if (is_dir)
+            len--;
+        if (len == 0)
+            continue;
+
+        ziprand_tree_node_t* node = root;
+        size_t depth = 0;
+        size_t start = 0;
+        while (start <= len) {
+            const char* component = entry->name + start;
+            const char* slash = memchr(component, '/', len - start);
+            size_t component_len = slash ? (size_t)(slash - component) : len - start;
+
+            if (depth + 1 >= stack_capacity) {
+                ziprand_tree_node_t** grown =
+                    realloc(stack, stack_capacity * 2 * sizeof(*stack));
+                if (!grown) {
+                    err = ZIPRAND_ERR_NOMEM;
+                    break;
+                }
+                memset(grown + stack_capacity, 0, stack_capacity * sizeof(*stack));
+                stack = grown;
+                stack_capacity *= 2;
+            }
+
+            /* stack[depth + 1] is the most recently added child of node, if it has one */
+            ziprand_tree_node_t* last = stack[depth + 1];
+            if (last && last->parent != node)
+                last = NULL;
+
+            ziprand_tree_node_t* child = last;
+            if (!child || strlen(child->name) != component_len ||
+                memcmp(child->name, component, component_len) != 0) {
+                child = tree_new_node(archive, node, component, component_len);
+                if (!child) {
+                    err = ZIPRAND_ERR_NOMEM;
+                    break;
+                }
+                if (last)
+                    last->next_sibling = child;
+                else
+                    node->first_child = child;
+                stack[depth + 1] = child;
+            }
+
+            node = child;
+            depth++;
+            start += component_len + 1;
+            if (start <= len)
+                node->is_dir = 1;
+        }
+
+        if (err != ZIPRAND_OK)
+            break;
+        if (is_dir)
+            node->is_dir = 1;
+        if (!node->entry || archive->options.duplicate_policy == ZIPRAND_DUPLICATE_LAST_WINS)
+            node->entry = entry;
+    }
+
+    free(stack);
+    if (sorted != archive->sorted_index)
+        free(sorted);
+
+    if (err != ZIPRAND_OK) {
+        free_tree(archive);
+        return err;
+    }
+
+    archive->tree = root;
+    return ZIPRAND_OK;
+}
+
+const ziprand_tree_node_t* ziprand_tree_find(ziprand_archive_t* archive, const char* path)
+{
+    if (!archive || !path)
+        return NULL;
+
+    if (!archive->tree && build_tree(archive) != ZIPRAND_OK)
+        return NULL;
+
+    const ziprand_tree_node_t* node = archive->tree;
+    size_t len = strlen(path);
+    if (len > 0 && path[len - 1] == '/')
+        len--;
+
+    size_t start = 0;
+    while (node && start < len) {
+        const char* component = path + start;
+        const char* slash = memchr(component, '/', len - start);
+        size_t component_len = slash ? (size_t)(slash - component) : len - start;
+
+        const ziprand_tree_node_t* child = node->first_child;
+        while (child && (strlen(child->name) != component_len ||
+                         memcmp(child->name, component, component_len) != 0))
+            child = child->next_sibling;
+
+        node = child;
+        start += component_len + 1;
+    }
+    return node;
+}
+
+int64_t ziprand_read_dir(ziprand_archive_t* archive,
+                         const char* path,
+                         const ziprand_tree_node_t** children,
+                         size_t max_children)
+{
+    const ziprand_tree_node_t* dir = ziprand_tree_find(archive, path);
+    if (!dir || !dir->is_dir)
+        return -1;
+
+    int64_t found = 0;
+    for (const ziprand_tree_node_t* child = dir->first_child; child; child = child->next_sibling) {
+        if (children && (size_t)found < max_children)
+            children[found] = child;
+        found++;
+    }
+    return found;
+}
+
+int ziprand_tree_walk(const ziprand_tree_node_t* node, ziprand_tree_visit_fn visit, void* ctx)
+{
+    if (!node || !visit)
+        return 0;
+
+    /* iterative pre-order traversal using parent links */
+    const ziprand_tree_node_t* current = node->first_child;
+    size_t depth = 1;
+    while (current) {
+        int ret = visit(current, depth, ctx);
+        if (ret != 0)
+            return ret;
+
+        if (current->first_child) {
+            current = current->first_child;
+            depth++;
+            continue;
+        }
+
+        while (current != node && !current->next_sibling) {
+            current = current->parent;
+            depth--;
+        }
+        current = current == node ? NULL : current->next_sibling;
+    }
+    return 0;
+}
+
+int ziprand_entry_is_symlink(const ziprand_entry_t* entry)
+{
+    if (!entry)
+        return 0;
+    /* macOS archivers store Unix mode bits too */
+    int host = entry->version_made_by >> 8;
+    if (host != HOST_UNIX && host != HOST_MACOS)
+        return 0;
+    return ((entry->external_attr >> 16) & UNIX_S_IFMT) == UNIX_S_IFLNK;
+}
+
+ziprand_encryption_t ziprand_entry_encryption(const ziprand_entry_t* entry)
+{
+    if (!entry || !(entry->flags & FLAG_ENCRYPTED))
+        return ZIPRAND_ENCRYPTION_NONE;
+    if (entry->aes_strength)
+        return (ziprand_encryption_t)(ZIPRAND_ENCRYPTION_AES_128 + entry->aes_strength - 1);
+    if ((entry->flags & FLAG_STRONG_ENCRYPTED) || entry->compression_method == METHOD_AES)
+        return ZIPRAND_ENCRYPTION_UNSUPPORTED;
+    return ZIPRAND_ENCRYPTION_ZIPCRYPTO;
+}
+
+int64_t ziprand_read_symlink_target(ziprand_archive_t* archive,
+                                    const ziprand_entry_t* entry,
+                                    char* buffer,
+                                    size_t size)
+{
+    if (!buffer || !ziprand_entry_is_symlink(entry))
+        return -1;
+
+    if (entry->uncompressed_size >= size)
+        return -1;
+
+    ziprand_file_t* file = ziprand_fopen(archive, entry);
+    if (!file)
+        return -1;
+
+    /* the I/O callback may return short reads */
+    size_t len = entry->uncompressed_size;
+    size_t done = 0;
+    while (done < len) {
+        int64_t got = ziprand_fread_at(file, done, buffer + done, len - done);
+        if (got <= 0)
+            break;
+        done += got;
+    }
+    ziprand_fclose(file);
+
+    if (done != len)
+        return -1;
+
+    buffer[len] = '\0';
+    return len;
+}
+
+char* ziprand_entry_sanitized_path(const ziprand_entry_t* entry)
+{
+    if (!entry || !entry->name || entry->name_len == 0)
+        return NULL;
+
+    const char* name = entry->name;
+    size_t len = entry->name_len;
+
+    /* embedded NUL, absolute path or drive letter */
+    if (memchr(name, '\0', len) || name[0] == '/' || name[0] == '\\')
+        return NULL;
+    char letter = ascii_tolower(name[0]);
+    if (len >= 2 && letter >= 'a' && letter <= 'z' && name[1] == ':')
+        return NULL;
+#ifdef _WIN32
+    /* "name:stream" writes an NTFS alternate data stream */
+    if (memchr(name, ':', len))
+        return NULL;
+#endif
+
+    char* path = malloc(len + 1);
+    if (!path)
+        return NULL;
+
+    size_t out = 0;
+    size_t start = 0;
+    while (start < len) {
+        size_t end = start;
+        while (end < len && name[end] != '/' && name[end] != '\\')
+            end++;
+
+        size_t comp_len = end - start;
+        if (comp_len == 2 && name[start] == '.' && name[start + 1] == '.') {
+            free(path);
+            return NULL;
+        }
+
+        if (comp_len > 0 && !(comp_len == 1 && name[start] == '.')) {
+            if (out > 0)
+                path[out++] = '/';
+            memcpy(path + out, name + start, comp_len);
+            out += comp_len;
+        }
+        start = end + 1;
+    }
+
+    if (out == 0) {
+        free(path);
+        return NULL;
+    }
+
+    path[out] = '\0';
+    return path;
+}
+
+#ifdef ZIPRAND_HAVE_ZLIB
+static void* inflate_create(void* ctx, const ziprand_entry_t* entry)
+{
+    (void)ctx;
+    (void)entry;
+    z_stream* stream = calloc(1, sizeof(z_stream));
+    if (stream && inflateInit2(stream, -MAX_WBITS) != Z_OK) {
+        free(stream);
+        return NULL;
+    }
+    return stream;
+}
+
+static ziprand_error_t inflate_reset(void* state)
+{
+    return inflateReset(state) == Z_OK ? ZIPRAND_OK : ZIPRAND_ERR_DECOMPRESS;
+}
+
+static ziprand_error_t inflate_decode(void* state,
+                                      const uint8_t* in,
+                                      size_t in_size,
+                                      size_t* consumed,
+                                      uint8_t* out,
+                                      size_t out_size,
+                                      size_t* produced,
+                                      int* finished)
+{
+    z_stream* stream = state;
+    uInt avail_in = in_size < UINT32_MAX ? (uInt)in_size : UINT32_MAX;
+    uInt avail_out = out_size < UINT32_MAX ? (uInt)out_size : UINT32_MAX;
+    stream->next_in = (Bytef*)in;
+    stream->avail_in = avail_in;
+    stream->next_out = out;
+    stream->avail_out = avail_out;
+
+    int ret = inflate(stream, Z_NO_FLUSH);
+    *consumed = avail_in - stream->avail_in;
+    *produced = avail_out - stream->avail_out;
+    if (ret == Z_STREAM_END)
+        *finished = 1;
+    else if (ret != Z_OK && ret != Z_BUF_ERROR)
+        return ret == Z_MEM_ERROR ? ZIPRAND_ERR_NOMEM : ZIPRAND_ERR_DECOMPRESS;
+    return ZIPRAND_OK;
+}
+
+static void inflate_destroy(void* state)
+{
+    inflateEnd(state);
+    free(state);
+}
+
+static const ziprand_decompressor_t deflate_codec = {
+    inflate_create,
+    inflate_reset,
+    inflate_decode,
+    inflate_destroy,
+    NULL,
+};
+#endif
+
+#ifdef ZIPRAND_HAVE_BZIP2
+static void* bzip2_create(void* ctx, const ziprand_entry_t* entry)
+{
+    (void)ctx;
+    (void)entry;
+    bz_stream* stream = calloc(1, sizeof(bz_stream));
+    if (stream && BZ2_bzDecompressInit(stream, 0, 0) != BZ_OK) {
+        free(stream);
+        return NULL;
+    }
+    return stream;
+}
+
+/* libbz2 has no reset, start a new stream */
+static ziprand_error_t bzip2_reset(void* state)
+{
+    bz_stream* stream = state;
+    BZ2_bzDecompressEnd(stream);
+    memset(stream, 0, sizeof(bz_stream));
+    return BZ2_bzDecompressInit(stream, 0, 0) == BZ_OK ? ZIPRAND_OK : ZIPRAND_ERR_NOMEM;
+}
+
+static ziprand_error_t bzip2_decode(void* state,
+                                    const uint8_t* in,
+                                    size_t in_size,
+                                    size_t* consumed,
+                                    uint8_t* out,
+                                    size_t out_size,
+                                    size_t* produced,
+                                    int* finished)
+{
+    bz_stream* stream = state;
+    unsigned int avail_in = in_size < UINT32_MAX ? (unsigned int)in_size : UINT32_MAX;
+    unsigned int avail_out = out_size < UINT32_MAX ? (unsigned int)out_size : UINT32_MAX;
+    stream->next_in = (char*)in;
+    stream->avail_in = avail_in;
+    stream->next_out = (char*)out;
+    stream->avail_out = avail_out;
+
+    int ret = BZ2_bzDecompress(stream);
+    *consumed = avail_in - stream->avail_in;
+    *produced = avail_out - stream->avail_out;
+    if (ret == BZ_STREAM_END)
+        *finished = 1;
+    else if (ret != BZ_OK)
+        return ret == BZ_MEM_ERROR ? ZIPRAND_ERR_NOMEM : ZIPRAND_ERR_DECOMPRESS;
+    return ZIPRAND_OK;
+}
+
+static void bzip2_destroy(void* state)
+{
+    BZ2_bzDecompressEnd(state);
+    free(state);
+}
+
+static const ziprand_decompressor_t bzip2_codec = {
+    bzip2_create,
+    bzip2_reset,
+    bzip2_decode,
+    bzip2_destroy,
+    NULL,
+};
+#endif
+
+#ifdef ZIPRAND_HAVE_ZSTD
+typedef struct {
+    ZSTD_DStream* stream;
+    size_t pending; /* last ZSTD_decompressStream result, 0 between frames */
+} zstd_state_t;
+
+static void* zstd_create(void* ctx, const ziprand_entry_t* entry)
+{
+    (void)ctx;
+    (void)entry;
+    zstd_state_t* zstd = calloc(1, sizeof(zstd_state_t));
+    if (zstd && !(zstd->stream = ZSTD_createDStream())) {
+        free(zstd);
+        return NULL;
+    }
+    return zstd;
+}
+
+static ziprand_error_t zstd_reset(void* state)
+{
+    zstd_state_t* zstd = state;
+    zstd->pending = 0;
+    size_t ret = ZSTD_DCtx_reset(zstd->stream, ZSTD_reset_session_only);
+    return ZSTD_isError(ret) ? ZIPRAND_ERR_DECOMPRESS : ZIPRAND_OK;
+}
+
+/* the data may hold several frames, so it ends where the input runs out between frames */
+static ziprand_error_t zstd_decode(void* state,
+                                   const uint8_t* in,
+                                   size_t in_size,
+                                   size_t* consumed,
+                                   uint8_t* out,
+                                   size_t out_size,
+                                   size_t* produced,
+                                   int* finished)
+{
+    zstd_state_t* zstd = state;
+    if (in_size == 0 && zstd->pending == 0) {
+        *consumed = 0;
+        *produced = 0;
+        *finished = 1;
+        return ZIPRAND_OK;
+    }
+
+    ZSTD_inBuffer input = {in, in_size, 0};
+    ZSTD_outBuffer output = {out, out_size, 0};
+    size_t ret = ZSTD_decompressStream(zstd->stream, &output, &input);
+    *consumed = input.pos;
+    *produced = output.pos;
+    if (ZSTD_isError(ret))
+        return ZSTD_getErrorCode(ret) == ZSTD_error_memory_allocation ? ZIPRAND_ERR_NOMEM
+                                                                       : ZIPRAND_ERR_DECOMPRESS;
+    zstd->pending = ret;
+    return ZIPRAND_OK;
+}
+
+static void zstd_destroy(void* state)
+{
+    zstd_state_t* zstd = state;
+    ZSTD_freeDStream(zstd->stream);
+    free(zstd);
+}
+
+static const ziprand_decompressor_t zstd_codec = {
+    zstd_create,
+    zstd_reset,
+    zstd_decode,
+    zstd_destroy,
+    NULL,
+};
+#endif
+
+#ifdef ZIPRAND_HAVE_LZMA
+typedef struct {
+    lzma_stream stream;
+    int xz;
+    uint64_t size;                    /* uncompressed size, LZMA streams may have no end marker */
+    uint8_t header[LZMA_HEADER_SIZE]; /* LZMA: header, collected before decoding starts */
+    size_t header_len;
+    int started;
+} lzma_state_t;
+
+static ziprand_error_t lzma_start(lzma_state_t* lzma)
+{
+    lzma_ret ret;
+    if (lzma->xz) {
+        ret = lzma_stream_decoder(&lzma->stream, UINT64_MAX, 0);
+    } else {
+        if (read_u16_le(&lzma->header[2]) != 5)
+            return ZIPRAND_ERR_DECOMPRESS;
+
+        /* LZMA1EXT takes the size and accepts an end marker after exactly that much data */
+        lzma_filter filters[2] = {{LZMA_FILTER_LZMA1, NULL}, {LZMA_VLI_UNKNOWN, NULL}};
+        if (lzma_properties_decode(&filters[0], NULL, &lzma->header[4], 5) != LZMA_OK)
+            return ZIPRAND_ERR_DECOMPRESS;
+        lzma_options_lzma* options = filters[0].options;
+        filters[0].id = LZMA_FILTER_LZMA1EXT;
+        options->ext_flags = LZMA_LZMA1EXT_ALLOW_EOPM;
+        lzma_set_ext_size(*options, lzma->size);
+        ret = lzma_raw_decoder(&lzma->stream, filters);
+        free(options);
+    }
+    if (ret != LZMA_OK)
+        return ret == LZMA_MEM_ERROR ? ZIPRAND_ERR_NOMEM : ZIPRAND_ERR_DECOMPRESS;
+    lzma->started = 1;
+    return ZIPRAND_OK;
+}
+
+static void* lzma_create(void* ctx, const ziprand_entry_t* entry)
+{
+    (void)ctx;
+    lzma_state_t* lzma = calloc(1, sizeof(lzma_state_t));
+    if (!lzma)
+        return NULL;
+    lzma->stream = (lzma_stream)LZMA_STREAM_INIT;
+    lzma->xz = entry->compression_method == METHOD_XZ;
+    lzma->size = entry->uncompressed_size;
+    return lzma;
+}
+
+static ziprand_error_t lzma_reset(void* state)
+{
+    lzma_state_t* lzma = state;
+    lzma_end(&lzma->stream);
+    lzma->stream = (lzma_stream)LZMA_STREAM_INIT;
+    lzma->header_len = 0;
+    lzma->started = 0;
+    return ZIPRAND_OK;
+}
+
+static ziprand_error_t lzma_decode(void* state,
+                                   const uint8_t* in,
+                                   size_t in_size,
+                                   size_t* consumed,
+                                   uint8_t* out,
+                                   size_t out_size,
+                                   size_t* produced,
+                                   int* finished)
+{
+    lzma_state_t* lzma = state;
+    *consumed = 0;
+    *produced = 0;
+
+    if (!lzma->xz && lzma->header_len < LZMA_HEADER_SIZE) {
+        size_t n = LZMA_HEADER_SIZE - lzma->header_len;
+        if (n > in_size)
+            n = in_size;
+        memcpy(lzma->header + lzma->header_len, in, n);
+        lzma->header_len += n;
+        *consumed = n;
+        in += n;
+        in_size -= n;
+        if (lzma->header_len < LZMA_HEADER_SIZE)
+            return ZIPRAND_OK;
+    }
+    if (!lzma->started) {
+        ziprand_error_t err = lzma_start(lzma);
+        if (err != ZIPRAND_OK)
+            return err;
+    }
+
+    lzma->stream.next_in = in;
+    lzma->stream.avail_in = in_size;
+    lzma->stream.next_out = out;
+    lzma->stream.avail_out = out_size;
+    lzma_ret ret = lzma_code(&lzma->stream, LZMA_RUN);
+    *consumed += in_size - lzma->stream.avail_in;
+    *produced = out_size - lzma->stream.avail_out;
+    if (ret == LZMA_STREAM_END)
+        *finished = 1;
+    else if (ret != LZMA_OK && ret != LZMA_BUF_ERROR)
+        return ret == LZMA_MEM_ERROR ? ZIPRAND_ERR_NOMEM : ZIPRAND_ERR_DECOMPRESS;
+    return ZIPRAND_OK;
+}
+
+static void lzma_destroy(void* state)
+{
+    lzma_state_t* lzma = state;
+    lzma_end(&lzma->stream);
+    free(lzma);
+}
+
+static const ziprand_decompressor_t lzma_codec = {
+    lzma_create,
+    lzma_reset,
+    lzma_decode,
+    lzma_destroy,
+    NULL,
+};
+#endif
+
+/* CRC-32 (IEEE 802.3, reflected), four bits at a time */
+static const uint32_t crc32_nibble_table[16] = {
+    0x00000000, 0x1DB71064, 0x3B6E20C8, 0x26D930AC, 0x76DC4190, 0x6B6B51F4, 0x4DB26158, 0x5005713C,
+    0xEDB88320, 0xF00F9344, 0xD6D6A3E8, 0xCB61B38C, 0x9B64C2B0, 0x86D3D2D4, 0xA00AE278, 0xBDBDF21C,
+};
+
+/* one byte of the CRC register update, without the pre- and post-inversion */
+static uint32_t crc32_byte(uint32_t crc, uint8_t byte)
+{
+    crc ^= byte;
+    crc = (crc >> 4) ^ crc32_nibble_table[crc & 0x0F];
+    crc = (crc >> 4) ^ crc32_nibble_table[crc & 0x0F];
+    return crc;
+}
+
+static uint32_t crc32_update(uint32_t crc, const uint8_t* data, size_t size)
+{
+    crc = ~crc;
+    for (size_t i = 0; i < size; i++)
+        crc = crc32_byte(crc, data[i]);
+    return ~crc;
+}
+
+#ifdef ZIPRAND_CRYPTO
+static void zipcrypto_update(zipcrypto_t* cipher, uint8_t byte)
+{
+    cipher->keys[0] = crc32_byte(cipher->keys[0], byte);
+    cipher->keys[1] = (cipher->keys[1] + (cipher->keys[0] & 0xFF)) * 134775813 + 1;
+    cipher->keys[2] = crc32_byte(cipher->keys[2], cipher->keys[1] >> 24);
+}
+
+static void zipcrypto_init(zipcrypto_t* cipher, const char* password)
+{
+    cipher->keys[0] = 0x12345678;
+    cipher->keys[1] = 0x23456789;
+    cipher->keys[2] = 0x34567890;
+    for (const char* p = password; *p; p++)
+        zipcrypto_update(cipher, (uint8_t)*p);
+}
+
+static void zipcrypto_decrypt(zipcrypto_t* cipher, uint8_t* data, size_t size)
+{
+    for (size_t i = 0; i < size; i++) {
+        uint32_t temp = (cipher->keys[2] & 0xFFFF) | 2;
+        data[i] ^= (uint8_t)((temp * (temp ^ 1)) >> 8);
+        zipcrypto_update(cipher, data[i]);
+    }
+}
+#endif
+
+#ifdef ZIPRAND_HAVE_OPENSSL
+static EVP_CIPHER_CTX* aes_create(const uint8_t* key, int key_size)
+{
+    const EVP_CIPHER* cipher = key_size == 16   ? EVP_aes_128_ecb()
+                               : key_size == 24 ? EVP_aes_192_ecb()
+                                                : EVP_aes_256_ecb();
+    EVP_CIPHER_CTX* ctx = EVP_CIPHER_CTX_new();
+    if (ctx && (!EVP_EncryptInit_ex(ctx, cipher, NULL, key, NULL) ||
+                !EVP_CIPHER_CTX_set_padding(ctx, 0))) {
+        EVP_CIPHER_CTX_free(ctx);
+        return NULL;
+    }
+    return ctx;
+}
+
+/* WinZip AES is CTR mode with a little-endian block counter starting at 1, so any byte can
+ * be decrypted from its position in the data alone */
+static ziprand_error_t
+aes_decrypt(EVP_CIPHER_CTX* ctx, uint64_t position, uint8_t* data, size_t size)
+{
+    uint8_t counter[AES_BLOCK_SIZE];
+    uint8_t stream[AES_BLOCK_SIZE];
+    size_t done = 0;
+    while (done < size) {
+        uint64_t block = (position + done) / AES_BLOCK_SIZE;
+        size_t skip = (position + done) % AES_BLOCK_SIZE;
+        memset(counter, 0, sizeof(counter));
+        write_u64_le(counter, block + 1);
+
+        int len;
+        if (!EVP_EncryptUpdate(ctx, stream, &len, counter, AES_BLOCK_SIZE))
+            return ZIPRAND_ERR_DECOMPRESS;
+
+        size_t n = AES_BLOCK_SIZE - skip < size - done ? AES_BLOCK_SIZE - skip : size - done;
+        for (size_t i = 0; i < n; i++)
+            data[done + i] ^= stream[skip + i];
+        done += n;
+    }
+    return ZIPRAND_OK;
+}
+
+/* HMAC-SHA1 over the encrypted data, compared with the authentication code after it */
+static EVP_MD_CTX* hmac_create(const uint8_t* key, int key_size)
+{
+    EVP_PKEY* pkey = EVP_PKEY_new_mac_key(EVP_PKEY_HMAC, NULL, key, key_size);
+    EVP_MD_CTX* ctx = pkey ? EVP_MD_CTX_new() : NULL;
+    if (ctx && !EVP_DigestSignInit(ctx, NULL, EVP_sha1(), NULL, pkey)) {
+        EVP_MD_CTX_free(ctx);
+        ctx = NULL;
+    }
+    EVP_PKEY_free(pkey); /* the context keeps its own reference */
+    return ctx;
+}
+#endif
+
+#if defined(ZIPRAND_CRYPTO) || defined(ZIPRAND_HAVE_OPENSSL)
+/* stored entries behind encryption go through a decoder too, since they can only be
+ * decrypted front to back; this one passes the decrypted bytes through */
+static void* copy_create(void* ctx, const ziprand_entry_t* entry)
+{
+    (void)ctx;
+    return (void*)entry; /* no state, but NULL would mean allocation failure */
+}
+
+static ziprand_error_t copy_reset(void* state)
+{
+    (void)state;
+    return ZIPRAND_OK;
+}
+
+static ziprand_error_t copy_decode(void* state,
+                                   const uint8_t* in,
+                                   size_t in_size,
+                                   size_t* consumed,
+                                   uint8_t* out,
+                                   size_t out_size,
+                                   size_t* produced,
+                                   int* finished)
+{
+    (void)state;
+    size_t size = in_size < out_size ? in_size : out_size;
+    memcpy(out, in, size);
+    *consumed = size;
+    *produced = size;
+    *finished = in_size == 0;
+    return ZIPRAND_OK;
+}
+
+static void copy_destroy(void* state)
+{
+    (void)state;
+}
+
+static const ziprand_decompressor_t copy_codec = {
+    copy_create,
+    copy_reset,
+    copy_decode,
+    copy_destroy,
+    NULL,
+};
+#endif
+
+/* decompressor for a compression method, preferring one registered in the archive options
+ * over a built-in one, or NULL if there is neither */
+static const ziprand_decompressor_t* find_codec(const ziprand_archive_t* archive, uint16_t method)
+{
+    const ziprand_options_t* options = &archive->options;
+    for (size_t i = 0; i < options->method_count; i++) {
+        if (options->methods[i].method == method)
+            return &options->methods[i].decompressor;
+    }
+
+    switch (method) {
+#ifdef ZIPRAND_HAVE_ZLIB
+    case METHOD_DEFLATE:
+        return &deflate_codec;
+#endif
+#ifdef ZIPRAND_HAVE_BZIP2
+    case METHOD_BZIP2:
+        return &bzip2_codec;
+#endif
+#ifdef ZIPRAND_HAVE_ZSTD
+    case METHOD_ZSTD:
+        return &zstd_codec;
+#endif
+#ifdef ZIPRAND_HAVE_LZMA
+    case METHOD_LZMA:
+    case METHOD_XZ:
+        return &lzma_codec;
+#endif
+    default:
+        return NULL;
+    }
+}
+
+static int method_supported(const ziprand_archive_t* archive, uint16_t method)
+{
+    return method == METHOD_STORED || find_codec(archive, method) != NULL;
+}
+
+/* DEFLATE seek indexes need zlib's decoder, not a registered replacement, and data that
+ * can be read from the middle, which encrypted data can't */
+static int uses_builtin_deflate(const ziprand_file_t* file)
+{
+#ifdef ZIPRAND_HAVE_ZLIB
+    return file->codec == &deflate_codec && !file->encrypted;
+#else
+    (void)file;
+    return 0;
+#endif
+}
+
+/* read the local headers of entries whose data offset is still unknown, coalescing headers
+ * that are close together into one read */
+static ziprand_error_t
+resolve_data_offsets(ziprand_archive_t* archive, const ziprand_entry_t** entries, size_t count)
+{
+    const ziprand_io_t* io = &archive->io;
+    int strict = archive->options.parse_mode == ZIPRAND_PARSE_STRICT;
+
+    ziprand_entry_t** pending = malloc((count ? count : 1) * sizeof(ziprand_entry_t*));
+    if (!pending)
+        return ZIPRAND_ERR_NOMEM;
+
+    size_t pending_count = 0;
+    for (size_t i = 0; i < count; i++) {
+        if (entries[i]->data_offset == 0)
+            pending[pending_count++] = (ziprand_entry_t*)entries[i];
+    }
+    if (pending_count == 0) {
+        free(pending);
+        return ZIPRAND_OK;
+    }
+
+    qsort(pending, pending_count, sizeof(ziprand_entry_t*), compare_entry_offsets);
+
+    /* remote backends get a window of at least one block, trading bytes for round trips */
+    ziprand_io_caps_t caps;
+    ziprand_io_capabilities(io, &caps);
+    size_t window_size = LOCAL_HEADER_WINDOW;
+    if (caps.latency == ZIPRAND_LATENCY_REMOTE && caps.block_size > window_size)
+        window_size = caps.block_size;
+
+    /* a multi-range backend gets many windows per request */
+    size_t batch_size = window_size;
+    if (io->read_ranges && RANGES_BATCH_SIZE > batch_size)
+        batch_size = RANGES_BATCH_SIZE;
+
+    uint8_t* batch = malloc(batch_size);
+    ziprand_read_range_t* ranges = malloc(pending_count * sizeof(ziprand_read_range_t));
+    size_t* group_ends = malloc(pending_count * sizeof(size_t));
+    if (!batch || !ranges || !group_ends) {
+        free(batch);
+        free(ranges);
+        free(group_ends);
+        free(pending);
+        return ZIPRAND_ERR_NOMEM;
+    }
+
+    ziprand_error_t err = ZIPRAND_OK;
+    size_t i = 0;
+    while (i < pending_count && err == ZIPRAND_OK) {
+        size_t range_count = 0;
+        size_t used = 0;
+        size_t next = i;
+        while (next < pending_count) {
+            /* group the headers that fit in one window starting at the first one */
+            uint64_t start = pending[next]->offset;
+            uint64_t end = start + local_header_need(pending[next], strict);
+            size_t group_end = next + 1;
+            while (group_end < pending_count) {
+                uint64_t next_end =
+                    pending[group_end]->offset + local_header_need(pending[group_end], strict);
+                if (next_end - start > window_size)
+                    break;
+                if (next_end > end)
+                    end = next_end;
+                group_end++;
+            }
+
+            size_t size = end - start;
+            if (size > batch_size - used)
+                break;
+            ranges[range_count].offset = start;
+            ranges[range_count].buffer = batch + used;
+            ranges[range_count].size = size;
+            group_ends[range_count] = group_end;
+            range_count++;
+            used += size;
+            next = group_end;
+        }
+
+        err = io_read_ranges(io, ranges, range_count);
+        for (size_t r = 0; r < range_count && err == ZIPRAND_OK; r++) {
+            const uint8_t* window = ranges[r].buffer;
+            for (; i < group_ends[r] && err == ZIPRAND_OK; i++)
+                err = parse_local_header(
+                    window + (pending[i]->offset - ranges[r].offset), pending[i], strict);
+        }
+    }
+
+    free(batch);
+    free(ranges);
+    free(group_ends);
+    free(pending);
+    return err;
+}
+
+/* check a password against a ZipCrypto header, and set the file up to decrypt the data
+ * that follows it */
+static ziprand_error_t start_zipcrypto(ziprand_file_t* file, const char* password)
+{
+#ifdef ZIPRAND_CRYPTO
+    const ziprand_entry_t* entry = file->entry;
+    if (entry->compressed_size < ZIPCRYPTO_HEADER_SIZE)
+        return ZIPRAND_ERR_INVALID_ZIP;
+
+    uint8_t header[ZIPCRYPTO_HEADER_SIZE];
+    if (io_read(&file->archive->io, entry->data_offset, header, sizeof(header)) !=
+        (int64_t)sizeof(header))
+        return ZIPRAND_ERR_IO;
+
+    /* the last header byte repeats the top byte of the CRC-32, or of the modification
+     * time when the CRC-32 is only known after the data (data descriptor) */
+    zipcrypto_init(&file->cipher, password);
+    zipcrypto_decrypt(&file->cipher, header, sizeof(header));
+    uint8_t check = entry->flags & FLAG_DATA_DESCRIPTOR ? entry->mod_time >> 8
+                                                        : entry->crc32 >> 24;
+    if (header[ZIPCRYPTO_HEADER_SIZE - 1] != check)
+        return ZIPRAND_ERR_WRONG_PASSWORD;
+
+    file->encrypted = 1;
+    file->source_start = ZIPCRYPTO_HEADER_SIZE;
+    if (!file->codec)
+        file->codec = &copy_codec;
+    return ZIPRAND_OK;
+#else
+    (void)file;
+    (void)password;
+    return ZIPRAND_ERR_ENCRYPTED;
+#endif
+}
+
+/* derive WinZip AES keys from a password and the salt in front of the data, and check the
+ * password verifier that follows the salt */
+static ziprand_error_t start_aes(ziprand_file_t* file, const char* password)
+{
+#ifdef ZIPRAND_HAVE_OPENSSL
+    const ziprand_entry_t* entry = file->entry;
+    int key_size = 8 + 8 * entry->aes_strength;
+    size_t salt_size = key_size / 2;
+    size_t header_size = salt_size + AES_VERIFIER_SIZE;
+    if (entry->compressed_size < header_size + AES_MAC_SIZE)
+        return ZIPRAND_ERR_INVALID_ZIP;
+
+    uint8_t header[AES_MAX_KEY_SIZE / 2 + AES_VERIFIER_SIZE];
+    if (io_read(&file->archive->io, entry->data_offset, header, header_size) !=
+        (int64_t)header_size)
+        return ZIPRAND_ERR_IO;
+
+    /* encryption key, authentication key, password verifier */
+    uint8_t derived[2 * AES_MAX_KEY_SIZE + AES_VERIFIER_SIZE];
+    if (!PKCS5_PBKDF2_HMAC_SHA1(password,
+                                (int)strlen(password),
+                                header,
+                                (int)salt_size,
+                                AES_PBKDF2_ITERATIONS,
+                                2 * key_size + AES_VERIFIER_SIZE,
+                                derived))
+        return ZIPRAND_ERR_NOMEM;
+    if (memcmp(derived + 2 * key_size, header + salt_size, AES_VERIFIER_SIZE) != 0)
+        return ZIPRAND_ERR_WRONG_PASSWORD;
+
+    memcpy(file->key, derived, key_size);
+    memcpy(file->auth_key, derived + key_size, key_size);
+    file->key_size = key_size;
+    file->encrypted = 1;
+    file->source_start = header_size;
+    file->source_end = entry->compressed_size - AES_MAC_SIZE;
+    if (!file->codec)
+        file->codec = &copy_codec;
+    return ZIPRAND_OK;
+#else
+    (void)file;
+    (void)password;
+    return ZIPRAND_ERR_ENCRYPTED;
+#endif
+}
+
+static int encryption_supported(ziprand_encryption_t encryption)
+{
+    switch (encryption) {
+    case ZIPRAND_ENCRYPTION_NONE:
+        return 1;
+#ifdef ZIPRAND_CRYPTO
+    case ZIPRAND_ENCRYPTION_ZIPCRYPTO:
+        return 1;
+#endif
+#ifdef ZIPRAND_HAVE_OPENSSL
+    case ZIPRAND_ENCRYPTION_AES_128:
+    case ZIPRAND_ENCRYPTION_AES_192:
+    case ZIPRAND_ENCRYPTION_AES_256:
+        return 1;
+#endif
+    default:
+        return 0;
+    }
+}
+
+static ziprand_error_t
+try_password(ziprand_file_t* file, ziprand_encryption_t encryption, const char* password)
+{
+    if (encryption == ZIPRAND_ENCRYPTION_ZIPCRYPTO)
+        return start_zipcrypto(file, password);
+    return start_aes(file, password);
+}
+
+/* without a password, ask the archive's provider until it gives the right one or gives up;
+ * giving up before any attempt leaves the entry ZIPRAND_ERR_ENCRYPTED */
+static ziprand_error_t
+start_decryption(ziprand_file_t* file, ziprand_encryption_t encryption, const char* password)
+{
+    if (password)
+        return try_password(file, encryption, password);
+
+    const ziprand_password_provider_t* provider = &file->archive->options.password_provider;
+    char buffer[PASSWORD_BUFFER_SIZE];
+    ziprand_error_t err = ZIPRAND_ERR_ENCRYPTED;
+    for (unsigned int attempt = 0; provider->get; attempt++) {
+        if (provider->get(provider->ctx, file->entry, attempt, buffer, sizeof(buffer)) != 0)
+            break;
+        buffer[sizeof(buffer) - 1] = '\0';
+        err = try_password(file, encryption, buffer);
+        if (provider->result && (err == ZIPRAND_OK || err == ZIPRAND_ERR_WRONG_PASSWORD))
+            provider->result(provider->ctx, file->entry, buffer, err == ZIPRAND_OK);
+        if (err != ZIPRAND_ERR_WRONG_PASSWORD)
+            break;
+    }
+
+    /* don't leave the password on the stack */
+    volatile char* wipe = buffer;
+    for (size_t i = 0; i < sizeof(buffer); i++)
+        wipe[i] = 0;
+    return err;
+}
+
+/* raw handles read the compressed bytes as they are stored, whatever the method; encrypted
+ * entries need a password, given or from the archive's password provider */
+static ziprand_file_t* create_file(ziprand_archive_t* archive,
+                                   const ziprand_entry_t* entry,
+                                   int raw,
+                                   const char* password,
+                                   ziprand_error_t* error)
+{
+    ziprand_encryption_t encryption = raw ? ZIPRAND_ENCRYPTION_NONE
+                                          : ziprand_entry_encryption(entry);
+    if (encryption != ZIPRAND_ENCRYPTION_NONE &&
+        ((!password && !archive->options.password_provider.get) ||
+         !encryption_supported(encryption))) {
+        *error = ZIPRAND_ERR_ENCRYPTED;
+        return NULL;
+    }
+    if (!raw && !method_supported(archive, entry->compression_method)) {
+        *error = ZIPRAND_ERR_COMPRESSED;
+        return NULL;
+    }
+
+    /* calculate data offset if not already done */
+    ziprand_entry_t* mutable_entry = (ziprand_entry_t*)entry;
+    int strict = archive->options.parse_mode == ZIPRAND_PARSE_STRICT;
+    if (mutable_entry->data_offset == 0) {
+        *error = get_data_offset(&archive->io, mutable_entry, strict);
+        if (*error != ZIPRAND_OK)
+            return NULL;
+    }
+
+    ziprand_file_t* file = malloc(sizeof(ziprand_file_t));
+    if (!file) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    file->archive = archive;
+    file->entry = entry;
+    file->codec = raw ? NULL : find_codec(archive, entry->compression_method);
+    file->decoder = NULL;
+    file->index = NULL;
+    file->size = raw ? entry->compressed_size : entry->uncompressed_size;
+    file->encrypted = 0;
+    memset(&file->cipher, 0, sizeof(file->cipher));
+    file->key_size = 0;
+    file->authenticate = 0;
+    file->source_start = 0;
+    file->source_end = entry->compressed_size;
+    file->position = 0;
+    file->verify = 0;
+    file->crc = 0;
+    file->crc_offset = 0;
+    file->error = ZIPRAND_OK;
+    file->readahead = NULL;
+    file->readahead_capacity = 0;
+    file->readahead_start = 0;
+    file->readahead_len = 0;
+
+    *error = ZIPRAND_OK;
+    if (encryption != ZIPRAND_ENCRYPTION_NONE)
+        *error = start_decryption(file, encryption, password);
+    if (*error != ZIPRAND_OK) {
+        free(file);
+        return NULL;
+    }
+    return file;
+}
+
+static ziprand_file_t* open_file(ziprand_archive_t* archive,
+                                 const ziprand_entry_t* entry,
+                                 int raw,
+                                 const char* password,
+                                 ziprand_error_t* error)
+{
+    uint64_t start = trace_begin();
+    ziprand_file_t* file = create_file(archive, entry, raw, password, error);
+    trace_end(ZIPRAND_TRACE_OPEN_ENTRY,
+              archive->io.ctx,
+              entry->name,
+              entry->offset,
+              entry->compressed_size,
+              *error,
+              start);
+    return file;
+}
+
+ziprand_file_t* ziprand_fopen(ziprand_archive_t* archive, const ziprand_entry_t* entry)
+{
+    ziprand_error_t err;
+    if (!archive || !entry)
+        return NULL;
+    return open_file(archive, entry, 0, NULL, &err);
+}
+
+ziprand_error_t ziprand_fopen_many(ziprand_archive_t* archive,
+                                   const ziprand_entry_t** entries,
+                                   size_t count,
+                                   ziprand_file_t** files)
+{
+    if (!archive || ((!entries || !files) && count > 0))
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    for (size_t i = 0; i < count; i++) {
+        files[i] = NULL;
+        if (!entries[i])
+            return ZIPRAND_ERR_INVALID_PARAM;
+        if ((entries[i]->flags & FLAG_ENCRYPTED) && !archive->options.password_provider.get)
+            return ZIPRAND_ERR_ENCRYPTED;
+        if (!method_supported(archive, entries[i]->compression_method))
+            return ZIPRAND_ERR_COMPRESSED;
+    }
+
+    ziprand_error_t err = resolve_data_offsets(archive, entries, count);
+    for (size_t i = 0; i < count && err == ZIPRAND_OK; i++)
+        files[i] = open_file(archive, entries[i], 0, NULL, &err);
+
+    if (err != ZIPRAND_OK) {
+        for (size_t i = 0; i < count; i++) {
+            ziprand_fclose(files[i]);
+            files[i] = NULL;
+        }
+    }
+    return err;
+}
+
+ziprand_file_t* ziprand_fopen_verified(ziprand_archive_t* archive, const ziprand_entry_t* entry)
+{
+    ziprand_file_t* file = ziprand_fopen(archive, entry);
+    if (file) {
+        file->verify = 1;
+        file->authenticate = 1;
+    }
+    return file;
+}
+
+ziprand_file_t* ziprand_fopen_by_name(ziprand_archive_t* archive, const char* name)
+{
+    const ziprand_entry_t* entry = ziprand_find_entry(archive, name);
+    if (!entry)
+        return NULL;
+    return ziprand_fopen(archive, entry);
+}
+
+ziprand_file_t*
+ziprand_fopen_by_name_ex(ziprand_archive_t* archive, const char* name, ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!archive || !name) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    const ziprand_entry_t* entry = ziprand_find_entry(archive, name);
+    if (!entry) {
+        *error = ZIPRAND_ERR_NOT_FOUND;
+        return NULL;
+    }
+    return open_file(archive, entry, 0, NULL, error);
+}
+
+ziprand_file_t* ziprand_fopen_with_password(ziprand_archive_t* archive,
+                                            const ziprand_entry_t* entry,
+                                            const char* password,
+                                            ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!archive || !entry || !password) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+    return open_file(archive, entry, 0, password, error);
+}
+
+ziprand_file_t*
+ziprand_fopen_raw(ziprand_archive_t* archive, const ziprand_entry_t* entry, ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!archive || !entry) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+    return open_file(archive, entry, 1, NULL, error);
+}
+
+ziprand_file_t* ziprand_fopen_http(ziprand_archive_t* archive,
+                                   const ziprand_entry_t* entry,
+                                   int accept_deflate,
+                                   ziprand_http_entry_t* info,
+                                   ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!archive || !entry || !info) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    /* encrypted bytes can't go out as they are */
+    info->passthrough = ZIPRAND_PASSTHROUGH_NONE;
+    if (!(entry->flags & FLAG_ENCRYPTED)) {
+        if (entry->compression_method == METHOD_STORED)
+            info->passthrough = ZIPRAND_PASSTHROUGH_IDENTITY;
+        else if (entry->compression_method == METHOD_DEFLATE && accept_deflate)
+            info->passthrough = ZIPRAND_PASSTHROUGH_DEFLATE;
+    }
+    info->compression_method = entry->compression_method;
+    info->compressed_size = entry->compressed_size;
+    info->uncompressed_size = entry->uncompressed_size;
+
+    /* a strong ETag names one representation, so the DEFLATE-encoded one gets its own; the
+     * local header offset tells apart entries with the same content in the archive */
+    int passthrough_deflate = info->passthrough == ZIPRAND_PASSTHROUGH_DEFLATE;
+    info->content_length = passthrough_deflate ? entry->compressed_size
+                                               : entry->uncompressed_size;
+    info->etag[0] = '\0';
+    if (entry->aes_version != 2) /* AE-2 leaves out the CRC-32 */
+        snprintf(info->etag,
+                 sizeof(info->etag),
+                 "\"%08lx-%llx-%llx%s\"",
+                 (unsigned long)entry->crc32,
+                 (unsigned long long)entry->uncompressed_size,
+                 (unsigned long long)entry->offset,
+                 passthrough_deflate ? "-deflate" : "");
+
+    return open_file(archive, entry, info->passthrough != ZIPRAND_PASSTHROUGH_NONE, NULL, error);
+}
+
+ziprand_file_t* ziprand_fdup(const ziprand_file_t* file)
+{
+    if (!file)
+        return NULL;
+
+    ziprand_file_t* copy = malloc(sizeof(ziprand_file_t));
+    if (!copy)
+        return NULL;
+
+    *copy = *file;
+
+    /* the copy decompresses on its own and gets its own, empty readahead buffer */
+    copy->decoder = NULL;
+    copy->readahead = NULL;
+    copy->readahead_len = 0;
+    if (file->readahead && ziprand_fsetbuf(copy, file->readahead_capacity) != ZIPRAND_OK) {
+        free(copy);
+        return NULL;
+    }
+    return copy;
+}
+
+/* feed bytes just read at the current position into a verified file's running CRC */
+static ziprand_error_t update_file_crc(ziprand_file_t* file, const uint8_t* data, uint64_t size)
+{
+    uint64_t start = file->position;
+
+    /* a forward seek skipped unread bytes, the checksum can no longer be computed */
+    if (start > file->crc_offset) {
+        file->verify = 0;
+        return ZIPRAND_OK;
+    }
+
+    /* re-reading data that was already checked */
+    if (start + size <= file->crc_offset)
+        return ZIPRAND_OK;
+
+    uint64_t skip = file->crc_offset - start;
+    file->crc = crc32_update(file->crc, data + skip, size - skip);
+    file->crc_offset = start + size;
+
+    /* AE-2 leaves the CRC-32 out, the authentication code covers the data instead */
+    if (file->entry->aes_version == 2)
+        return ZIPRAND_OK;
+
+    if (file->crc_offset == file->entry->uncompressed_size && file->crc != file->entry->crc32) {
+        file->error = ZIPRAND_ERR_CRC_MISMATCH;
+        return file->error;
+    }
+    return ZIPRAND_OK;
+}
+
+ziprand_error_t ziprand_fsetbuf(ziprand_file_t* file, size_t buffer_size)
+{
+    if (!file)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    uint8_t* readahead = NULL;
+    if (buffer_size > 0) {
+        readahead = malloc(buffer_size);
+        if (!readahead)
+            return ZIPRAND_ERR_NOMEM;
+    }
+
+    free(file->readahead);
+    file->readahead = readahead;
+    file->readahead_capacity = buffer_size;
+    file->readahead_start = 0;
+    file->readahead_len = 0;
+    return ZIPRAND_OK;
+}
+
+#ifdef ZIPRAND_HAVE_ZLIB
+static ziprand_error_t add_deflate_point(ziprand_deflate_index_t* index,
+                                         size_t* capacity,
+                                         const z_stream* stream,
+                                         uint64_t offset,
+                                         uint64_t source_offset,
+                                         uint8_t byte,
+                                         const uint8_t* window)
+{
+    if (index->count == *capacity) {
+        size_t grown = *capacity ? *capacity * 2 : 8;
+        deflate_point_t* points = realloc(index->points, grown * sizeof(deflate_point_t));
+        if (!points)
+            return ZIPRAND_ERR_NOMEM;
+        index->points = points;
+        *capacity = grown;
+    }
+
+    deflate_point_t* point = &index->points[index->count++];
+    point->offset = offset;
+    point->source_offset = source_offset;
+    point->bits = stream->data_type & 7;
+    point->byte = byte;
+
+    /* window is circular, the oldest history byte is where the next output goes */
+    size_t left = stream->avail_out;
+    memcpy(point->window, window + DEFLATE_WINDOW_SIZE - left, left);
+    memcpy(point->window + left, window, DEFLATE_WINDOW_SIZE - left);
+    return ZIPRAND_OK;
+}
+
+/* decode a whole DEFLATE entry block by block, recording a checkpoint at the first block
+ * boundary after every span bytes of output */
+static ziprand_error_t
+build_deflate_index(ziprand_file_t* file, uint64_t span, ziprand_deflate_index_t* index)
+{
+    const ziprand_entry_t* entry = file->entry;
+    z_stream stream;
+    memset(&stream, 0, sizeof(stream));
+    if (inflateInit2(&stream, -MAX_WBITS) != Z_OK)
+        return ZIPRAND_ERR_NOMEM;
+
+    uint8_t* input = malloc(DECODE_INPUT_SIZE);
+    uint8_t* window = calloc(1, DEFLATE_WINDOW_SIZE);
+    if (!input || !window) {
+        free(input);
+        free(window);
+        inflateEnd(&stream);
+        return ZIPRAND_ERR_NOMEM;
+    }
+
+    ziprand_error_t err = ZIPRAND_OK;
+    size_t capacity = 0;
+    uint64_t source_offset = 0; /* compressed bytes read */
+    uint64_t consumed = 0;      /* compressed bytes decoded */
+    uint64_t produced = 0;
+    uint64_t last = 0; /* offset of the last checkpoint */
+    uint8_t byte = 0;  /* last compressed byte decoded */
+    int finished = 0;
+    stream.next_out = window;
+    stream.avail_out = DEFLATE_WINDOW_SIZE;
+
+    while (!finished && err == ZIPRAND_OK) {
+        if (stream.avail_in == 0 && source_offset < entry->compressed_size) {
+            uint64_t left = entry->compressed_size - source_offset;
+            size_t want = left < DECODE_INPUT_SIZE ? (size_t)left : DECODE_INPUT_SIZE;
+            int64_t got =
+                io_read(&file->archive->io, entry->data_offset + source_offset, input, want);
+            if (got <= 0) {
+                err = got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+                break;
+            }
+            stream.next_in = input;
+            stream.avail_in = got;
+            source_offset += got;
+        }
+
+        if (stream.avail_out == 0) {
+            stream.next_out = window;
+            stream.avail_out = DEFLATE_WINDOW_SIZE;
+        }
+
+        uInt avail_in = stream.avail_in;
+        uInt avail_out = stream.avail_out;
+        int ret = inflate(&stream, Z_BLOCK);
+        if (stream.avail_in < avail_in)
+            byte = stream.next_in[-1];
+        consumed += avail_in - stream.avail_in;
+        produced += avail_out - stream.avail_out;
+
+        if (ret == Z_STREAM_END) {
+            finished = 1;
+        } else if (ret == Z_MEM_ERROR) {
+            err = ZIPRAND_ERR_NOMEM;
+        } else if (ret != Z_OK) {
+            /* corrupt, or Z_BUF_ERROR: the compressed data ended before the stream did */
+            err = ZIPRAND_ERR_DECOMPRESS;
+        } else if (produced > entry->uncompressed_size) {
+            /* more data than the central directory promised */
+            err = ZIPRAND_ERR_DECOMPRESS;
+        } else if ((stream.data_type & 128) && !(stream.data_type & 64) &&
+                   produced - last >= span) {
+            /* at a block boundary, not after the final block */
+            err = add_deflate_point(index, &capacity, &stream, produced, consumed, byte, window);
+            last = produced;
+        }
+    }
+
+    if (err == ZIPRAND_OK && produced != entry->uncompressed_size)
+        err = ZIPRAND_ERR_DECOMPRESS;
+
+    free(input);
+    free(window);
+    inflateEnd(&stream);
+    return err;
+}
+#endif
+
+ziprand_deflate_index_t*
+ziprand_deflate_index_build(ziprand_file_t* file, uint64_t span, ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!file || file->entry->compression_method != METHOD_DEFLATE) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+#ifdef ZIPRAND_HAVE_ZLIB
+    if (!uses_builtin_deflate(file)) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+    ziprand_deflate_index_t* index = calloc(1, sizeof(ziprand_deflate_index_t));
+    if (!index) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+    index->entry_offset = file->entry->offset;
+    index->compressed_size = file->entry->compressed_size;
+    index->uncompressed_size = file->entry->uncompressed_size;
+    index->crc32 = file->entry->crc32;
+
+    *error = build_deflate_index(file, span ? span : DEFLATE_INDEX_DEFAULT_SPAN, index);
+    if (*error != ZIPRAND_OK) {
+        ziprand_deflate_index_free(index);
+        return NULL;
+    }
+    return index;
+#else
+    (void)span;
+    *error = ZIPRAND_ERR_COMPRESSED;
+    return NULL;
+#endif
+}
+
+size_t ziprand_deflate_index_count(const ziprand_deflate_index_t* index)
+{
+    return index ? index->count : 0;
+}
+
+void ziprand_deflate_index_free(ziprand_deflate_index_t* index)
+{
+    if (!index)
+        return;
+    free(index->points);
+    free(index);
+}
+
+ziprand_error_t ziprand_fset_index(ziprand_file_t* file, const ziprand_deflate_index_t* index)
+{
+    if (!file)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    const ziprand_entry_t* entry = file->entry;
+    if (index && (!uses_builtin_deflate(file) || index->entry_offset != entry->offset ||
+                  index->compressed_size != entry->compressed_size ||
+                  index->uncompressed_size != entry->uncompressed_size ||
+                  index->crc32 != entry->crc32))
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    file->index = index;
+    return ZIPRAND_OK;
+}
+
+/* serve a read at the current position from the readahead buffer, refilling it as needed */
+static int64_t read_buffered(ziprand_file_t* file, uint8_t* buffer, size_t size)
+{
+    size_t done = 0;
+    while (done < size) {
+        uint64_t pos = file->position + done;
+        if (pos < file->readahead_start || pos >= file->readahead_start + file->readahead_len) {
+            /* large reads go straight to the backend */
+            int64_t got;
+            if (size - done >= file->readahead_capacity) {
+                got = ziprand_fread_at(file, pos, buffer + done, size - done);
+                if (got < 0)
+                    return done > 0 ? (int64_t)done : got;
+                return done + got;
+            }
+
+            got = ziprand_fread_at(file, pos, file->readahead, file->readahead_capacity);
+            if (got < 0)
+                return done > 0 ? (int64_t)done : got;
+            if (got == 0)
+                break;
+            file->readahead_start = pos;
+            file->readahead_len = got;
+        }
+
+        size_t offset = pos - file->readahead_start;
+        size_t available = file->readahead_len - offset;
+        size_t n = size - done < available ? size - done : available;
+        memcpy(buffer + done, file->readahead + offset, n);
+        done += n;
+    }
+    return done;
+}
+
+int64_t ziprand_fread(ziprand_file_t* file, void* buffer, size_t size)
+{
+    if (!file || file->error != ZIPRAND_OK)
+        return -1;
+    int64_t result;
+    if (file->readahead && buffer)
+        result = read_buffered(file, buffer, size);
+    else
+        result = ziprand_fread_at(file, file->position, buffer, size);
+    if (result > 0) {
+        if (file->verify && update_file_crc(file, buffer, result) != ZIPRAND_OK)
+            return -1;
+        file->position += result;
+    } else if (result == ZIPRAND_ERR_SOURCE_CHANGED) {
+        /* nothing more can be read consistently from this archive */
+        file->error = ZIPRAND_ERR_SOURCE_CHANGED;
+    }
+    return result;
+}
+
+static void decoder_free(const ziprand_decompressor_t* codec, decoder_t* decoder)
+{
+    if (!decoder)
+        return;
+    if (decoder->state)
+        codec->destroy(decoder->state);
+    free(decoder->input);
+    free(decoder->frame_offsets);
+    free(decoder->frame_sources);
+#ifdef ZIPRAND_HAVE_OPENSSL
+    EVP_CIPHER_CTX_free(decoder->aes);
+    EVP_MD_CTX_free(decoder->mac);
+#endif
+    free(decoder);
+}
+
+#ifdef ZIPRAND_HAVE_ZSTD
+/* read the seek table of a seekable Zstandard entry into the decoder's frame list; entries
+ * without a valid one are simply decoded as a stream */
+static ziprand_error_t load_zstd_seek_table(ziprand_file_t* file, decoder_t* decoder)
+{
+    const ziprand_entry_t* entry = file->entry;
+    const ziprand_io_t* io = &file->archive->io;
+    if (entry->compressed_size < 8 + ZSTD_SEEK_FOOTER_SIZE)
+        return ZIPRAND_OK;
+
+    uint8_t footer[ZSTD_SEEK_FOOTER_SIZE];
+    uint64_t footer_offset = entry->data_offset + entry->compressed_size - sizeof(footer);
+    int64_t got = io_read(io, footer_offset, footer, sizeof(footer));
+    if (got < 0)
+        return got;
+    if (got != sizeof(footer) || read_u32_le(&footer[5]) != ZSTD_SEEKABLE_MAGIC)
+        return ZIPRAND_OK;
+
+    uint64_t count = read_u32_le(footer);
+    size_t record_size = footer[4] & ZSTD_SEEK_CHECKSUM_FLAG ? 12 : 8;
+    uint64_t frame_size = 8 + count * record_size + sizeof(footer);
+    if (count == 0 || frame_size > entry->compressed_size ||
+        frame_size > file->archive->total_size)
+        return ZIPRAND_OK;
+
+    uint8_t* table = malloc(frame_size);
+    decoder->frame_offsets = malloc(count * sizeof(uint64_t));
+    decoder->frame_sources = malloc(count * sizeof(uint64_t));
+    if (!table || !decoder->frame_offsets || !decoder->frame_sources) {
+        free(table);
+        return ZIPRAND_ERR_NOMEM;
+    }
+
+    uint64_t table_offset = entry->compressed_size - frame_size;
+    got = io_read(io, entry->data_offset + table_offset, table, frame_size);
+    if (got < 0) {
+        free(table);
+        return got;
+    }
+
+    /* the frames must add up to exactly the entry, or the table is not trusted */
+    int valid = (uint64_t)got == frame_size && read_u32_le(table) == ZSTD_SKIPPABLE_MAGIC &&
+                read_u32_le(&table[4]) == frame_size - 8;
+    uint64_t offset = 0;
+    uint64_t source = 0;
+    for (uint64_t i = 0; i < count && valid; i++) {
+        const uint8_t* record = &table[8 + i * record_size];
+        decoder->frame_offsets[i] = offset;
+        decoder->frame_sources[i] = source;
+        source += read_u32_le(record);
+        offset += read_u32_le(&record[4]);
+    }
+    free(table);
+
+    if (valid && source == table_offset && offset == entry->uncompressed_size) {
+        decoder->frame_count = count;
+    } else {
+        free(decoder->frame_offsets);
+        free(decoder->frame_sources);
+        decoder->frame_offsets = NULL;
+        decoder->frame_sources = NULL;
+    }
+    return ZIPRAND_OK;
+}
+#endif
+
+#ifdef ZIPRAND_HAVE_OPENSSL
+/* finish the HMAC of the AES data and compare it with the 10-byte authentication code */
+static ziprand_error_t check_mac(ziprand_file_t* file)
+{
+    decoder_t* decoder = file->decoder;
+    uint8_t digest[EVP_MAX_MD_SIZE];
+    size_t digest_size = sizeof(digest);
+    int signed_ok = EVP_DigestSignFinal(decoder->mac, digest, &digest_size);
+    EVP_MD_CTX_free(decoder->mac);
+    decoder->mac = NULL;
+    if (!signed_ok)
+        return ZIPRAND_ERR_DECOMPRESS;
+
+    uint8_t code[AES_MAC_SIZE];
+    int64_t got = io_read(&file->archive->io,
+                          file->entry->data_offset + file->source_end,
+                          code,
+                          sizeof(code));
+    if (got != (int64_t)sizeof(code))
+        return got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+    if (memcmp(digest, code, AES_MAC_SIZE) != 0) {
+        file->error = ZIPRAND_ERR_AUTH_FAILED;
+        return file->error;
+    }
+    return ZIPRAND_OK;
+}
+
+/* feed encrypted bytes read at source offset start into the HMAC; once a seek has skipped
+ * some, the authentication code can no longer be checked */
+static ziprand_error_t
+update_mac(ziprand_file_t* file, uint64_t start, const uint8_t* data, size_t size)
+{
+    decoder_t* decoder = file->decoder;
+    if (start > decoder->mac_offset) {
+        EVP_MD_CTX_free(decoder->mac);
+        decoder->mac = NULL;
+        return ZIPRAND_OK;
+    }
+
+    /* re-reading data that was already hashed */
+    if (start + size <= decoder->mac_offset)
+        return ZIPRAND_OK;
+
+    size_t skip = decoder->mac_offset - start;
+    if (!EVP_DigestSignUpdate(decoder->mac, data + skip, size - skip))
+        return ZIPRAND_ERR_DECOMPRESS;
+    decoder->mac_offset = start + size;
+    return decoder->mac_offset == file->source_end ? check_mac(file) : ZIPRAND_OK;
+}
+
+/* verified handles hash the encrypted data a seek skipped before decoding goes on */
+static ziprand_error_t catch_up_mac(ziprand_file_t* file)
+{
+    decoder_t* decoder = file->decoder;
+    while (decoder->mac && decoder->mac_offset < decoder->source_offset) {
+        uint64_t left = decoder->source_offset - decoder->mac_offset;
+        size_t want = left < DECODE_INPUT_SIZE ? (size_t)left : DECODE_INPUT_SIZE;
+        int64_t got = io_read(&file->archive->io,
+                              file->entry->data_offset + decoder->mac_offset,
+                              decoder->input,
+                              want);
+        if (got <= 0)
+            return got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+        ziprand_error_t err = update_mac(file, decoder->mac_offset, decoder->input, got);
+        if (err != ZIPRAND_OK)
+            return err;
+    }
+    return ZIPRAND_OK;
+}
+#endif
+
+/* decode the next bytes of a compressed entry into out, returns 0 at the end of the stream */
+static int64_t decode_some(ziprand_file_t* file, uint8_t* out, size_t out_size)
+{
+    const ziprand_entry_t* entry = file->entry;
+    decoder_t* decoder = file->decoder;
+
+    while (!decoder->finished) {
+        if (decoder->input_pos == decoder->input_len &&
+            decoder->source_offset < file->source_end) {
+#ifdef ZIPRAND_HAVE_OPENSSL
+            if (decoder->mac && file->authenticate) {
+                ziprand_error_t err = catch_up_mac(file);
+                if (err != ZIPRAND_OK)
+                    return err;
+            }
+#endif
+            uint64_t left = file->source_end - decoder->source_offset;
+            size_t want = left < DECODE_INPUT_SIZE ? (size_t)left : DECODE_INPUT_SIZE;
+            int64_t got = io_read(&file->archive->io,
+                                  entry->data_offset + decoder->source_offset,
+                                  decoder->input,
+                                  want);
+            if (got <= 0)
+                return got < 0 ? got : ZIPRAND_ERR_IO;
+#ifdef ZIPRAND_HAVE_OPENSSL
+            /* the authentication code covers the encrypted bytes */
+            if (decoder->mac) {
+                ziprand_error_t err = update_mac(file, decoder->source_offset, decoder->input, got);
+                if (err != ZIPRAND_OK)
+                    return err;
+            }
+            if (decoder->aes) {
+                ziprand_error_t err = aes_decrypt(decoder->aes,
+                                                  decoder->source_offset - file->source_start,
+                                                  decoder->input,
+                                                  got);
+                if (err != ZIPRAND_OK)
+                    return err;
+            }
+#endif
+#ifdef ZIPRAND_CRYPTO
+            if (file->encrypted && !file->key_size)
+                zipcrypto_decrypt(&decoder->cipher, decoder->input, got);
+#endif
+            decoder->input_pos = 0;
+            decoder->input_len = got;
+            decoder->source_offset += got;
+        }
+
+        size_t consumed = 0;
+        size_t produced = 0;
+        ziprand_error_t err = file->codec->decode(decoder->state,
+                                                  decoder->input + decoder->input_pos,
+                                                  decoder->input_len - decoder->input_pos,
+                                                  &consumed,
+                                                  out,
+                                                  out_size,
+                                                  &produced,
+                                                  &decoder->finished);
+        if (err != ZIPRAND_OK)
+            return err;
+        decoder->input_pos += consumed;
+        decoder->offset += produced;
+        if (produced > 0)
+            return produced;
+
+        /* no progress: the compressed data ended before the stream did */
+        if (consumed == 0 && !decoder->finished)
+            return ZIPRAND_ERR_DECOMPRESS;
+    }
+    return 0;
+}
+
+#ifdef ZIPRAND_HAVE_ZLIB
+/* last checkpoint at or before offset, or NULL */
+static const deflate_point_t* find_deflate_point(const ziprand_deflate_index_t* index,
+                                                 uint64_t offset)
+{
+    size_t lo = 0;
+    size_t hi = index->count;
+    while (lo < hi) {
+        size_t mid = lo + (hi - lo) / 2;
+        if (index->points[mid].offset <= offset)
+            lo = mid + 1;
+        else
+            hi = mid;
+    }
+    return lo > 0 ? &index->points[lo - 1] : NULL;
+}
+
+/* carry on from a checkpoint: the stream's bit position and history */
+static ziprand_error_t restore_deflate_point(decoder_t* decoder, const deflate_point_t* point)
+{
+    z_stream* stream = decoder->state;
+    if (point->bits && inflatePrime(stream, point->bits, point->byte >> (8 - point->bits)) != Z_OK)
+        return ZIPRAND_ERR_DECOMPRESS;
+    if (inflateSetDictionary(stream, point->window, DEFLATE_WINDOW_SIZE) != Z_OK)
+        return ZIPRAND_ERR_DECOMPRESS;
+    return ZIPRAND_OK;
+}
+#endif
+
+/* reset a decoder to decode from a place where decoding can start afresh */
+static ziprand_error_t
+restart_decoder(ziprand_file_t* file, uint64_t offset, uint64_t source_offset)
+{
+    decoder_t* decoder = file->decoder;
+    if (file->codec->reset(decoder->state) != ZIPRAND_OK)
+        return ZIPRAND_ERR_DECOMPRESS;
+    decoder->input_pos = 0;
+    decoder->input_len = 0;
+    decoder->source_offset = source_offset;
+    decoder->offset = offset;
+    decoder->finished = 0;
+    decoder->cipher = file->cipher;
+    return ZIPRAND_OK;
+}
+
+/* move a decoder to where it can decode offset from: the nearest frame or index checkpoint
+ * if that saves work, or the start of the entry for an offset it has already passed */
+static ziprand_error_t seek_decoder(ziprand_file_t* file, uint64_t offset)
+{
+    decoder_t* decoder = file->decoder;
+
+    if (decoder->frame_count) {
+        size_t lo = 0;
+        size_t hi = decoder->frame_count;
+        while (hi - lo > 1) {
+            size_t mid = lo + (hi - lo) / 2;
+            if (decoder->frame_offsets[mid] <= offset)
+                lo = mid;
+            else
+                hi = mid;
+        }
+        uint64_t start = decoder->frame_offsets[lo];
+        if (offset < decoder->offset || start > decoder->offset)
+            return restart_decoder(file, start, decoder->frame_sources[lo]);
+        return ZIPRAND_OK;
+    }
+
+#ifdef ZIPRAND_HAVE_ZLIB
+    const deflate_point_t* point = file->index ? find_deflate_point(file->index, offset) : NULL;
+    if (point && (offset < decoder->offset || point->offset > decoder->offset)) {
+        ziprand_error_t err = restart_decoder(file, point->offset, point->source_offset);
+        return err == ZIPRAND_OK ? restore_deflate_point(decoder, point) : err;
+    }
+#endif
+
+#ifdef ZIPRAND_HAVE_OPENSSL
+    /* stored data under AES can be decrypted from anywhere */
+    if (file->codec == &copy_codec && file->key_size) {
+        if (offset == decoder->offset)
+            return ZIPRAND_OK;
+        return restart_decoder(file, offset, file->source_start + offset);
+    }
+#endif
+
+    if (offset >= decoder->offset)
+        return ZIPRAND_OK;
+    return restart_decoder(file, 0, file->source_start);
+}
+
+/* read uncompressed bytes of a compressed entry; streams only decode forward, so reading
+ * before the decoder's position restarts from a checkpoint or the beginning of the entry */
+static int64_t read_decoded(ziprand_file_t* file, uint64_t offset, uint8_t* buffer, size_t size)
+{
+    decoder_t* decoder = file->decoder;
+    if (size == 0)
+        return 0;
+
+    /* data that failed authentication stays unreadable */
+    if (file->error == ZIPRAND_ERR_AUTH_FAILED)
+        return file->error;
+
+    if (!decoder) {
+        decoder = calloc(1, sizeof(decoder_t));
+        if (!decoder)
+            return ZIPRAND_ERR_NOMEM;
+        decoder->state = file->codec->create(file->codec->ctx, file->entry);
+        decoder->input = malloc(DECODE_INPUT_SIZE);
+        if (!decoder->state || !decoder->input) {
+            decoder_free(file->codec, decoder);
+            return ZIPRAND_ERR_NOMEM;
+        }
+        decoder->source_offset = file->source_start;
+        decoder->cipher = file->cipher;
+#ifdef ZIPRAND_HAVE_OPENSSL
+        if (file->key_size) {
+            decoder->aes = aes_create(file->key, file->key_size);
+            decoder->mac = hmac_create(file->auth_key, file->key_size);
+            decoder->mac_offset = file->source_start;
+            if (!decoder->aes || !decoder->mac) {
+                decoder_free(file->codec, decoder);
+                return ZIPRAND_ERR_NOMEM;
+            }
+        }
+#endif
+#ifdef ZIPRAND_HAVE_ZSTD
+        if (file->codec == &zstd_codec && !file->encrypted) {
+            ziprand_error_t err = load_zstd_seek_table(file, decoder);
+            if (err != ZIPRAND_OK) {
+                decoder_free(file->codec, decoder);
+                return err;
+            }
+        }
+#endif
+        file->decoder = decoder;
+    }
+
+    ziprand_error_t err = seek_decoder(file, offset);
+    if (err != ZIPRAND_OK)
+        return err;
+
+    /* decode and drop everything before offset, using the caller's buffer as scratch space */
+    while (decoder->offset < offset) {
+        uint64_t skip = offset - decoder->offset;
+        int64_t got = decode_some(file, buffer, skip < size ? (size_t)skip : size);
+        if (got <= 0)
+            return got < 0 ? got : ZIPRAND_ERR_DECOMPRESS;
+    }
+
+    size_t done = 0;
+    while (done < size) {
+        int64_t got = decode_some(file, buffer + done, size - done);
+        /* a failed authentication fails the whole read */
+        if (got < 0)
+            return done > 0 && got != ZIPRAND_ERR_AUTH_FAILED ? (int64_t)done : got;
+        if (got == 0)
+            break;
+        done += got;
+    }
+
+    /* at the end of the entry, let the codec reach the end of its stream, which is where
+     * some check their own checksums; data beyond the entry size is an error too */
+    if (decoder->offset == file->entry->uncompressed_size && !decoder->finished) {
+        uint8_t extra;
+        int64_t got = decode_some(file, &extra, 1);
+        if (got != 0)
+            return got < 0 ? got : ZIPRAND_ERR_DECOMPRESS;
+    }
+    return done;
+}
+
+int64_t ziprand_fread_at(ziprand_file_t* file, uint64_t offset, void* buffer, size_t size)
+{
+    if (!file || !buffer)
+        return -1;
 
-    for (size_t i = 0; i < archive->entry_count; i++) {
-        if (strcmp(archive->entries[i].name, name) == 0)
-            return &archive->entries[i];
+    if (offset >= file->size)
+        return 0;
+
+    uint64_t remaining = file->size - offset;
+    size_t to_read = size < remaining ? size : remaining;
+
+    if (file->codec)
+        return read_decoded(file, offset, buffer, to_read);
+    return io_read(&file->archive->io, file->entry->data_offset + offset, buffer, to_read);
+}
+
+int64_t ziprand_fread_at_vectored(ziprand_file_t* file,
+                                  uint64_t offset,
+                                  const ziprand_iovec_t* iov,
+                                  size_t iovcnt)
+{
+    if (!file || (!iov && iovcnt > 0))
+        return -1;
+
+    if (offset >= file->size)
+        return 0;
+
+    /* trim the buffer list so the read stays within the file */
+    uint64_t remaining = file->size - offset;
+    ziprand_iovec_t* trimmed = NULL;
+    uint64_t total = 0;
+    size_t count = 0;
+    while (count < iovcnt && total < remaining) {
+        total += iov[count].len;
+        count++;
     }
-    return NULL;
+    if (total > remaining) {
+        trimmed = malloc(count * sizeof(ziprand_iovec_t));
+        if (!trimmed)
+            return -1;
+        memcpy(trimmed, iov, count * sizeof(ziprand_iovec_t));
+        trimmed[count - 1].len -= total - remaining;
+        iov = trimmed;
+    }
+
+    const ziprand_io_t* io = &file->archive->io;
+    uint64_t source_offset = file->entry->data_offset + offset;
+    int64_t result = 0;
+    if (file->codec) {
+        for (size_t i = 0; i < count; i++) {
+            int64_t got = read_decoded(file, offset + result, iov[i].base, iov[i].len);
+            if (got < 0) {
+                result = got;
+                break;
+            }
+            result += got;
+            if ((size_t)got < iov[i].len)
+                break;
+        }
+    } else if (io->read_vectored) {
+        result = io_read_vectored(io, source_offset, iov, count);
+    } else {
+        for (size_t i = 0; i < count; i++) {
+            int64_t got = io_read(io, source_offset + result, iov[i].base, iov[i].len);
+            if (got < 0) {
+                result = got;
+                break;
+            }
+            result += got;
+            if ((size_t)got < iov[i].len)
+                break;
+        }
+    }
+
+    free(trimmed);
+    return result;
 }
 
-ziprand_file_t* ziprand_fopen(ziprand_archive_t* archive, const ziprand_entry_t* entry)
+static void release_allocated_buffer(ziprand_buffer_t* buffer)
 {
-    if (!archive || !entry)
+    free(buffer->owner);
+}
+
+ziprand_error_t
+ziprand_fread_buffer(ziprand_file_t* file, uint64_t offset, size_t size, ziprand_buffer_t* buffer)
+{
+    if (!file || !buffer)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    buffer->data = NULL;
+    buffer->size = 0;
+    buffer->release = NULL;
+    buffer->owner = NULL;
+
+    if (offset >= file->size)
+        return ZIPRAND_OK;
+
+    uint64_t remaining = file->size - offset;
+    if (size > remaining)
+        size = remaining;
+
+    const ziprand_io_t* io = &file->archive->io;
+    uint64_t source_offset = file->entry->data_offset + offset;
+    if (io->read_buffer && !file->codec)
+        return io_read_buffer(io, source_offset, size, buffer);
+
+    uint8_t* data = malloc(size ? size : 1);
+    if (!data)
+        return ZIPRAND_ERR_NOMEM;
+
+    /* the I/O callback may return short reads */
+    size_t done = 0;
+    while (done < size) {
+        int64_t got = ziprand_fread_at(file, offset + done, data + done, size - done);
+        if (got <= 0) {
+            free(data);
+            return got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+        }
+        done += got;
+    }
+
+    buffer->data = data;
+    buffer->size = size;
+    buffer->release = release_allocated_buffer;
+    buffer->owner = data;
+    return ZIPRAND_OK;
+}
+
+const void* ziprand_fdata(const ziprand_file_t* file)
+{
+    if (!file || !file->archive->io.mapped || file->codec)
         return NULL;
 
-    if (entry->compression_method != 0)
+    /* sizes come from the archive, make sure the entry really is inside the source */
+    const ziprand_entry_t* entry = file->entry;
+    if (file->size > file->archive->total_size ||
+        entry->data_offset > file->archive->total_size - file->size)
         return NULL;
 
-    /* calculate data offset if not already done */
-    ziprand_entry_t* mutable_entry = (ziprand_entry_t*)entry;
-    if (mutable_entry->data_offset == 0) {
-        if (get_data_offset(archive, mutable_entry) != ZIPRAND_OK)
-            return NULL;
-    }
+    return (const uint8_t*)file->archive->io.mapped + entry->data_offset;
+}
 
-    ziprand_file_t* file = malloc(sizeof(ziprand_file_t));
+ziprand_error_t ziprand_fsource_range(const ziprand_file_t* file,
+                                      const ziprand_io_t** io,
+                                      uint64_t* offset,
+                                      uint64_t* size)
+{
     if (!file)
-        return NULL;
+        return ZIPRAND_ERR_INVALID_PARAM;
+    if (file->encrypted)
+        return ZIPRAND_ERR_ENCRYPTED;
+    if (file->codec)
+        return ZIPRAND_ERR_COMPRESSED;
+    if (io)
+        *io = &file->archive->io;
+    if (offset)
+        *offset = file->entry->data_offset;
+    if (size)
+        *size = file->size;
+    return ZIPRAND_OK;
+}
 
-    file->archive = archive;
-    file->entry = entry;
-    file->position = 0;
+void ziprand_buffer_release(ziprand_buffer_t* buffer)
+{
+    if (!buffer)
+        return;
+    if (buffer->release)
+        buffer->release(buffer);
+    buffer->data = NULL;
+    buffer->size = 0;
+    buffer->release = NULL;
+    buffer->owner = NULL;
+}
 
-    return file;
+/* refuse to allocate for reads that are over the limits or cannot be backed by the archive */
+static ziprand_error_t check_read_size(ziprand_file_t* file, uint64_t size, uint64_t max_size)
+{
+    const ziprand_entry_t* entry = file->entry;
+    uint64_t limit = file->archive->options.limits.max_read_size;
+    uint64_t stored = file->codec ? entry->compressed_size : file->size;
+
+    if (stored > file->archive->total_size ||
+        entry->data_offset > file->archive->total_size - stored)
+        return ZIPRAND_ERR_INVALID_ZIP;
+    if ((max_size && size > max_size) || (limit && size > limit))
+        return ZIPRAND_ERR_ENTRY_TOO_LARGE;
+    if (size > SIZE_MAX - 1)
+        return ZIPRAND_ERR_NOMEM;
+    return ZIPRAND_OK;
 }
 
-ziprand_file_t* ziprand_fopen_by_name(ziprand_archive_t* archive, const char* name)
+ziprand_error_t ziprand_fcheck_size(ziprand_file_t* file, uint64_t max_size)
 {
-    const ziprand_entry_t* entry = ziprand_find_entry(archive, name);
-    if (!entry)
+    if (!file)
+        return ZIPRAND_ERR_INVALID_PARAM;
+    return check_read_size(file, file->size, max_size);
+}
+
+void* ziprand_fread_all(ziprand_file_t* file, uint64_t max_size, ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    *error = ziprand_fcheck_size(file, max_size);
+    if (*error != ZIPRAND_OK)
         return NULL;
-    return ziprand_fopen(archive, entry);
+
+    return ziprand_fread_range(file, 0, file->size, error);
 }
 
-int64_t ziprand_fread(ziprand_file_t* file, void* buffer, size_t size)
+void* ziprand_fread_range(ziprand_file_t* file,
+                          uint64_t start,
+                          uint64_t end,
+                          ziprand_error_t* error)
 {
-    if (!file)
-        return -1;
-    int64_t result = ziprand_fread_at(file, file->position, buffer, size);
-    if (result > 0)
-        file->position += result;
-    return result;
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!file) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+    if (start > end || end > file->size) {
+        *error = ZIPRAND_ERR_SEEK_BEYOND_END;
+        return NULL;
+    }
+    *error = check_read_size(file, end - start, 0);
+    if (*error != ZIPRAND_OK)
+        return NULL;
+
+    size_t size = end - start;
+    uint8_t* buffer = malloc(size ? size : 1);
+    if (!buffer) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    /* the I/O callback may return short reads */
+    size_t done = 0;
+    while (done < size) {
+        int64_t got = ziprand_fread_at(file, start + done, buffer + done, size - done);
+        if (got <= 0) {
+            free(buffer);
+            *error = got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+            return NULL;
+        }
+        done += got;
+    }
+
+    *error = ZIPRAND_OK;
+    return buffer;
 }
 
-int64_t ziprand_fread_at(ziprand_file_t* file, uint64_t offset, void* buffer, size_t size)
+int64_t ziprand_fcopy(ziprand_file_t* file, ziprand_write_fn write, void* ctx, size_t chunk_size)
 {
-    if (!file || !buffer)
-        return -1;
+    if (!file || !write)
+        return ZIPRAND_ERR_INVALID_PARAM;
 
-    if (offset >= file->entry->uncompressed_size)
-        return 0;
+    if (chunk_size == 0)
+        chunk_size = COPY_CHUNK_SIZE;
 
-    uint64_t remaining = file->entry->uncompressed_size - offset;
-    size_t to_read = size < remaining ? size : remaining;
+    uint8_t* buffer = malloc(chunk_size);
+    if (!buffer)
+        return ZIPRAND_ERR_NOMEM;
+
+    int64_t copied = 0;
+    while (file->position < file->size) {
+        /* go through ziprand_fread so verified handles check the CRC as they copy */
+        int64_t got = ziprand_fread(file, buffer, chunk_size);
+        if (got <= 0) {
+            free(buffer);
+            if (file->error != ZIPRAND_OK)
+                return file->error;
+            return got < 0 ? got : ZIPRAND_ERR_IO;
+        }
+
+        ziprand_error_t err = write_all(write, ctx, buffer, got);
+        if (err != ZIPRAND_OK) {
+            free(buffer);
+            return err;
+        }
+
+        copied += got;
+    }
+
+    free(buffer);
+    return copied;
+}
+
+ziprand_chunk_iter_t* ziprand_chunk_iter_open(ziprand_file_t* file,
+                                              uint64_t offset,
+                                              size_t chunk_size,
+                                              ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!file) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+    if (offset > file->size) {
+        *error = ZIPRAND_ERR_SEEK_BEYOND_END;
+        return NULL;
+    }
+
+    ziprand_chunk_iter_t* iter = malloc(sizeof(ziprand_chunk_iter_t));
+    if (!iter) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    iter->file = file;
+    iter->offset = offset;
+    iter->chunk_size = chunk_size ? chunk_size : COPY_CHUNK_SIZE;
+    iter->buffer = malloc(iter->chunk_size);
+    if (!iter->buffer) {
+        free(iter);
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
 
-    return file->archive->io.read(
-        file->archive->io.ctx, file->entry->data_offset + offset, buffer, to_read);
+    *error = ZIPRAND_OK;
+    return iter;
+}
+
+ziprand_error_t
+ziprand_chunk_iter_next(ziprand_chunk_iter_t* iter, const void** data, size_t* size)
+{
+    if (!iter || !data || !size)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    *data = NULL;
+    *size = 0;
+    if (iter->offset >= iter->file->size)
+        return ZIPRAND_OK;
+
+    int64_t got = ziprand_fread_at(iter->file, iter->offset, iter->buffer, iter->chunk_size);
+    if (got <= 0)
+        return got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+
+    iter->offset += got;
+    *data = iter->buffer;
+    *size = got;
+    return ZIPRAND_OK;
+}
+
+void ziprand_chunk_iter_close(ziprand_chunk_iter_t* iter)
+{
+    if (!iter)
+        return;
+    free(iter->buffer);
+    free(iter);
 }
 
 int64_t ziprand_fseek(ziprand_file_t* file, int64_t offset, int whence)
@@ -419,19 +4961,19 @@ int64_t ziprand_fseek(ziprand_file_t* file, int64_t offset, int whence)
         break;
     case SEEK_END:
         if (offset >= 0) {
-            new_pos = file->entry->uncompressed_size + offset;
+            new_pos = file->size + offset;
         } else {
-            if ((uint64_t)(-offset) > file->entry->uncompressed_size)
+            if ((uint64_t)(-offset) > file->size)
                 new_pos = 0;
             else
-                new_pos = file->entry->uncompressed_size - (-offset);
+                new_pos = file->size - (-offset);
         }
         break;
     default:
         return -1;
     }
 
-    if (new_pos > file->entry->uncompressed_size)
+    if (new_pos > file->size)
         return -1;
 
     file->position = new_pos;
@@ -445,14 +4987,354 @@ int64_t ziprand_ftell(ziprand_file_t* file)
 
 int64_t ziprand_fsize(ziprand_file_t* file)
 {
-    return file ? (int64_t)file->entry->uncompressed_size : -1;
+    return file ? (int64_t)file->size : -1;
 }
 
 void ziprand_fclose(ziprand_file_t* file)
 {
+    if (!file)
+        return;
+    decoder_free(file->codec, file->decoder);
+    free(file->readahead);
     free(file);
 }
 
+ziprand_error_t ziprand_ferror(const ziprand_file_t* file)
+{
+    if (!file)
+        return ZIPRAND_ERR_INVALID_PARAM;
+    return file->error;
+}
+
+ziprand_error_t
+ziprand_prefetch(ziprand_archive_t* archive, const ziprand_entry_t** entries, size_t count)
+{
+    if (!archive || (!entries && count > 0))
+        return ZIPRAND_ERR_INVALID_PARAM;
+    if (!archive->io.prefetch || count == 0)
+        return ZIPRAND_OK;
+
+    ziprand_range_t* ranges = malloc(count * sizeof(ziprand_range_t));
+    if (!ranges)
+        return ZIPRAND_ERR_NOMEM;
+
+    for (size_t i = 0; i < count; i++) {
+        const ziprand_entry_t* entry = entries[i];
+        if (!entry) {
+            free(ranges);
+            return ZIPRAND_ERR_INVALID_PARAM;
+        }
+
+        /* local header through the end of the data */
+        uint64_t header_size = entry->data_offset != 0
+                                   ? entry->data_offset - entry->offset
+                                   : 30 + entry->name_len + PREFETCH_EXTRA_SLACK;
+        ranges[i].offset = entry->offset;
+        ranges[i].size = header_size + entry->compressed_size;
+    }
+
+    io_prefetch(&archive->io, ranges, count);
+    free(ranges);
+    return ZIPRAND_OK;
+}
+
+static int compare_plan_items(const void* a, const void* b)
+{
+    const ziprand_plan_item_t* ia = a;
+    const ziprand_plan_item_t* ib = b;
+    uint64_t oa = ia->entry->data_offset;
+    uint64_t ob = ib->entry->data_offset;
+    return (oa > ob) - (oa < ob);
+}
+
+ziprand_read_plan_t* ziprand_plan_reads(ziprand_archive_t* archive,
+                                        const ziprand_entry_t** entries,
+                                        size_t count,
+                                        uint64_t max_gap,
+                                        uint64_t max_read_size,
+                                        ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!archive || (!entries && count > 0)) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    ziprand_read_plan_t* plan = calloc(1, sizeof(ziprand_read_plan_t));
+    if (plan && count > 0) {
+        plan->items = calloc(count, sizeof(ziprand_plan_item_t));
+        plan->reads = calloc(count, sizeof(ziprand_plan_read_t));
+    }
+    if (!plan || (count > 0 && (!plan->items || !plan->reads))) {
+        ziprand_read_plan_free(plan);
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    for (size_t i = 0; i < count; i++) {
+        if (!entries[i]) {
+            ziprand_read_plan_free(plan);
+            *error = ZIPRAND_ERR_INVALID_PARAM;
+            return NULL;
+        }
+    }
+
+    *error = resolve_data_offsets(archive, entries, count);
+    if (*error != ZIPRAND_OK) {
+        ziprand_read_plan_free(plan);
+        return NULL;
+    }
+
+    for (size_t i = 0; i < count; i++) {
+        const ziprand_entry_t* entry = entries[i];
+        plan->items[i].entry = entry;
+        plan->items[i].size = entry->compressed_size;
+    }
+    plan->item_count = count;
+
+    qsort(plan->items, count, sizeof(ziprand_plan_item_t), compare_plan_items);
+
+    if (max_gap == ZIPRAND_GAP_AUTO) {
+        ziprand_io_caps_t caps;
+        ziprand_io_capabilities(&archive->io, &caps);
+        if (caps.latency == ZIPRAND_LATENCY_REMOTE)
+            max_gap = caps.block_size;
+        else if (caps.latency == ZIPRAND_LATENCY_LOCAL)
+            max_gap = LOCAL_COALESCE_GAP;
+        else
+            max_gap = 0;
+    }
+
+    ziprand_plan_read_t* read = NULL;
+    for (size_t i = 0; i < count; i++) {
+        ziprand_plan_item_t* item = &plan->items[i];
+        uint64_t start = item->entry->data_offset;
+        uint64_t end = start + item->size;
+
+        /* extend the current read if the gap is small and the read stays within bounds */
+        uint64_t read_end = read ? read->offset + read->size : 0;
+        uint64_t merged_end = end > read_end ? end : read_end;
+        int merge = read && start <= read_end + max_gap &&
+                    (max_read_size == 0 || merged_end - read->offset <= max_read_size);
+        if (!merge) {
+            read = &plan->reads[plan->read_count++];
+            read->offset = start;
+            read->size = 0;
+            read->first_item = i;
+            read->item_count = 0;
+        }
+
+        if (end > read->offset + read->size)
+            read->size = end - read->offset;
+        item->read_index = plan->read_count - 1;
+        item->read_offset = start - read->offset;
+        read->item_count++;
+    }
+
+    *error = ZIPRAND_OK;
+    return plan;
+}
+
+int ziprand_read_plan_execute(ziprand_archive_t* archive,
+                              const ziprand_read_plan_t* plan,
+                              ziprand_plan_fn callback,
+                              void* ctx)
+{
+    if (!archive || !plan || !callback)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    /* a multi-range backend gets several reads per request, up to the batch size */
+    uint64_t batch_size = archive->io.read_ranges ? RANGES_BATCH_SIZE : 0;
+
+    ziprand_read_range_t* ranges = malloc((plan->read_count ? plan->read_count : 1) *
+                                          sizeof(ziprand_read_range_t));
+    if (!ranges)
+        return ZIPRAND_ERR_NOMEM;
+
+    uint8_t* buffer = NULL;
+    size_t capacity = 0;
+    int ret = ZIPRAND_OK;
+
+    size_t r = 0;
+    while (r < plan->read_count && ret == ZIPRAND_OK) {
+        uint64_t total = plan->reads[r].size;
+        size_t batch_end = r + 1;
+        while (batch_end < plan->read_count &&
+               total + plan->reads[batch_end].size <= batch_size) {
+            total += plan->reads[batch_end].size;
+            batch_end++;
+        }
+        if (total > SIZE_MAX) {
+            ret = ZIPRAND_ERR_NOMEM;
+            break;
+        }
+
+        if (total > capacity) {
+            uint8_t* grown = realloc(buffer, total);
+            if (!grown) {
+                ret = ZIPRAND_ERR_NOMEM;
+                break;
+            }
+            buffer = grown;
+            capacity = total;
+        }
+
+        size_t range_count = 0;
+        size_t used = 0;
+        for (size_t b = r; b < batch_end; b++) {
+            if (plan->reads[b].size == 0)
+                continue;
+            ranges[range_count].offset = plan->reads[b].offset;
+            ranges[range_count].buffer = buffer + used;
+            ranges[range_count].size = plan->reads[b].size;
+            range_count++;
+            used += plan->reads[b].size;
+        }
+        ret = io_read_ranges(&archive->io, ranges, range_count);
+
+        used = 0;
+        for (; r < batch_end && ret == ZIPRAND_OK; r++) {
+            const ziprand_plan_read_t* read = &plan->reads[r];
+            for (size_t i = 0; i < read->item_count && ret == ZIPRAND_OK; i++) {
+                const ziprand_plan_item_t* item = &plan->items[read->first_item + i];
+                ret = callback(item->entry, buffer + used + item->read_offset, item->size, ctx);
+            }
+            used += read->size;
+        }
+    }
+
+    free(ranges);
+    free(buffer);
+    return ret;
+}
+
+void ziprand_read_plan_free(ziprand_read_plan_t* plan)
+{
+    if (!plan)
+        return;
+    free(plan->reads);
+    free(plan->items);
+    free(plan);
+}
+
+ziprand_concat_t* ziprand_concat_open(ziprand_archive_t* archive,
+                                      const ziprand_entry_t** entries,
+                                      size_t count,
+                                      ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    if (!archive || (!entries && count > 0)) {
+        *error = ZIPRAND_ERR_INVALID_PARAM;
+        return NULL;
+    }
+
+    ziprand_concat_t* concat = calloc(1, sizeof(ziprand_concat_t));
+    if (!concat) {
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    concat->files = calloc(count ? count : 1, sizeof(ziprand_file_t*));
+    concat->starts = malloc((count + 1) * sizeof(uint64_t));
+    if (!concat->files || !concat->starts) {
+        ziprand_concat_close(concat);
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    uint64_t total = 0;
+    for (size_t i = 0; i < count; i++) {
+        if (!entries[i] || entries[i]->compression_method != 0 ||
+            (entries[i]->flags & FLAG_ENCRYPTED)) {
+            ziprand_concat_close(concat);
+            if (!entries[i])
+                *error = ZIPRAND_ERR_INVALID_PARAM;
+            else if (entries[i]->flags & FLAG_ENCRYPTED)
+                *error = ZIPRAND_ERR_ENCRYPTED;
+            else
+                *error = ZIPRAND_ERR_COMPRESSED;
+            return NULL;
+        }
+
+        concat->files[i] = open_file(archive, entries[i], 0, NULL, error);
+        concat->count = i + 1;
+        if (!concat->files[i]) {
+            ziprand_concat_close(concat);
+            return NULL;
+        }
+
+        concat->starts[i] = total;
+        total += entries[i]->uncompressed_size;
+    }
+    concat->starts[count] = total;
+
+    *error = ZIPRAND_OK;
+    return concat;
+}
+
+uint64_t ziprand_concat_size(const ziprand_concat_t* concat)
+{
+    return concat ? concat->starts[concat->count] : 0;
+}
+
+int64_t
+ziprand_concat_read_at(ziprand_concat_t* concat, uint64_t offset, void* buffer, size_t size)
+{
+    if (!concat || !buffer)
+        return -1;
+
+    if (offset >= concat->starts[concat->count])
+        return 0;
+
+    /* last piece starting at or before offset; empty pieces are skipped by the read loop */
+    size_t lo = 0;
+    size_t hi = concat->count;
+    while (hi - lo > 1) {
+        size_t mid = lo + (hi - lo) / 2;
+        if (concat->starts[mid] <= offset)
+            lo = mid;
+        else
+            hi = mid;
+    }
+
+    uint8_t* out = buffer;
+    size_t done = 0;
+    for (size_t i = lo; i < concat->count && done < size; i++) {
+        uint64_t piece_offset = offset + done - concat->starts[i];
+        uint64_t piece_size = concat->starts[i + 1] - concat->starts[i];
+        if (piece_offset >= piece_size)
+            continue;
+
+        uint64_t piece_remaining = piece_size - piece_offset;
+        size_t want = size - done < piece_remaining ? size - done : piece_remaining;
+        int64_t got = ziprand_fread_at(concat->files[i], piece_offset, out + done, want);
+        if (got < 0)
+            return done > 0 ? (int64_t)done : got;
+        done += got;
+        if ((size_t)got < want)
+            break;
+    }
+
+    return done;
+}
+
+void ziprand_concat_close(ziprand_concat_t* concat)
+{
+    if (!concat)
+        return;
+    for (size_t i = 0; i < concat->count; i++)
+        ziprand_fclose(concat->files[i]);
+    free(concat->files);
+    free(concat->starts);
+    free(concat);
+}
+
 const char* ziprand_strerror(ziprand_error_t error)
 {
     switch (error) {
@@ -472,6 +5354,30 @@ const char* ziprand_strerror(ziprand_error_t error)
         return "Invalid parameter";
     case ZIPRAND_ERR_SEEK_BEYOND_END:
         return "Seek beyond end of file";
+    case ZIPRAND_ERR_DUPLICATE_ENTRY:
+        return "Duplicate entry name";
+    case ZIPRAND_ERR_INVALID_INDEX:
+        return "Invalid index file";
+    case ZIPRAND_ERR_STALE_INDEX:
+        return "Index does not match archive";
+    case ZIPRAND_ERR_LIMIT_EXCEEDED:
+        return "Archive exceeds parsing limits";
+    case ZIPRAND_ERR_CRC_MISMATCH:
+        return "CRC-32 mismatch";
+    case ZIPRAND_ERR_ENTRY_TOO_LARGE:
+        return "Entry too large to read into memory";
+    case ZIPRAND_ERR_SOURCE_CHANGED:
+        return "Archive source changed";
+    case ZIPRAND_ERR_TIMEOUT:
+        return "Operation timed out";
+    case ZIPRAND_ERR_DECOMPRESS:
+        return "Corrupt compressed data";
+    case ZIPRAND_ERR_ENCRYPTED:
+        return "Entry is encrypted";
+    case ZIPRAND_ERR_WRONG_PASSWORD:
+        return "Wrong password";
+    case ZIPRAND_ERR_AUTH_FAILED:
+        return "Authentication code mismatch";
     default:
         return "Unknown error";
     }
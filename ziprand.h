@@ -3,6 +3,7 @@
 
 #include <stddef.h>
 #include <stdint.h>
+#include <stdio.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -17,7 +18,19 @@ typedef enum {
     ZIPRAND_ERR_COMPRESSED = -4,
     ZIPRAND_ERR_NOMEM = -5,
     ZIPRAND_ERR_INVALID_PARAM = -6,
-    ZIPRAND_ERR_SEEK_BEYOND_END = -7
+    ZIPRAND_ERR_SEEK_BEYOND_END = -7,
+    ZIPRAND_ERR_DUPLICATE_ENTRY = -8,
+    ZIPRAND_ERR_INVALID_INDEX = -9,
+    ZIPRAND_ERR_STALE_INDEX = -10,
+    ZIPRAND_ERR_LIMIT_EXCEEDED = -11,
+    ZIPRAND_ERR_CRC_MISMATCH = -12,
+    ZIPRAND_ERR_ENTRY_TOO_LARGE = -13,
+    ZIPRAND_ERR_SOURCE_CHANGED = -14,
+    ZIPRAND_ERR_TIMEOUT = -15,
+    ZIPRAND_ERR_DECOMPRESS = -16,
+    ZIPRAND_ERR_ENCRYPTED = -17,
+    ZIPRAND_ERR_WRONG_PASSWORD = -18,
+    ZIPRAND_ERR_AUTH_FAILED = -19
 } ziprand_error_t;
 
 /* I/O callback function types */
@@ -29,7 +42,8 @@ typedef struct ziprand_io ziprand_io_t;
  * @param offset Absolute offset to read from
  * @param buffer Buffer to read into
  * @param size Number of bytes to read
- * @return Number of bytes read, or -1 on error
+ * @return Number of bytes read, or -1 on error (or a negative ziprand_error_t for a specific
+ *         error, e.g. ZIPRAND_ERR_SOURCE_CHANGED, which reads pass on to the caller)
  */
 typedef int64_t (*ziprand_read_fn)(void* io_ctx, uint64_t offset, void* buffer, size_t size);
 
@@ -46,12 +60,130 @@ typedef int64_t (*ziprand_size_fn)(void* io_ctx);
  */
 typedef void (*ziprand_close_fn)(void* io_ctx);
 
+/* Byte range of the source */
+typedef struct {
+    uint64_t offset; /* Absolute offset */
+    uint64_t size;   /* Size in bytes */
+} ziprand_range_t;
+
+/* Buffer of a vectored read */
+typedef struct {
+    void* base; /* Buffer start */
+    size_t len; /* Buffer size in bytes */
+} ziprand_iovec_t;
+
+/**
+ * Optional vectored read callback - fills several buffers from consecutive source bytes
+ * Lets scatter-gather backends (preadv, io_uring) serve a vectored read with one request.
+ * @param io_ctx User-provided context
+ * @param offset Absolute offset to read from
+ * @param iov Buffers to fill, in order
+ * @param iovcnt Number of buffers
+ * @return Total number of bytes read, or -1 on error
+ */
+typedef int64_t (*ziprand_read_vectored_fn)(void* io_ctx,
+                                            uint64_t offset,
+                                            const ziprand_iovec_t* iov,
+                                            size_t iovcnt);
+
+/* One range of a multi-range read */
+typedef struct {
+    uint64_t offset; /* Absolute offset */
+    void* buffer;    /* Buffer to fill */
+    size_t size;     /* Number of bytes to read */
+} ziprand_read_range_t;
+
+/**
+ * Optional multi-range read callback - fills several discontiguous ranges at once
+ * Lets backends that serve many ranges in one round trip (HTTP multipart range requests,
+ * object store batch reads) do so for batch opens and read plans. Ranges may come in any
+ * order and lie within the source.
+ * @param io_ctx User-provided context
+ * @param ranges Ranges to read
+ * @param count Number of ranges
+ * @return ZIPRAND_OK once every buffer is filled completely, or error code
+ */
+typedef ziprand_error_t (*ziprand_read_ranges_fn)(void* io_ctx,
+                                                  const ziprand_read_range_t* ranges,
+                                                  size_t count);
+
+/* Read-only buffer handed out by ziprand_fread_buffer */
+typedef struct ziprand_buffer {
+    const void* data;                               /* Buffer contents */
+    size_t size;                                    /* Size in bytes */
+    void (*release)(struct ziprand_buffer* buffer); /* Frees the buffer (NULL if not needed) */
+    void* owner;                                    /* Backend data used by release */
+} ziprand_buffer_t;
+
+/**
+ * Optional buffer read callback - returns a range as a buffer owned by the backend
+ * Lets mmap and object-store backends hand out their own memory instead of copying it
+ * into a caller buffer.
+ * @param io_ctx User-provided context
+ * @param offset Absolute offset to read from
+ * @param size Number of bytes wanted (the buffer may be shorter at the end of the source)
+ * @param buffer Receives the data and how to release it
+ * @return ZIPRAND_OK or error code
+ */
+typedef ziprand_error_t (*ziprand_read_buffer_fn)(void* io_ctx,
+                                                  uint64_t offset,
+                                                  size_t size,
+                                                  ziprand_buffer_t* buffer);
+
+/**
+ * Optional prefetch callback - hints that ranges will be read soon
+ * Backends may start fetching them in the background (page cache advice, range warming);
+ * the hint must not block on the data.
+ * @param io_ctx User-provided context
+ * @param ranges Ranges expected to be read
+ * @param count Number of ranges
+ */
+typedef void (*ziprand_prefetch_fn)(void* io_ctx, const ziprand_range_t* ranges, size_t count);
+
+/* How far away a backend's data is, which decides how reads are sized and coalesced */
+typedef enum {
+    ZIPRAND_LATENCY_LOCAL = 0,  /* Local disk: cheap requests, gaps worth skipping */
+    ZIPRAND_LATENCY_MEMORY = 1, /* In memory: requests cost nothing, nothing to coalesce */
+    ZIPRAND_LATENCY_REMOTE = 2  /* Network: round trips dominate, read large and in parallel */
+} ziprand_latency_t;
+
+/* Backend capability hints (zero fields = no preference) */
+typedef struct {
+    size_t block_size;         /* Preferred read size and alignment in bytes */
+    uint32_t max_concurrency;  /* Number of reads worth having in flight at once */
+    ziprand_latency_t latency; /* Latency class */
+} ziprand_io_caps_t;
+
+/**
+ * Optional capabilities callback - describes the backend so the reader can pick chunk sizes,
+ * coalescing and parallelism that suit it
+ * @param io_ctx User-provided context
+ * @param caps Hints to fill in; arrives with zero sizes and the latency class the library
+ *        would assume (memory if io->mapped is set, otherwise local)
+ */
+typedef void (*ziprand_capabilities_fn)(void* io_ctx, ziprand_io_caps_t* caps);
+
+/**
+ * Write callback - writes data to a sink (used to save indexes)
+ * @param ctx User-provided context
+ * @param buffer Data to write
+ * @param size Number of bytes to write
+ * @return Number of bytes written, or -1 on error
+ */
+typedef int64_t (*ziprand_write_fn)(void* ctx, const void* buffer, size_t size);
+
 /* I/O interface structure */
 struct ziprand_io {
-    void* ctx;                /* User-provided context pointer */
-    ziprand_read_fn read;     /* Read function */
-    ziprand_size_fn get_size; /* Get size function */
-    ziprand_close_fn close;   /* Optional close function (can be NULL) */
+    void* ctx;                              /* User-provided context pointer */
+    ziprand_read_fn read;                   /* Read function */
+    ziprand_size_fn get_size;               /* Get size function */
+    ziprand_close_fn close;                 /* Optional close function (can be NULL) */
+    ziprand_prefetch_fn prefetch;           /* Optional prefetch hint function (can be NULL) */
+    ziprand_read_vectored_fn read_vectored; /* Optional vectored read function (can be NULL) */
+    ziprand_read_buffer_fn read_buffer;     /* Optional zero-copy read function (can be NULL) */
+    const void* mapped;                     /* Optional whole source in memory (can be NULL) */
+    ziprand_capabilities_fn capabilities;   /* Optional capability hints function (can be NULL) */
+    ziprand_read_ranges_fn read_ranges;     /* Optional multi-range read function (can be NULL) */
 };
 
 /* ZIP entry information */
@@ -62,14 +194,292 @@ typedef struct {
     uint64_t offset;             /* Offset of local header */
     uint64_t data_offset;        /* Offset of actual data */
     uint16_t compression_method; /* 0 = stored, 8 = deflate, etc. */
+    size_t name_len;             /* Length of name in bytes, as stored in the archive */
+    uint16_t version_made_by;    /* Creator version; upper byte is the host system */
+    uint32_t external_attr;      /* External file attributes (Unix mode in upper 16 bits) */
+    uint32_t crc32;              /* CRC-32 of the uncompressed data */
+    uint16_t flags;              /* General purpose bit flags (bit 0: encrypted) */
+    uint16_t mod_time;           /* Last modification time, MS-DOS format */
+    uint16_t mod_date;           /* Last modification date, MS-DOS format */
+    uint8_t aes_strength;        /* WinZip AES key size: 1 = 128, 2 = 192, 3 = 256 bits, 0 = none */
+    uint8_t aes_version;         /* WinZip AES vendor version: 1 = AE-1, 2 = AE-2 (no CRC-32) */
 } ziprand_entry_t;
 
+/* How an entry's data is encrypted (ziprand_entry_encryption) */
+typedef enum {
+    ZIPRAND_ENCRYPTION_NONE = 0,       /* Not encrypted */
+    ZIPRAND_ENCRYPTION_ZIPCRYPTO = 1,  /* Traditional PKWARE encryption */
+    ZIPRAND_ENCRYPTION_AES_128 = 2,    /* WinZip AES with a 128-bit key */
+    ZIPRAND_ENCRYPTION_AES_192 = 3,    /* WinZip AES with a 192-bit key */
+    ZIPRAND_ENCRYPTION_AES_256 = 4,    /* WinZip AES with a 256-bit key */
+    ZIPRAND_ENCRYPTION_UNSUPPORTED = 5 /* PKWARE strong encryption or an unknown scheme */
+} ziprand_encryption_t;
+
+/* How ziprand_fopen_http serves an entry's stored bytes */
+typedef enum {
+    ZIPRAND_PASSTHROUGH_NONE = 0,     /* Decoded by the library: send without Content-Encoding */
+    ZIPRAND_PASSTHROUGH_IDENTITY = 1, /* Stored entry, bytes sent as they are */
+    ZIPRAND_PASSTHROUGH_DEFLATE = 2   /* Raw DEFLATE stream: send with Content-Encoding: deflate */
+} ziprand_passthrough_t;
+
+/* Quoted ETag length, including the terminator */
+#define ZIPRAND_ETAG_SIZE 64
+
+/* What an HTTP response for an entry needs (ziprand_fopen_http) */
+typedef struct {
+    ziprand_passthrough_t passthrough; /* Whether the stored bytes are sent as they are */
+    uint16_t compression_method;       /* Entry's compression method */
+    uint64_t compressed_size;          /* Entry's stored size */
+    uint64_t uncompressed_size;        /* Entry's size once decoded */
+    uint64_t content_length;           /* Bytes the returned handle reads (Content-Length) */
+    char etag[ZIPRAND_ETAG_SIZE];      /* Strong ETag with quotes, empty without a CRC-32 */
+} ziprand_http_entry_t;
+
+/* Archive-level metadata from the End of Central Directory records */
+typedef struct {
+    uint64_t eocd_offset;       /* Offset of the EOCD record */
+    uint64_t zip64_eocd_offset; /* Offset of the ZIP64 EOCD record (0 if absent) */
+    uint64_t cd_offset;         /* Offset of the central directory */
+    uint64_t cd_size;           /* Size of the central directory in bytes */
+    uint64_t total_entries;     /* Total number of entries */
+    uint32_t disk_number;       /* Number of this disk */
+    uint32_t cd_disk_number;    /* Disk where the central directory starts */
+    uint16_t comment_length;    /* Archive comment length in bytes */
+    int is_zip64;               /* Nonzero if ZIP64 EOCD records are present */
+} ziprand_archive_info_t;
+
+/* How lookups resolve entries that share the same name */
+typedef enum {
+    ZIPRAND_DUPLICATE_FIRST_WINS = 0, /* First central directory entry wins */
+    ZIPRAND_DUPLICATE_LAST_WINS = 1,  /* Last central directory entry wins (APK behaviour) */
+    ZIPRAND_DUPLICATE_ERROR = 2       /* Opening fails with ZIPRAND_ERR_DUPLICATE_ENTRY */
+} ziprand_duplicate_policy_t;
+
+/* Name lookup index built on the first ziprand_find_entry() call */
+typedef enum {
+    ZIPRAND_INDEX_HASH = 0,   /* Hash table: O(1) lookups, two words per entry */
+    ZIPRAND_INDEX_SORTED = 1, /* Sorted index array: O(log n) lookups, 4 bytes per entry */
+    ZIPRAND_INDEX_NONE = 2    /* No index: linear scan on every lookup */
+} ziprand_index_mode_t;
+
+/* How ziprand_find_entry() compares names */
+typedef enum {
+    ZIPRAND_MATCH_EXACT = 0,      /* Byte-for-byte comparison */
+    ZIPRAND_MATCH_IGNORE_CASE = 1 /* Fall back to ASCII case-insensitive matching */
+} ziprand_name_match_t;
+
+/* How strictly archive structures are validated */
+typedef enum {
+    ZIPRAND_PARSE_LENIENT = 0, /* Accept inconsistencies that do not prevent reading */
+    ZIPRAND_PARSE_STRICT = 1   /* Reject inconsistent headers, layouts and CD/local mismatches */
+} ziprand_parse_mode_t;
+
+/* Central directory parsing limits for untrusted archives (0 = unlimited) */
+typedef struct {
+    uint64_t max_entries;        /* Maximum number of entries */
+    uint32_t max_name_length;    /* Maximum entry name length in bytes */
+    uint32_t max_extra_length;   /* Maximum extra field length in bytes, per entry */
+    uint32_t max_comment_length; /* Maximum entry or archive comment length in bytes */
+    uint64_t max_metadata_size;  /* Maximum central directory size in bytes */
+    uint64_t max_read_size;      /* Maximum buffer allocated for one read of entry data */
+} ziprand_limits_t;
+
+/**
+ * Decompressor for one compression method, registered with ziprand_options_register_method.
+ * The library feeds decode() compressed bytes of the entry in order and asks for uncompressed
+ * bytes; seeking backwards calls reset() and decodes again from the start of the entry.
+ */
+typedef struct {
+    /* Create decoding state for an entry; NULL on allocation failure */
+    void* (*create)(void* ctx, const ziprand_entry_t* entry);
+    /* Return the state to the start of the compressed stream */
+    ziprand_error_t (*reset)(void* state);
+    /* Decode from in into out, reporting bytes used and written, and set *finished at the
+     * end of the compressed stream; in_size is 0 once the entry's compressed bytes are all
+     * passed in. ZIPRAND_ERR_DECOMPRESS on corrupt data */
+    ziprand_error_t (*decode)(void* state,
+                              const uint8_t* in,
+                              size_t in_size,
+                              size_t* consumed,
+                              uint8_t* out,
+                              size_t out_size,
+                              size_t* produced,
+                              int* finished);
+    void (*destroy)(void* state);
+    void* ctx; /* Passed to create */
+} ziprand_decompressor_t;
+
+/* Maximum number of methods registered in one ziprand_options_t */
+#define ZIPRAND_MAX_METHODS 8
+
+/* A registered compression method */
+typedef struct {
+    uint16_t method;
+    ziprand_decompressor_t decompressor;
+} ziprand_method_t;
+
+/**
+ * Supplies passwords for encrypted entries when they are opened without one, so
+ * interactive tools and keyrings can be asked only for the entries actually read.
+ * get() is called again after each wrong password until it gives up.
+ */
+typedef struct {
+    /* Write a NUL-terminated password for entry into buffer; attempt counts the wrong
+     * passwords already given for this open. Return 0 to try it, nonzero to give up */
+    int (*get)(void* ctx,
+               const ziprand_entry_t* entry,
+               unsigned int attempt,
+               char* buffer,
+               size_t size);
+    /* Told whether the password from get() was right (can be NULL) */
+    void (*result)(void* ctx,
+                   const ziprand_entry_t* entry,
+                   const char* password,
+                   int accepted);
+    void* ctx; /* Passed to get and result */
+} ziprand_password_provider_t;
+
+/* Archive open options (initialize with ziprand_options_init) */
+typedef struct {
+    ziprand_duplicate_policy_t duplicate_policy;   /* Duplicate name handling */
+    ziprand_index_mode_t index_mode;               /* Name lookup index */
+    ziprand_name_match_t name_match;               /* Name comparison for lookups */
+    ziprand_limits_t limits;                       /* Parsing limits (ZIPRAND_ERR_LIMIT_EXCEEDED) */
+    ziprand_parse_mode_t parse_mode;               /* Validation strictness */
+    ziprand_method_t methods[ZIPRAND_MAX_METHODS]; /* Registered decompressors */
+    size_t method_count;                           /* Entries used in methods */
+    ziprand_password_provider_t password_provider; /* Passwords for encrypted entries */
+} ziprand_options_t;
+
+/* Built-in filters for ziprand_list_entries_filtered (bitmask) */
+typedef enum {
+    ZIPRAND_FILTER_MACOSX = 1 << 0,      /* __MACOSX/ tree and ._* AppleDouble files */
+    ZIPRAND_FILTER_DS_STORE = 1 << 1,    /* .DS_Store files */
+    ZIPRAND_FILTER_THUMBS_DB = 1 << 2,   /* Thumbs.db and desktop.ini files */
+    ZIPRAND_FILTER_DIRECTORIES = 1 << 3, /* Zero-length directory placeholder entries */
+    ZIPRAND_FILTER_JUNK = 0x0F           /* All of the above */
+} ziprand_filter_flags_t;
+
+/**
+ * Entry predicate for custom filtering
+ * @param entry Entry being considered
+ * @param ctx User-provided context
+ * @return Nonzero to keep the entry, 0 to skip it
+ */
+typedef int (*ziprand_entry_pred_fn)(const ziprand_entry_t* entry, void* ctx);
+
+/* Entry filter: built-in flags plus an optional user predicate */
+typedef struct {
+    unsigned int flags;              /* ZIPRAND_FILTER_* bits of entries to skip */
+    ziprand_entry_pred_fn predicate; /* Optional predicate (can be NULL) */
+    void* predicate_ctx;             /* Context passed to predicate */
+} ziprand_entry_filter_t;
+
+/* Archive statistics from ziprand_get_stats */
+typedef struct {
+    uint64_t entry_count;                 /* Number of entries */
+    uint64_t directory_count;             /* Entries whose name ends in '/' */
+    uint64_t stored_count;                /* Entries stored without compression (method 0) */
+    uint64_t deflated_count;              /* Entries compressed with deflate (method 8) */
+    uint64_t other_method_count;          /* Entries using any other compression method */
+    uint64_t encrypted_count;             /* Entries whose data is encrypted */
+    uint64_t total_compressed_size;       /* Sum of compressed (stored) sizes */
+    uint64_t total_uncompressed_size;     /* Sum of uncompressed sizes */
+    const ziprand_entry_t* largest_entry; /* Entry with the largest uncompressed size */
+    uint64_t gap_bytes;                   /* Bytes between entries not used by any entry */
+} ziprand_stats_t;
+
+/* Archive regions reported by ziprand_detect_overlaps */
+typedef enum {
+    ZIPRAND_REGION_ENTRY = 0,             /* Another entry's local header and data */
+    ZIPRAND_REGION_CENTRAL_DIRECTORY = 1, /* Central directory */
+    ZIPRAND_REGION_EOCD = 2               /* (ZIP64) End of Central Directory records */
+} ziprand_region_t;
+
+/* Overlap between an entry's local header and data and another region */
+typedef struct {
+    const ziprand_entry_t* entry; /* Entry whose range overlaps */
+    ziprand_region_t region;      /* Kind of region it overlaps */
+    const ziprand_entry_t* other; /* Overlapped entry for ZIPRAND_REGION_ENTRY, else NULL */
+} ziprand_overlap_t;
+
+/* Node of the directory tree built from entry names (see ziprand_tree_find) */
+typedef struct ziprand_tree_node {
+    const char* name;                       /* Last path component ("" for the root) */
+    const ziprand_entry_t* entry;           /* Entry, or NULL for implied directories and root */
+    struct ziprand_tree_node* parent;       /* Parent directory (NULL for the root) */
+    struct ziprand_tree_node* first_child;  /* First child in name order (NULL if none) */
+    struct ziprand_tree_node* next_sibling; /* Next child of the same parent (NULL if last) */
+    int is_dir;                             /* Nonzero for directories */
+} ziprand_tree_node_t;
+
+/**
+ * Tree walk callback
+ * @param node Node being visited
+ * @param depth Depth below the starting node (1 for its children)
+ * @param ctx User-provided context
+ * @return 0 to continue, nonzero to stop the walk
+ */
+typedef int (*ziprand_tree_visit_fn)(const ziprand_tree_node_t* node, size_t depth, void* ctx);
+
 /* Main ZIP archive handle */
 typedef struct ziprand_archive ziprand_archive_t;
 
 /* ZIP file reader handle */
 typedef struct ziprand_file ziprand_file_t;
 
+/* Lazy central directory iterator handle */
+typedef struct ziprand_iter ziprand_iter_t;
+
+/* Chunked sequential reader over a file */
+typedef struct ziprand_chunk_iter ziprand_chunk_iter_t;
+
+/* Several entries read as one contiguous file */
+typedef struct ziprand_concat ziprand_concat_t;
+
+/* Restart points for random access into a DEFLATE entry */
+typedef struct ziprand_deflate_index ziprand_deflate_index_t;
+
+/* max_gap of ziprand_plan_reads: choose from the backend's latency class */
+#define ZIPRAND_GAP_AUTO UINT64_MAX
+
+/* One coalesced read of a read plan */
+typedef struct {
+    uint64_t offset;   /* Archive offset to read from */
+    uint64_t size;     /* Number of bytes to read */
+    size_t first_item; /* Index of the first item served by this read */
+    size_t item_count; /* Number of items served by this read */
+} ziprand_plan_read_t;
+
+/* Entry data served by a read of a read plan */
+typedef struct {
+    const ziprand_entry_t* entry; /* Entry */
+    size_t read_index;            /* Read containing the entry data */
+    uint64_t read_offset;         /* Offset of the entry data within that read */
+    uint64_t size;                /* Size of the entry data (compressed size) */
+} ziprand_plan_item_t;
+
+/* Read plan: entries ordered by data offset, adjacent ranges coalesced */
+typedef struct {
+    ziprand_plan_read_t* reads; /* Reads in ascending offset order */
+    size_t read_count;          /* Number of reads */
+    ziprand_plan_item_t* items; /* Items in ascending offset order */
+    size_t item_count;          /* Number of items */
+} ziprand_read_plan_t;
+
+/**
+ * Read plan callback, invoked once per planned entry
+ * @param entry Entry whose data is provided
+ * @param data Raw entry data as stored in the archive
+ * @param size Size of data in bytes
+ * @param ctx User-provided context
+ * @return 0 to continue, nonzero to stop
+ */
+typedef int (*ziprand_plan_fn)(const ziprand_entry_t* entry,
+                               const void* data,
+                               uint64_t size,
+                               void* ctx);
+
 /**
  * Open a ZIP archive using provided I/O callbacks
  * @param io I/O interface (copied internally)
@@ -77,12 +487,157 @@ typedef struct ziprand_file ziprand_file_t;
  */
 ziprand_archive_t* ziprand_open(const ziprand_io_t* io);
 
+/**
+ * Initialize open options with defaults
+ * @param options Options to initialize
+ */
+void ziprand_options_init(ziprand_options_t* options);
+
+/**
+ * Register a decompressor for a compression method, used by archives opened with these
+ * options. It takes precedence over a built-in decompressor for the same method and
+ * replaces an earlier registration of it.
+ * @param options Options to register with
+ * @param method ZIP compression method ID (not 0, stored)
+ * @param decompressor Callbacks, copied into the options
+ * @return ZIPRAND_OK, ZIPRAND_ERR_INVALID_PARAM if a callback is missing, or
+ *         ZIPRAND_ERR_LIMIT_EXCEEDED if ZIPRAND_MAX_METHODS are already registered
+ */
+ziprand_error_t ziprand_options_register_method(ziprand_options_t* options,
+                                                uint16_t method,
+                                                const ziprand_decompressor_t* decompressor);
+
+/**
+ * Open a ZIP archive with options
+ * @param io I/O interface (copied internally)
+ * @param options Open options, or NULL for defaults
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Archive handle or NULL on error
+ */
+ziprand_archive_t*
+ziprand_open_ex(const ziprand_io_t* io, const ziprand_options_t* options, ziprand_error_t* error);
+
+/**
+ * Save the parsed central directory as an index that can be reused across runs
+ * @param archive Archive handle
+ * @param write Write callback receiving the serialized index
+ * @param ctx Context passed to write
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_index_save(ziprand_archive_t* archive, ziprand_write_fn write, void* ctx);
+
+/**
+ * Open a ZIP archive from a previously saved index, skipping central directory parsing
+ * The archive's size and EOCD record are checked against the index; a regenerated archive
+ * fails with ZIPRAND_ERR_STALE_INDEX.
+ * @param io I/O interface for the archive (copied internally)
+ * @param index_io I/O interface for the saved index (only used during this call)
+ * @param options Open options, or NULL for defaults
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Archive handle or NULL on error
+ */
+ziprand_archive_t* ziprand_open_with_index(const ziprand_io_t* io,
+                                           const ziprand_io_t* index_io,
+                                           const ziprand_options_t* options,
+                                           ziprand_error_t* error);
+
+/**
+ * Open a ZIP archive from a saved index held in memory (e.g. a memory-mapped file)
+ * Entry names are used in place rather than copied, so the data must stay valid and
+ * unchanged until the archive is closed. Stale indexes are rejected as in
+ * ziprand_open_with_index().
+ * @param io I/O interface for the archive (copied internally)
+ * @param index_data Saved index bytes as written by ziprand_index_save
+ * @param index_size Size of index_data in bytes
+ * @param options Open options, or NULL for defaults
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Archive handle or NULL on error
+ */
+ziprand_archive_t* ziprand_open_with_index_memory(const ziprand_io_t* io,
+                                                  const void* index_data,
+                                                  size_t index_size,
+                                                  const ziprand_options_t* options,
+                                                  ziprand_error_t* error);
+
+/**
+ * Open a stored entry that is itself a ZIP archive (an APK inside an OTA, zips of zips)
+ * Reads go straight to the entry's bytes in the parent, nothing is extracted. The parent
+ * archive must stay open until the nested archive is closed.
+ * @param parent Archive containing the entry
+ * @param entry Stored entry holding the nested archive
+ * @param options Open options, or NULL for defaults
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Archive handle or NULL on error
+ */
+ziprand_archive_t* ziprand_open_nested(ziprand_archive_t* parent,
+                                       const ziprand_entry_t* entry,
+                                       const ziprand_options_t* options,
+                                       ziprand_error_t* error);
+
+/**
+ * Re-read the central directory, e.g. after the underlying source changed
+ * The directory is parsed once at open and cached; this discards that cache. All entry
+ * pointers and file handles previously obtained from the archive become invalid on success.
+ * On failure the archive keeps its previous state.
+ * @param archive Archive handle
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_refresh(ziprand_archive_t* archive);
+
 /**
  * Close the archive and free all resources
  * @param archive Archive handle
  */
 void ziprand_close(ziprand_archive_t* archive);
 
+/**
+ * Get archive-level metadata (EOCD / ZIP64 EOCD fields)
+ * @param archive Archive handle
+ * @param info Receives the archive information
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_get_archive_info(ziprand_archive_t* archive, ziprand_archive_info_t* info);
+
+/**
+ * Read archive-level metadata without opening the archive
+ * Only the EOCD record (and the ZIP64 EOCD record when needed) is read; the central
+ * directory is not parsed.
+ * @param io I/O interface for the archive
+ * @param info Receives the archive information
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_read_archive_info(const ziprand_io_t* io, ziprand_archive_info_t* info);
+
+/**
+ * Get the number of entries without opening the archive (see ziprand_read_archive_info)
+ * @param io I/O interface for the archive
+ * @return Number of entries, or a negative error code
+ */
+int64_t ziprand_peek_entry_count(const ziprand_io_t* io);
+
+/**
+ * Start iterating over the central directory without loading it into memory
+ * Only the EOCD is read up front; each call to ziprand_iter_next() parses one record.
+ * @param io I/O interface (copied internally, not closed by the iterator)
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Iterator handle or NULL on error
+ */
+ziprand_iter_t* ziprand_iter_open(const ziprand_io_t* io, ziprand_error_t* error);
+
+/**
+ * Parse the next central directory entry
+ * @param iter Iterator handle
+ * @param entry Receives the entry (valid until the next call), or NULL after the last entry
+ * @return ZIPRAND_OK or error code (iteration stops after an error)
+ */
+ziprand_error_t ziprand_iter_next(ziprand_iter_t* iter, const ziprand_entry_t** entry);
+
+/**
+ * Free an iterator
+ * @param iter Iterator handle
+ */
+void ziprand_iter_close(ziprand_iter_t* iter);
+
 /**
  * Get number of entries in the archive
  * @param archive Archive handle
@@ -107,7 +662,185 @@ const ziprand_entry_t* ziprand_get_entry_by_index(ziprand_archive_t* archive, si
 const ziprand_entry_t* ziprand_find_entry(ziprand_archive_t* archive, const char* name);
 
 /**
- * Open a file within the archive for reading (only uncompressed files supported)
+ * Find entry by raw name bytes (names may contain any bytes, including NUL)
+ * @param archive Archive handle
+ * @param name Entry name bytes to find
+ * @param name_len Length of name in bytes
+ * @return Entry information or NULL if not found (do not free, owned by archive)
+ */
+const ziprand_entry_t*
+ziprand_find_entry_raw(ziprand_archive_t* archive, const void* name, size_t name_len);
+
+/**
+ * Check whether an entry exists, using the same lookup index as ziprand_find_entry()
+ * @param archive Archive handle
+ * @param name Entry name to check
+ * @return 1 if the entry exists, 0 if not, or error code
+ */
+int ziprand_contains(ziprand_archive_t* archive, const char* name);
+
+/**
+ * Find all entries with the given name, in central directory order
+ * @param archive Archive handle
+ * @param name Entry name to find
+ * @param entries Array receiving up to max_entries matches (can be NULL)
+ * @param max_entries Capacity of entries
+ * @return Total number of matching entries (may exceed max_entries)
+ */
+size_t ziprand_find_all_by_name(ziprand_archive_t* archive,
+                                const char* name,
+                                const ziprand_entry_t** entries,
+                                size_t max_entries);
+
+/**
+ * Check whether an entry passes a filter
+ * @param entry Entry to check
+ * @param filter Filter to apply
+ * @return 1 if the entry is kept, 0 if it is filtered out
+ */
+int ziprand_entry_matches_filter(const ziprand_entry_t* entry,
+                                 const ziprand_entry_filter_t* filter);
+
+/**
+ * List entries that pass a filter, in central directory order
+ * @param archive Archive handle
+ * @param filter Filter to apply (NULL keeps every entry)
+ * @param entries Array receiving up to max_entries entries (can be NULL)
+ * @param max_entries Capacity of entries
+ * @return Total number of entries that pass the filter (may exceed max_entries)
+ */
+size_t ziprand_list_entries_filtered(ziprand_archive_t* archive,
+                                     const ziprand_entry_filter_t* filter,
+                                     const ziprand_entry_t** entries,
+                                     size_t max_entries);
+
+/**
+ * Compute archive statistics, e.g. to decide whether an archive suits random access
+ * Reads each local header that has not been read yet to measure gaps between entries; data
+ * descriptors and padding both count as gap bytes.
+ * @param archive Archive handle
+ * @param stats Receives the statistics
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_get_stats(ziprand_archive_t* archive, ziprand_stats_t* stats);
+
+/**
+ * Detect entries whose local header and data overlap another entry or the ZIP structures
+ * Overlapping data is a common trick in malicious archives (e.g. zip bombs). Every entry
+ * involved in an overlap appears in at least one report, as entry or other. Reads each local
+ * header that has not been read yet.
+ * @param archive Archive handle
+ * @param overlaps Array receiving up to max_overlaps overlaps (can be NULL)
+ * @param max_overlaps Capacity of overlaps
+ * @return Total number of overlaps found (may exceed max_overlaps), or a negative error code
+ */
+int64_t ziprand_detect_overlaps(ziprand_archive_t* archive,
+                                ziprand_overlap_t* overlaps,
+                                size_t max_overlaps);
+
+/**
+ * Check whether an entry name matches a glob pattern
+ * '*' and '?' match within one path component and '**' matches across components; '**'
+ * followed by '/' also matches zero directories. "[...]" matches a character class ("[!...]"
+ * negates) and a backslash escapes the next character.
+ * @param entry Entry to check
+ * @param pattern Glob pattern
+ * @return 1 if the name matches, 0 otherwise
+ */
+int ziprand_entry_matches_glob(const ziprand_entry_t* entry, const char* pattern);
+
+/**
+ * Find entries whose names match a glob pattern, in central directory order
+ * @param archive Archive handle
+ * @param pattern Glob pattern (see ziprand_entry_matches_glob)
+ * @param entries Array receiving up to max_entries matches (can be NULL)
+ * @param max_entries Capacity of entries
+ * @return Total number of matching entries (may exceed max_entries)
+ */
+size_t ziprand_find_glob(ziprand_archive_t* archive,
+                         const char* pattern,
+                         const ziprand_entry_t** entries,
+                         size_t max_entries);
+
+/**
+ * Find a node in the directory tree of the archive
+ * The tree is built from entry names on first use; directories that only appear as path
+ * prefixes get nodes without an entry. Nodes stay valid until ziprand_close() or
+ * ziprand_refresh().
+ * @param archive Archive handle
+ * @param path Path with '/' separators; a trailing '/' is ignored and "" is the root
+ * @return Node or NULL if not found
+ */
+const ziprand_tree_node_t* ziprand_tree_find(ziprand_archive_t* archive, const char* path);
+
+/**
+ * List the immediate children of a directory, in name order
+ * @param archive Archive handle
+ * @param path Directory path, e.g. "assets/textures/" ("" for the root)
+ * @param children Array receiving up to max_children nodes (can be NULL)
+ * @param max_children Capacity of children
+ * @return Total number of children (may exceed max_children), or -1 if path is not a directory
+ */
+int64_t ziprand_read_dir(ziprand_archive_t* archive,
+                         const char* path,
+                         const ziprand_tree_node_t** children,
+                         size_t max_children);
+
+/**
+ * Walk all nodes below a tree node depth-first, parents before their children
+ * @param node Starting node (not visited itself)
+ * @param visit Callback invoked for each node
+ * @param ctx Context passed to visit
+ * @return 0 if the walk completed, otherwise the nonzero value returned by visit
+ */
+int ziprand_tree_walk(const ziprand_tree_node_t* node, ziprand_tree_visit_fn visit, void* ctx);
+
+/**
+ * Check whether an entry is a symbolic link (Unix mode bits in external attributes)
+ * @param entry Entry to check
+ * @return 1 if the entry is a symlink, 0 otherwise
+ */
+int ziprand_entry_is_symlink(const ziprand_entry_t* entry);
+
+/**
+ * Tell how an entry is encrypted, from the central directory alone
+ * Encrypted entries are listed like any other; opening one needs ziprand_fopen_with_password()
+ * or a password provider in the open options.
+ * @param entry Entry to check
+ * @return Encryption scheme, ZIPRAND_ENCRYPTION_NONE for plain entries
+ */
+ziprand_encryption_t ziprand_entry_encryption(const ziprand_entry_t* entry);
+
+/**
+ * Read the target of a symlink entry (stored as the entry's data)
+ * @param archive Archive handle
+ * @param entry Symlink entry
+ * @param buffer Buffer to receive the null-terminated target
+ * @param size Buffer size in bytes (must leave room for the terminator)
+ * @return Length of the target, or -1 on error
+ */
+int64_t ziprand_read_symlink_target(ziprand_archive_t* archive,
+                                    const ziprand_entry_t* entry,
+                                    char* buffer,
+                                    size_t size);
+
+/**
+ * Get a relative path for the entry that is safe to join onto an extraction directory
+ * Rejects absolute paths, ".." components, drive letter prefixes and embedded NUL bytes,
+ * and on Windows any ':' (NTFS alternate data streams); backslashes are treated as
+ * separators and empty or "." components are dropped.
+ * @param entry Entry to sanitize
+ * @return Allocated "/"-separated path (free with free()), or NULL if the name is unsafe
+ */
+char* ziprand_entry_sanitized_path(const ziprand_entry_t* entry);
+
+/**
+ * Open a file within the archive for reading
+ * Stored entries are read straight from the archive. Compressed entries are decompressed
+ * as they are read when libziprand is built with their codec: DEFLATE (method 8, deflate
+ * option), bzip2 (method 12, bzip2 option), LZMA (method 14) and XZ (method 95, lzma
+ * option) or Zstandard (method 93, zstd option); other methods fail with
+ * ZIPRAND_ERR_COMPRESSED.
  * @param archive Archive handle
  * @param entry Entry to open
  * @return File handle or NULL on error
@@ -122,25 +855,325 @@ ziprand_file_t* ziprand_fopen(ziprand_archive_t* archive, const ziprand_entry_t*
  */
 ziprand_file_t* ziprand_fopen_by_name(ziprand_archive_t* archive, const char* name);
 
+/**
+ * Open a file by name, reporting why it could not be opened
+ * @param archive Archive handle
+ * @param name Entry name
+ * @param error Receives the error code on failure (can be NULL), e.g. ZIPRAND_ERR_NOT_FOUND
+ *              or ZIPRAND_ERR_COMPRESSED
+ * @return File handle or NULL on error
+ */
+ziprand_file_t*
+ziprand_fopen_by_name_ex(ziprand_archive_t* archive, const char* name, ziprand_error_t* error);
+
+/**
+ * Open an encrypted file, checking the password first
+ * Entries with traditional PKWARE encryption (ZipCrypto) are decrypted as they are read,
+ * front to back like compressed entries, and WinZip AES entries from any position. Needs the
+ * crypto build option for ZipCrypto and the aes option (OpenSSL) for AES. A read that takes
+ * AES data through to its end checks the authentication code, and fails with
+ * ZIPRAND_ERR_AUTH_FAILED if it doesn't match.
+ * The other open functions ask the archive's password provider, if its options set one,
+ * when they reach an encrypted entry.
+ * @param archive Archive handle
+ * @param entry Entry to open
+ * @param password Password, NUL-terminated
+ * @param error Receives the error code on failure (can be NULL), e.g.
+ *              ZIPRAND_ERR_WRONG_PASSWORD, or ZIPRAND_ERR_ENCRYPTED without crypto support
+ * @return File handle or NULL on error
+ */
+ziprand_file_t* ziprand_fopen_with_password(ziprand_archive_t* archive,
+                                            const ziprand_entry_t* entry,
+                                            const char* password,
+                                            ziprand_error_t* error);
+
+/**
+ * Open the compressed bytes of an entry as they are stored, whatever its compression method
+ * The handle reads compressed_size bytes and supports the whole file API, including
+ * ziprand_fdata() and ziprand_fsource_range(); the entry gives the method, CRC-32 and
+ * uncompressed size needed to decode them elsewhere.
+ * @param archive Archive handle
+ * @param entry Entry to open
+ * @param error Receives the error code on failure (can be NULL)
+ * @return File handle or NULL on error
+ */
+ziprand_file_t*
+ziprand_fopen_raw(ziprand_archive_t* archive, const ziprand_entry_t* entry, ziprand_error_t* error);
+
+/**
+ * Open an entry for an HTTP response, passing its stored bytes through when the client can
+ * take them: stored entries as they are, and DEFLATE entries with Content-Encoding: deflate
+ * when accept_deflate is set. Other entries are decoded like ziprand_fopen() does, and
+ * encrypted ones are always decrypted (with the archive's password provider).
+ * @param archive Archive handle
+ * @param entry Entry to open
+ * @param accept_deflate Nonzero if the client accepts Content-Encoding: deflate
+ * @param info Receives the passthrough mode, sizes and ETag
+ * @param error Receives the error code on failure (can be NULL)
+ * @return File handle or NULL on error
+ */
+ziprand_file_t* ziprand_fopen_http(ziprand_archive_t* archive,
+                                   const ziprand_entry_t* entry,
+                                   int accept_deflate,
+                                   ziprand_http_entry_t* info,
+                                   ziprand_error_t* error);
+
+/**
+ * Open many files at once, reading their local headers in as few I/O calls as possible
+ * Headers are read in offset order and nearby ones are fetched together, so opening
+ * thousands of small files from remote storage doesn't cost one round trip each.
+ * @param archive Archive handle
+ * @param entries Entries to open
+ * @param count Number of entries
+ * @param files Array of count handles receiving the opened files, in the order of entries
+ * @return ZIPRAND_OK or error code (on failure no handles are left open)
+ */
+ziprand_error_t ziprand_fopen_many(ziprand_archive_t* archive,
+                                   const ziprand_entry_t** entries,
+                                   size_t count,
+                                   ziprand_file_t** files);
+
+/**
+ * Open a file that verifies its CRC-32 while it is read
+ * Sequential ziprand_fread() calls keep a running checksum; the read that reaches the end
+ * of the entry returns -1 if it does not match the central directory, and ziprand_ferror()
+ * reports ZIPRAND_ERR_CRC_MISMATCH. Seeking forward past unread data ends verification.
+ * WinZip AES entries also have their authentication code checked, even after seeks, since
+ * the skipped data is read for it; AE-2 entries, which have no CRC-32, rely on it alone.
+ * A mismatch is reported as ZIPRAND_ERR_AUTH_FAILED.
+ * @param archive Archive handle
+ * @param entry Entry to open
+ * @return File handle or NULL on error
+ */
+ziprand_file_t* ziprand_fopen_verified(ziprand_archive_t* archive, const ziprand_entry_t* entry);
+
+/**
+ * Duplicate a file handle
+ * The copy reads the same entry with its own position (starting at the original's position)
+ * and does not re-read the local header. Reads on open handles of one archive, copies
+ * included, may run on several threads at once if the I/O callbacks allow it; finding and
+ * opening entries update the archive and must not run concurrently on it.
+ * Close it with ziprand_fclose().
+ * @param file File handle to duplicate
+ * @return New file handle or NULL on error
+ */
+ziprand_file_t* ziprand_fdup(const ziprand_file_t* file);
+
+/**
+ * Give a file a readahead buffer for small sequential reads
+ * ziprand_fread() then fetches buffer_size bytes at a time from the I/O backend and serves
+ * later reads from memory, so parsers reading a few bytes at a time don't turn into
+ * thousands of tiny backend reads. Reads at least as large as the buffer bypass it.
+ * @param file File handle
+ * @param buffer_size Readahead size in bytes (0 to remove the buffer)
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_fsetbuf(ziprand_file_t* file, size_t buffer_size);
+
+/**
+ * Build a seek index for a DEFLATE entry
+ * Decompresses the entry once and records a checkpoint (stream position plus 32 KiB of
+ * history) at a block boundary about every span uncompressed bytes. Handles using the index
+ * (ziprand_fset_index) then restart decompression from the nearest checkpoint instead of
+ * the start of the entry, so a random read costs at most about span bytes of decoding.
+ * Needs libziprand built with zlib. Does not change the file position.
+ * @param file Handle of a DEFLATE entry
+ * @param span Uncompressed bytes between checkpoints (0 for 1 MiB); each checkpoint takes
+ *        32 KiB of memory
+ * @param error Receives the error code on failure (can be NULL), e.g. ZIPRAND_ERR_DECOMPRESS
+ * @return Index (free with ziprand_deflate_index_free()), or NULL on error
+ */
+ziprand_deflate_index_t*
+ziprand_deflate_index_build(ziprand_file_t* file, uint64_t span, ziprand_error_t* error);
+
+/**
+ * Get the number of checkpoints in a seek index
+ * @param index Index
+ * @return Number of checkpoints (entries smaller than the span have none)
+ */
+size_t ziprand_deflate_index_count(const ziprand_deflate_index_t* index);
+
+/**
+ * Free a seek index
+ * @param index Index to free (must no longer be set on any file handle)
+ */
+void ziprand_deflate_index_free(ziprand_deflate_index_t* index);
+
+/**
+ * Make a file handle restart decompression from an index's checkpoints
+ * The index is only read, so one index can serve many handles of the entry, including
+ * ziprand_fdup() copies on other threads, and handles of the same archive opened again.
+ * @param file Handle of the entry the index was built for
+ * @param index Index, or NULL to stop using one; must outlive its use by the handle
+ * @return ZIPRAND_OK, or ZIPRAND_ERR_INVALID_PARAM if the index is for another entry
+ */
+ziprand_error_t ziprand_fset_index(ziprand_file_t* file, const ziprand_deflate_index_t* index);
+
 /**
  * Read from current position in file
  * @param file File handle
  * @param buffer Buffer to read into
  * @param size Number of bytes to read
- * @return Number of bytes read, or -1 on error
+ * @return Number of bytes read, or -1 on error (or a negative ziprand_error_t for a specific
+ *         error, e.g. ZIPRAND_ERR_SOURCE_CHANGED, ZIPRAND_ERR_TIMEOUT, ZIPRAND_ERR_DECOMPRESS
+ *         or ZIPRAND_ERR_AUTH_FAILED)
  */
 int64_t ziprand_fread(ziprand_file_t* file, void* buffer, size_t size);
 
 /**
  * Read from specific offset (random access)
+ * Compressed entries decode forward from the last read, so reading before it decompresses
+ * the entry again from the start (or from the nearest frame of seekable Zstandard data, or
+ * checkpoint of a ziprand_fset_index() index), and reads share the handle's decoder: use
+ * ziprand_fdup() for concurrent readers.
  * @param file File handle
  * @param offset Offset within the file
  * @param buffer Buffer to read into
  * @param size Number of bytes to read
- * @return Number of bytes read, or -1 on error
+ * @return Number of bytes read, or -1 on error (or a negative ziprand_error_t for a specific
+ *         error, e.g. ZIPRAND_ERR_SOURCE_CHANGED, ZIPRAND_ERR_TIMEOUT, ZIPRAND_ERR_DECOMPRESS
+ *         or ZIPRAND_ERR_AUTH_FAILED)
  */
 int64_t ziprand_fread_at(ziprand_file_t* file, uint64_t offset, void* buffer, size_t size);
 
+/**
+ * Read from specific offset into several buffers (scatter read)
+ * Uses the I/O vectored read callback when available, otherwise one read per buffer.
+ * Does not change the file position.
+ * @param file File handle
+ * @param offset Offset within the file
+ * @param iov Buffers to fill, in order
+ * @param iovcnt Number of buffers
+ * @return Total number of bytes read, or -1 on error (or a negative ziprand_error_t for a
+ *         specific error, as for ziprand_fread_at())
+ */
+int64_t ziprand_fread_at_vectored(ziprand_file_t* file,
+                                  uint64_t offset,
+                                  const ziprand_iovec_t* iov,
+                                  size_t iovcnt);
+
+/**
+ * Read from specific offset into a buffer, without copying when the backend allows it
+ * Uses the I/O buffer read callback when available (e.g. a pointer into mapped memory),
+ * otherwise allocates a buffer and reads into it. Does not change the file position.
+ * @param file File handle
+ * @param offset Offset within the file
+ * @param size Number of bytes to read (clamped to the end of the file)
+ * @param buffer Receives the data; release it with ziprand_buffer_release()
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t
+ziprand_fread_buffer(ziprand_file_t* file, uint64_t offset, size_t size, ziprand_buffer_t* buffer);
+
+/**
+ * Get a file's bytes in place when the whole source is in memory (io->mapped)
+ * Lets large entries be parsed directly from a mapping without any read calls or copies.
+ * @param file File handle
+ * @return Pointer to ziprand_fsize() bytes, valid as long as the source memory, or NULL if
+ *         the source is not memory-backed
+ */
+const void* ziprand_fdata(const ziprand_file_t* file);
+
+/**
+ * Get where a file's data lives in the archive source
+ * For handing the range to other I/O paths, e.g. sendfile() from an HTTP server.
+ * @param file File handle
+ * @param io Receives the archive's I/O interface (can be NULL)
+ * @param offset Receives the absolute source offset of the file's first byte (can be NULL)
+ * @param size Receives the file size (can be NULL)
+ * @return ZIPRAND_OK, or ZIPRAND_ERR_COMPRESSED for a compressed entry, whose bytes in the
+ *         archive are not the file's
+ */
+ziprand_error_t ziprand_fsource_range(const ziprand_file_t* file,
+                                      const ziprand_io_t** io,
+                                      uint64_t* offset,
+                                      uint64_t* size);
+
+/**
+ * Release a buffer returned by ziprand_fread_buffer
+ * @param buffer Buffer to release (reset to empty)
+ */
+void ziprand_buffer_release(ziprand_buffer_t* buffer);
+
+/**
+ * Check that a whole file can safely be read into memory before allocating for it
+ * Guards against entry sizes that lie (e.g. forged ZIP64 fields).
+ * @param file File handle
+ * @param max_size Largest acceptable size in bytes (0 = only the archive's max_read_size limit)
+ * @return ZIPRAND_OK, ZIPRAND_ERR_ENTRY_TOO_LARGE, or ZIPRAND_ERR_INVALID_ZIP if the entry
+ *         extends past the end of the archive
+ */
+ziprand_error_t ziprand_fcheck_size(ziprand_file_t* file, uint64_t max_size);
+
+/**
+ * Read a whole file into a newly allocated buffer
+ * Does not change the file position.
+ * @param file File handle
+ * @param max_size Largest acceptable size in bytes (0 = only the archive's max_read_size limit)
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Buffer of ziprand_fsize() bytes (free with free()), or NULL on error
+ */
+void* ziprand_fread_all(ziprand_file_t* file, uint64_t max_size, ziprand_error_t* error);
+
+/**
+ * Read the byte range [start, end) of a file into a newly allocated buffer
+ * Does not change the file position.
+ * @param file File handle
+ * @param start First byte of the range
+ * @param end End of the range (exclusive, at most the file size)
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Buffer of end - start bytes (free with free()), or NULL on error (ranges larger than
+ *         the archive's max_read_size limit fail with ZIPRAND_ERR_ENTRY_TOO_LARGE)
+ */
+void* ziprand_fread_range(ziprand_file_t* file,
+                          uint64_t start,
+                          uint64_t end,
+                          ziprand_error_t* error);
+
+/**
+ * Stream the rest of a file, from the current position, to a write callback in chunks
+ * Advances the position by the number of bytes copied. The callback can also feed a hash
+ * (SHA-256 and the like) to digest an entry in the same pass; on a verified handle
+ * (ziprand_fopen_verified) a CRC mismatch fails the copy with ZIPRAND_ERR_CRC_MISMATCH.
+ * @param file File handle
+ * @param write Write callback receiving the data
+ * @param ctx Context passed to write
+ * @param chunk_size Bytes per read and write (0 for a 64 KiB default)
+ * @return Number of bytes copied, or a negative error code
+ */
+int64_t ziprand_fcopy(ziprand_file_t* file, ziprand_write_fn write, void* ctx, size_t chunk_size);
+
+/**
+ * Start reading a file as a sequence of chunks, e.g. to feed an HTTP body stream
+ * Chunks are read sequentially and independently of the file position; the file handle must
+ * stay open while the iterator is used.
+ * @param file File handle
+ * @param offset Offset within the file to start at
+ * @param chunk_size Maximum chunk size in bytes (0 for a 64 KiB default)
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Chunk iterator or NULL on error
+ */
+ziprand_chunk_iter_t* ziprand_chunk_iter_open(ziprand_file_t* file,
+                                              uint64_t offset,
+                                              size_t chunk_size,
+                                              ziprand_error_t* error);
+
+/**
+ * Read the next chunk
+ * @param iter Chunk iterator
+ * @param data Receives the chunk (valid until the next call), or NULL at the end of the file
+ * @param size Receives the chunk size in bytes (0 at the end of the file)
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t
+ziprand_chunk_iter_next(ziprand_chunk_iter_t* iter, const void** data, size_t* size);
+
+/**
+ * Free a chunk iterator
+ * @param iter Chunk iterator (can be NULL)
+ */
+void ziprand_chunk_iter_close(ziprand_chunk_iter_t* iter);
+
 /**
  * Seek to position in file
  * @param file File handle
@@ -170,12 +1203,171 @@ int64_t ziprand_fsize(ziprand_file_t* file);
  */
 void ziprand_fclose(ziprand_file_t* file);
 
+/**
+ * Get the error that stopped reads on a file handle
+ * @param file File handle
+ * @return ZIPRAND_OK, ZIPRAND_ERR_CRC_MISMATCH after a failed verification, or
+ *         ZIPRAND_ERR_SOURCE_CHANGED once the backend reported that the archive was replaced
+ */
+ziprand_error_t ziprand_ferror(const ziprand_file_t* file);
+
+/**
+ * Hint that entries will be read soon, passing their ranges to the I/O prefetch callback
+ * Does nothing if the I/O interface has no prefetch callback. Local headers are not read; when
+ * an entry's data offset is not known yet, its range is estimated generously.
+ * @param archive Archive handle
+ * @param entries Entries that will be read
+ * @param count Number of entries
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t
+ziprand_prefetch(ziprand_archive_t* archive, const ziprand_entry_t** entries, size_t count);
+
+/**
+ * Plan reads for a set of entries: order them by data offset and coalesce nearby ranges
+ * Reads each local header that has not been read yet (coalesced as in ziprand_fopen_many).
+ * @param archive Archive handle
+ * @param entries Entries to read
+ * @param count Number of entries
+ * @param max_gap Largest gap in bytes between two ranges that is read through to merge them
+ *        (ZIPRAND_GAP_AUTO to pick one from the backend's capability hints)
+ * @param max_read_size Largest merged read in bytes (0 = unlimited); larger entries get their own
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Read plan (free with ziprand_read_plan_free) or NULL on error
+ */
+ziprand_read_plan_t* ziprand_plan_reads(ziprand_archive_t* archive,
+                                        const ziprand_entry_t** entries,
+                                        size_t count,
+                                        uint64_t max_gap,
+                                        uint64_t max_read_size,
+                                        ziprand_error_t* error);
+
+/**
+ * Execute a read plan, calling back with the data of each entry in offset order
+ * @param archive Archive handle the plan was made for
+ * @param plan Read plan
+ * @param callback Callback receiving each entry's data
+ * @param ctx Context passed to callback
+ * @return ZIPRAND_OK, error code, or the nonzero value that stopped the callback
+ */
+int ziprand_read_plan_execute(ziprand_archive_t* archive,
+                              const ziprand_read_plan_t* plan,
+                              ziprand_plan_fn callback,
+                              void* ctx);
+
+/**
+ * Free a read plan
+ * @param plan Read plan (can be NULL)
+ */
+void ziprand_read_plan_free(ziprand_read_plan_t* plan);
+
+/**
+ * Open several stored entries as one contiguous file (e.g. data.bin.000, data.bin.001, ...)
+ * Offsets are translated across the pieces, in the order given.
+ * @param archive Archive handle
+ * @param entries Entries to concatenate
+ * @param count Number of entries
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Concatenated reader (close with ziprand_concat_close) or NULL on error
+ */
+ziprand_concat_t* ziprand_concat_open(ziprand_archive_t* archive,
+                                      const ziprand_entry_t** entries,
+                                      size_t count,
+                                      ziprand_error_t* error);
+
+/**
+ * Get the total size of a concatenated reader
+ * @param concat Concatenated reader
+ * @return Sum of the entry sizes
+ */
+uint64_t ziprand_concat_size(const ziprand_concat_t* concat);
+
+/**
+ * Read from a concatenated reader at an offset, crossing entry boundaries as needed
+ * @param concat Concatenated reader
+ * @param offset Offset within the concatenation
+ * @param buffer Buffer to fill
+ * @param size Number of bytes to read
+ * @return Bytes read (0 at the end), or -1 on error (or a negative ziprand_error_t for a
+ *         specific error, as for ziprand_fread_at())
+ */
+int64_t
+ziprand_concat_read_at(ziprand_concat_t* concat, uint64_t offset, void* buffer, size_t size);
+
+/**
+ * Close a concatenated reader
+ * @param concat Concatenated reader (can be NULL)
+ */
+void ziprand_concat_close(ziprand_concat_t* concat);
+
 /**
  * Get last error message
  * @return Error message string (do not free)
  */
 const char* ziprand_strerror(ziprand_error_t error);
 
+/* Operations reported to the trace callback */
+typedef enum {
+    ZIPRAND_TRACE_READ = 0,      /* Backend read (read, read_vectored or read_buffer) */
+    ZIPRAND_TRACE_GET_SIZE = 1,  /* Backend get_size */
+    ZIPRAND_TRACE_PREFETCH = 2,  /* Backend prefetch hint; size is the number of ranges */
+    ZIPRAND_TRACE_FIND_EOCD = 3, /* End of central directory search; size is the source size */
+    ZIPRAND_TRACE_PARSE_CD = 4,  /* Central directory parse */
+    ZIPRAND_TRACE_OPEN_ENTRY = 5 /* Entry open, including its local header read */
+} ziprand_trace_op_t;
+
+/* A completed operation */
+typedef struct {
+    ziprand_trace_op_t op;
+    const void* source;   /* I/O context (io->ctx) the operation ran against */
+    const char* name;     /* Entry name for ZIPRAND_TRACE_OPEN_ENTRY, otherwise NULL */
+    uint64_t offset;      /* Source offset */
+    uint64_t size;        /* Bytes requested or covered */
+    int64_t result;       /* Backend return value, or a ziprand_error_t for library operations */
+    uint64_t duration_us; /* Wall-clock duration in microseconds */
+} ziprand_trace_event_t;
+
+/**
+ * Trace callback - called after each traced operation completes
+ * @param ctx User-provided context
+ * @param event The operation (valid only during the call)
+ */
+typedef void (*ziprand_trace_fn)(void* ctx, const ziprand_trace_event_t* event);
+
+/**
+ * Install a process-wide trace callback (requires building with the trace option)
+ *
+ * The callback runs on the thread performing the operation, so it must be thread-safe if
+ * archives are used from several threads. Install it before opening archives.
+ * @param fn Callback, or NULL to stop tracing
+ * @param ctx Context passed to the callback
+ * @return ZIPRAND_OK, or ZIPRAND_ERR_INVALID_PARAM when the library was built without tracing
+ */
+ziprand_error_t ziprand_set_trace(ziprand_trace_fn fn, void* ctx);
+
+/**
+ * Get the capability hints of an I/O interface, with defaults for what it leaves open
+ * Block size defaults to 64 KiB (1 MiB for remote backends) and concurrency to 1 for memory,
+ * 4 for local and 8 for remote backends. The reader uses these for central directory reads,
+ * local header coalescing (ziprand_fopen_many, ziprand_plan_reads with ZIPRAND_GAP_AUTO)
+ * and ziprand_fread_all_parallel defaults.
+ * @param io I/O interface
+ * @param caps Receives the hints
+ * @return ZIPRAND_OK or error code
+ */
+ziprand_error_t ziprand_io_capabilities(const ziprand_io_t* io, ziprand_io_caps_t* caps);
+
+/**
+ * Read several discontiguous ranges from an I/O interface
+ * Uses io->read_ranges when the backend has one, otherwise reads the ranges one by one.
+ * @param io I/O interface
+ * @param ranges Ranges to read
+ * @param count Number of ranges
+ * @return ZIPRAND_OK once every buffer is filled completely, or error code
+ */
+ziprand_error_t
+ziprand_io_read_ranges(const ziprand_io_t* io, const ziprand_read_range_t* ranges, size_t count);
+
 /* Helper functions for common I/O sources */
 
 /**
@@ -193,12 +1385,319 @@ ziprand_io_t* ziprand_io_file(const char* path);
  */
 ziprand_io_t* ziprand_io_memory(const void* data, size_t size);
 
+/**
+ * Create I/O interface that memory-maps a file
+ * Reads are plain memory copies, and ziprand_fread_buffer and ziprand_fdata hand out pointers
+ * into the mapping, which makes this the fastest backend for random access to local archives.
+ * The file must not be truncated while it is mapped.
+ * @param path File path
+ * @return Allocated I/O interface (must be freed with ziprand_io_free), or NULL on error or if
+ *         the file is empty
+ */
+ziprand_io_t* ziprand_io_mmap(const char* path);
+
+/**
+ * Create I/O interface from a seekable stdio stream
+ * For sources that can only seek and read (decrypting streams from fopencookie/funopen, custom
+ * containers). Every read seeks and reads under a lock, so the interface is thread-safe but
+ * reads are serialized. The stream is not closed by ziprand_io_free and must outlive the
+ * interface; don't use it directly in the meantime.
+ * @param stream Stream opened for binary reading
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_stream(FILE* stream);
+
+/**
+ * Create I/O interface for a remote file over HTTP(S) range requests
+ * Needs libziprand built with libcurl (meson option http). The server is probed once on
+ * creation: it must answer a range request with 206 Partial Content, and the archive size is
+ * taken from its Content-Range header. Reads are thread-safe and reuse a small pool of
+ * connections, so ziprand_fread_all_parallel gets several requests in flight. Reads are made
+ * conditional on the ETag (If-Match) or Last-Modified (If-Unmodified-Since) of the probe, and
+ * fail with ZIPRAND_ERR_SOURCE_CHANGED if the remote archive was replaced in the meantime.
+ * @param url URL of the archive
+ * @return Allocated I/O interface (must be freed with ziprand_io_free), or NULL on error, if
+ *         the server doesn't support range requests or if built without HTTP support
+ */
+ziprand_io_t* ziprand_io_http(const char* url);
+
+/* Timeouts of the HTTP and S3 backends (zero fields take the defaults) */
+typedef struct {
+    uint32_t timeout_ms;         /* Deadline of each request (0 = none) */
+    uint32_t connect_timeout_ms; /* Deadline for connecting (0 = 10 s) */
+    uint32_t stall_timeout_ms;   /* Give up transfers idle this long (0 = 30 s) */
+} ziprand_http_options_t;
+
+/**
+ * Create I/O interface for a remote file over HTTP(S) range requests, with timeouts
+ * Like ziprand_io_http. Requests that run into a timeout are retried like dropped
+ * connections, and reads fail with ZIPRAND_ERR_TIMEOUT once the retries are used up, so a
+ * hung connection can't stall a reader forever.
+ * @param url URL of the archive
+ * @param options Timeouts (NULL = defaults)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free), or NULL on error, if
+ *         the server doesn't support range requests or if built without HTTP support
+ */
+ziprand_io_t* ziprand_io_http_ex(const char* url, const ziprand_http_options_t* options);
+
+/* Location and credentials of an S3 object */
+typedef struct {
+    const char* bucket;            /* Bucket name */
+    const char* key;               /* Object key */
+    const char* region;            /* Region, e.g. "us-east-1" */
+    const char* endpoint;          /* S3-compatible service URL (path-style), NULL for AWS */
+    const char* access_key_id;     /* NULL for anonymous access to public buckets */
+    const char* secret_access_key; /* Required with access_key_id */
+    const char* session_token;     /* Temporary credentials token (can be NULL) */
+    ziprand_http_options_t http;   /* Timeouts */
+} ziprand_s3_options_t;
+
+/**
+ * Create I/O interface for an S3 object
+ * Works like ziprand_io_http, with every range request signed with AWS Signature Version 4
+ * (needs libcurl 7.75 or later). Failed requests are retried with backoff on throttling
+ * (429), server errors (5xx) and dropped connections. The strings are copied.
+ * @param options Object location and credentials
+ * @return Allocated I/O interface (must be freed with ziprand_io_free), or NULL on error or if
+ *         built without HTTP support
+ */
+ziprand_io_t* ziprand_io_s3(const ziprand_s3_options_t* options);
+
+/* Location and credentials of an Azure blob */
+typedef struct {
+    const char* account;         /* Storage account name */
+    const char* container;       /* Container name */
+    const char* blob;            /* Blob name */
+    const char* endpoint;        /* Service URL (path-style, e.g. Azurite), NULL for Azure */
+    const char* account_key;     /* Base64 account key for Shared Key, NULL otherwise */
+    const char* sas_token;       /* Shared access signature query string, NULL otherwise */
+    ziprand_http_options_t http; /* Timeouts */
+} ziprand_azure_options_t;
+
+/**
+ * Create I/O interface for an Azure blob
+ * Works like ziprand_io_http, authorized with a SAS token, or with every range request
+ * signed with the account key (Shared Key, needs libcrypto from the aes option), or
+ * anonymously for public containers. The strings are copied.
+ * @param options Blob location and credentials (account_key and sas_token are exclusive)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free), or NULL on error or if
+ *         built without HTTP support
+ */
+ziprand_io_t* ziprand_io_azure(const ziprand_azure_options_t* options);
+
+/**
+ * Wrap an I/O interface so that it only sees aligned reads
+ * Every read is rounded out to whole blocks of alignment bytes and read into an aligned
+ * buffer, and the requested bytes are copied out of it, as O_DIRECT descriptors and raw block
+ * devices require. Reads that are already aligned (offset, size and buffer) pass straight
+ * through.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @param alignment Block size, a power of two (0 = 4096)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_aligned(ziprand_io_t* inner, size_t alignment);
+
+/**
+ * Join several I/O interfaces into one logical source
+ * The parts are concatenated in order and reads are translated to the right part, spanning
+ * part boundaries as needed, for archives stored as separate pieces (e.g. fixed-size blobs in
+ * object storage). Part sizes are queried once, here.
+ * @param parts Array of count I/O interfaces, freed with the returned interface (not on
+ *              failure); the array itself is copied
+ * @param count Number of parts
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_chain(ziprand_io_t* const* parts, size_t count);
+
+/**
+ * Expose a byte window of another I/O interface as a source of its own
+ * For zips that don't start at byte 0 of their container: self-extractor stubs, installers,
+ * firmware images. Offset 0 of the window is offset bytes into inner.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @param offset Start of the window in inner
+ * @param size Window size (0 = up to the end of inner)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free), or NULL on error or if
+ *         the window doesn't fit in inner
+ */
+ziprand_io_t* ziprand_io_window(ziprand_io_t* inner, uint64_t offset, uint64_t size);
+
+/**
+ * Wrap an I/O interface with an LRU cache of fixed-size blocks
+ * Reads are served from cached blocks where possible; missing blocks are fetched whole, with
+ * one read per run of consecutive missing blocks. On HTTP and S3 backends this turns the many
+ * small header and directory reads into a handful of range requests. Reads at least as large
+ * as the cache bypass it. Thread-safe.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @param block_size Block size in bytes (0 = 64 KiB)
+ * @param capacity Maximum cached bytes, rounded down to whole blocks (0 = 16 MiB)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_cached(ziprand_io_t* inner, size_t block_size, size_t capacity);
+
+/* Block cache that can be shared by several I/O interfaces */
+typedef struct ziprand_cache ziprand_cache_t;
+
+/**
+ * Create a block cache to share between cached I/O interfaces
+ * One bounded memory pool for many archives: blocks are keyed by source and block index, and
+ * evicted least recently used across all sources.
+ * @param block_size Block size in bytes (0 = 64 KiB)
+ * @param capacity Maximum cached bytes across all sources (0 = 16 MiB)
+ * @return Cache (free with ziprand_cache_free), or NULL on allocation failure
+ */
+ziprand_cache_t* ziprand_cache_new(size_t block_size, size_t capacity);
+
+/**
+ * Wrap an I/O interface with a shared block cache
+ * Like ziprand_io_cached, but the blocks live in cache, next to those of other interfaces.
+ * Each interface is a separate source, and its blocks are dropped when it is freed.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @param cache Shared cache, kept alive by the returned interface
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_cached_shared(ziprand_io_t* inner, ziprand_cache_t* cache);
+
+/**
+ * Release a block cache
+ * The memory is freed once the interfaces using the cache are freed too.
+ * @param cache Cache (can be NULL)
+ */
+void ziprand_cache_free(ziprand_cache_t* cache);
+
+/**
+ * Retry classification callback
+ * @param ctx User context from the policy
+ * @param result Negative result of the failed call (-1 or a ziprand_error_t)
+ * @param attempt Number of the failed attempt, starting at 1
+ * @return Nonzero to retry, 0 to give up and return result
+ */
+typedef int (*ziprand_retry_fn)(void* ctx, int64_t result, unsigned attempt);
+
+/* Retry policy of ziprand_io_retry (zero fields take the defaults) */
+typedef struct {
+    unsigned max_attempts;         /* Attempts per call including the first (0 = 4) */
+    uint32_t initial_delay_ms;     /* Delay before the first retry (0 = 100) */
+    uint32_t max_delay_ms;         /* Cap of the doubling delay (0 = 5000) */
+    ziprand_retry_fn should_retry; /* NULL = everything but ZIPRAND_ERR_SOURCE_CHANGED */
+    void* ctx;                     /* Passed to should_retry */
+} ziprand_retry_policy_t;
+
+/**
+ * Wrap an I/O interface so that failed reads are retried
+ * Failed read and size calls are retried with exponential backoff and jitter, so a single
+ * failed range request of a flaky network backend doesn't abort a large extraction.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @param policy Retry policy, copied (NULL = defaults)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_retry(ziprand_io_t* inner, const ziprand_retry_policy_t* policy);
+
+/**
+ * Wrap an I/O interface with rate limits
+ * Token buckets for bytes and read calls hold reads back (by sleeping in the calling thread)
+ * once a limit is exceeded, allowing bursts of up to one second's worth. Keeps background
+ * scans from saturating shared links or exceeding request quotas. Thread-safe; the limits
+ * apply to all threads together.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @param bytes_per_sec Byte rate limit (0 = unlimited)
+ * @param ops_per_sec Read call rate limit (0 = unlimited)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t*
+ziprand_io_throttled(ziprand_io_t* inner, uint64_t bytes_per_sec, uint32_t ops_per_sec);
+
+#define ZIPRAND_LATENCY_BUCKETS 32
+
+/* Counters of a metered I/O interface */
+typedef struct {
+    uint64_t reads;    /* Read calls */
+    uint64_t errors;   /* Read calls that failed */
+    uint64_t bytes;    /* Bytes returned by reads */
+    uint64_t total_us; /* Time spent in reads, in microseconds */
+    uint64_t max_us;   /* Slowest read */
+    /* Reads by latency: bucket i counts reads that took [2^i, 2^(i+1)) microseconds (bucket 0
+     * also takes reads under 1 us, the last one everything slower) */
+    uint64_t latency_histogram[ZIPRAND_LATENCY_BUCKETS];
+} ziprand_io_metrics_t;
+
+/**
+ * Wrap an I/O interface so that its reads are counted and timed
+ * Shows how many backend requests and bytes each high-level operation costs: take a snapshot
+ * with ziprand_io_metrics before and after it and compare. Thread-safe.
+ * @param inner I/O interface to wrap, freed with the returned interface (not on failure)
+ * @return Allocated I/O interface (must be freed with ziprand_io_free)
+ */
+ziprand_io_t* ziprand_io_metered(ziprand_io_t* inner);
+
+/**
+ * Get a snapshot of the counters of a metered I/O interface
+ * @param io Interface created by ziprand_io_metered
+ * @param metrics Receives the counters
+ * @return ZIPRAND_OK, or ZIPRAND_ERR_INVALID_PARAM if io is not metered
+ */
+ziprand_error_t ziprand_io_metrics(const ziprand_io_t* io, ziprand_io_metrics_t* metrics);
+
 /**
  * Free I/O interface created by helper functions
  * @param io I/O interface
  */
 void ziprand_io_free(ziprand_io_t* io);
 
+/**
+ * Wrap an open entry in a read-only, seekable stdio stream
+ * Lets entries be passed to code that reads from a FILE* without copying them first. The
+ * stream takes ownership of the file handle: fclose() the stream instead of calling
+ * ziprand_fclose(). On failure the caller still owns the handle. Available on Linux
+ * (fopencookie) and macOS (funopen).
+ * @param file File handle
+ * @return Stream, or NULL on error or if unsupported on this platform
+ */
+FILE* ziprand_fdopen(ziprand_file_t* file);
+
+/**
+ * Extract the rest of a file, from the current position, to a file descriptor
+ * When the archive was opened with ziprand_io_file, the bytes are moved inside the kernel
+ * (copy_file_range, then sendfile, on Linux) and never pass through user space; otherwise,
+ * or when the kernel can't do it or the entry is compressed, they are copied with
+ * ziprand_fcopy. Writes at the descriptor's current offset and advances the file position.
+ * Kernel-side copies are not checked by ziprand_fopen_verified.
+ * @param file File handle
+ * @param fd Destination file descriptor, open for writing
+ * @return Number of bytes copied, or a negative error code
+ */
+int64_t ziprand_fextract_fd(ziprand_file_t* file, int fd);
+
+/**
+ * Extract the rest of a file to a file descriptor, sharing storage with the archive if possible
+ * On filesystems with reflinks (Btrfs, XFS) the block-aligned part of the entry is cloned
+ * from the archive file (FICLONERANGE) instead of copied, which is nearly free for huge
+ * entries; the rest is extracted as in ziprand_fextract_fd. Cloning needs the archive opened
+ * with ziprand_io_file on Linux, both on the same filesystem, and the entry data (e.g.
+ * zipalign -p) and the descriptor's offset aligned to the filesystem block size.
+ * @param file File handle
+ * @param fd Destination file descriptor, open for writing
+ * @return Number of bytes extracted, or a negative error code
+ */
+int64_t ziprand_fclone_fd(ziprand_file_t* file, int fd);
+
+/**
+ * Read a whole file into a newly allocated buffer using several concurrent reads
+ * The file is split into chunks that worker threads read over disjoint ranges, which helps
+ * high-latency backends (object stores, HTTP) where one sequential stream is slow. The
+ * I/O read callback must be safe to call from several threads at once. Does not change
+ * the file position. Compressed entries are decompressed in one pass instead.
+ * @param file File handle
+ * @param concurrency Number of concurrent reads (0 for the backend's max_concurrency hint)
+ * @param chunk_size Bytes per read (0 for a default of 4 MiB, rounded up to the backend's
+ *        block size)
+ * @param error Receives the error code on failure (can be NULL)
+ * @return Buffer of ziprand_fsize() bytes (free with free()), or NULL on error
+ */
+void* ziprand_fread_all_parallel(ziprand_file_t* file,
+                                 size_t concurrency,
+                                 size_t chunk_size,
+                                 ziprand_error_t* error);
+
 #ifdef __cplusplus
 }
 #endif
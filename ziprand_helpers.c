@@ -3,19 +3,55 @@
 #define _POSIX_C_SOURCE 200809L
 #endif
 
+/* Enable fopencookie (Linux) and funopen (macOS) for ziprand_fdopen */
+#if defined(__linux__)
+#define _GNU_SOURCE
+#elif defined(__APPLE__)
+#define _DARWIN_C_SOURCE
+#endif
+
 #include "ziprand.h"
 
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <time.h>
 
 #ifdef _WIN32
 #include <windows.h>
+#include <io.h>
+#include <malloc.h>
 #else
+#include <errno.h>
 #include <fcntl.h>
 #include <unistd.h>
 #include <sys/types.h>
 #include <sys/stat.h>
+#include <sys/mman.h>
+#include <pthread.h>
+#endif
+
+#ifdef ZIPRAND_HAVE_CURL
+#include <curl/curl.h>
+#include <stdarg.h>
+#ifdef ZIPRAND_HAVE_OPENSSL
+#include <openssl/evp.h>
+#include <openssl/hmac.h>
+#endif
+#elif defined(__EMSCRIPTEN__)
+#include <emscripten/fetch.h>
+#endif
+
+#if defined(__linux__)
+#include <linux/fs.h>
+#include <sys/ioctl.h>
+#include <sys/sendfile.h>
+#include <sys/uio.h>
+#include <limits.h>
+#endif
+
+#if defined(__linux__) && !defined(IOV_MAX)
+#define IOV_MAX 1024
 #endif
 
 /* File I/O using native handles for thread-safe pread */
@@ -32,18 +68,50 @@ static int64_t file_read(void* ctx, uint64_t offset, void* buffer, size_t size)
     file_io_ctx_t* fctx = ctx;
 
 #ifdef _WIN32
-    OVERLAPPED overlapped = {0};
-    overlapped.Offset = (DWORD)offset;
-    overlapped.OffsetHigh = (DWORD)(offset >> 32);
-    
-    DWORD bytes_read;
-    if (!ReadFile(fctx->handle, buffer, (DWORD)size, &bytes_read, &overlapped)) {
-        return -1;
+    /* ReadFile takes a DWORD length and may return short, so read in chunks until the buffer
+     * is full; reading at or past the end fails with ERROR_HANDLE_EOF */
+    size_t total = 0;
+    while (total < size) {
+        uint64_t position = offset + total;
+        size_t remaining = size - total;
+        DWORD chunk = remaining > 0x40000000 ? 0x40000000 : (DWORD)remaining;
+
+        OVERLAPPED overlapped = {0};
+        overlapped.Offset = (DWORD)position;
+        overlapped.OffsetHigh = (DWORD)(position >> 32);
+
+        DWORD bytes_read;
+        if (!ReadFile(fctx->handle, (uint8_t*)buffer + total, chunk, &bytes_read, &overlapped)) {
+            if (GetLastError() == ERROR_HANDLE_EOF) {
+                break;
+            }
+            return -1;
+        }
+        if (bytes_read == 0) {
+            break;
+        }
+        total += bytes_read;
     }
-    return (int64_t)bytes_read;
+    return (int64_t)total;
 #else
-    ssize_t bytes_read = pread(fctx->fd, buffer, size, offset);
-    return bytes_read;
+    /* pread may return less than asked for (signals, network filesystems), so keep reading
+     * until the buffer is full or the end of the file */
+    size_t total = 0;
+    while (total < size) {
+        ssize_t bytes_read = pread(fctx->fd, (uint8_t*)buffer + total, size - total,
+                                   (off_t)(offset + total));
+        if (bytes_read < 0) {
+            if (errno == EINTR) {
+                continue;
+            }
+            return -1;
+        }
+        if (bytes_read == 0) {
+            break;
+        }
+        total += (size_t)bytes_read;
+    }
+    return (int64_t)total;
 #endif
 }
 
@@ -66,6 +134,89 @@ static int64_t file_size(void* ctx)
 #endif
 }
 
+#if defined(__linux__)
+static int64_t file_read_vectored(void* ctx,
+                                  uint64_t offset,
+                                  const ziprand_iovec_t* iov,
+                                  size_t iovcnt)
+{
+    file_io_ctx_t* fctx = ctx;
+
+    struct iovec local[16];
+    struct iovec* vec = local;
+    if (iovcnt > 16) {
+        vec = malloc(iovcnt * sizeof(struct iovec));
+        if (!vec) {
+            return -1;
+        }
+    }
+
+    for (size_t i = 0; i < iovcnt; i++) {
+        vec[i].iov_base = iov[i].base;
+        vec[i].iov_len = iov[i].len;
+    }
+
+    /* like pread, preadv may return short or be interrupted, and takes at most IOV_MAX
+     * buffers per call, so keep going until every buffer is full or the end of the file */
+    size_t first = 0;
+    int64_t total = 0;
+    while (first < iovcnt) {
+        if (vec[first].iov_len == 0) {
+            first++;
+            continue;
+        }
+
+        size_t count = iovcnt - first;
+        if (count > IOV_MAX) {
+            count = IOV_MAX;
+        }
+        ssize_t bytes_read = preadv(fctx->fd, vec + first, (int)count, (off_t)(offset + total));
+        if (bytes_read < 0) {
+            if (errno == EINTR) {
+                continue;
+            }
+            total = -1;
+            break;
+        }
+        if (bytes_read == 0) {
+            break;
+        }
+
+        total += bytes_read;
+        size_t left = (size_t)bytes_read;
+        while (first < iovcnt && left >= vec[first].iov_len) {
+            left -= vec[first].iov_len;
+            first++;
+        }
+        if (left > 0) {
+            vec[first].iov_base = (uint8_t*)vec[first].iov_base + left;
+            vec[first].iov_len -= left;
+        }
+    }
+
+    if (vec != local) {
+        free(vec);
+    }
+    return total;
+}
+#endif
+
+static void file_prefetch(void* ctx, const ziprand_range_t* ranges, size_t count)
+{
+    file_io_ctx_t* fctx = ctx;
+
+#ifdef POSIX_FADV_WILLNEED
+    for (size_t i = 0; i < count; i++) {
+        posix_fadvise(
+            fctx->fd, (off_t)ranges[i].offset, (off_t)ranges[i].size, POSIX_FADV_WILLNEED);
+    }
+#else
+    (void)fctx;
+    (void)ranges;
+    (void)count;
+#endif
+}
+
 static void file_close(void* ctx)
 {
     file_io_ctx_t* fctx = ctx;
@@ -127,6 +278,16 @@ ziprand_io_t* ziprand_io_file(const char* path)
     io->read = file_read;
     io->get_size = file_size;
     io->close = file_close;
+    io->prefetch = file_prefetch;
+#if defined(__linux__)
+    io->read_vectored = file_read_vectored;
+#else
+    io->read_vectored = NULL;
+#endif
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = NULL;
+    io->read_ranges = NULL;
 
     return io;
 }
@@ -151,6 +312,20 @@ static int64_t memory_read(void* ctx, uint64_t offset, void* buffer, size_t size
     return to_read;
 }
 
+static ziprand_error_t
+memory_read_buffer(void* ctx, uint64_t offset, size_t size, ziprand_buffer_t* buffer)
+{
+    memory_io_ctx_t* mctx = ctx;
+
+    /* hand out the memory directly, it stays valid as long as the I/O interface */
+    size_t available = offset < mctx->size ? mctx->size - offset : 0;
+    buffer->data = available ? mctx->data + offset : NULL;
+    buffer->size = size < available ? size : available;
+    buffer->release = NULL;
+    buffer->owner = NULL;
+    return ZIPRAND_OK;
+}
+
 static int64_t memory_size(void* ctx)
 {
     memory_io_ctx_t* mctx = ctx;
@@ -184,15 +359,2827 @@ ziprand_io_t* ziprand_io_memory(const void* data, size_t size)
     io->read = memory_read;
     io->get_size = memory_size;
     io->close = memory_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = memory_read_buffer;
+    io->mapped = data;
+    io->capabilities = NULL;
+    io->read_ranges = NULL;
 
     return io;
 }
 
-void ziprand_io_free(ziprand_io_t* io)
+/* memory-mapped file I/O, served by the memory callbacks */
+typedef struct {
+    memory_io_ctx_t memory; /* must be first */
+#ifdef _WIN32
+    void* view;
+#else
+    void* map;
+    size_t map_size;
+#endif
+} mmap_io_ctx_t;
+
+static void mmap_close(void* ctx)
 {
-    if (!io)
-        return;
-    if (io->close)
-        io->close(io->ctx);
-    free(io);
+    mmap_io_ctx_t* mctx = ctx;
+#ifdef _WIN32
+    UnmapViewOfFile(mctx->view);
+#else
+    munmap(mctx->map, mctx->map_size);
+#endif
+    free(mctx);
+}
+
+ziprand_io_t* ziprand_io_mmap(const char* path)
+{
+    if (!path)
+        return NULL;
+
+    mmap_io_ctx_t* mctx = malloc(sizeof(mmap_io_ctx_t));
+    if (!mctx)
+        return NULL;
+
+#ifdef _WIN32
+    HANDLE handle = CreateFileA(
+        path,
+        GENERIC_READ,
+        FILE_SHARE_READ,
+        NULL,
+        OPEN_EXISTING,
+        FILE_ATTRIBUTE_NORMAL,
+        NULL
+    );
+    if (handle == INVALID_HANDLE_VALUE) {
+        free(mctx);
+        return NULL;
+    }
+
+    LARGE_INTEGER file_size;
+    if (!GetFileSizeEx(handle, &file_size) || file_size.QuadPart <= 0 ||
+        (uint64_t)file_size.QuadPart > SIZE_MAX) {
+        CloseHandle(handle);
+        free(mctx);
+        return NULL;
+    }
+
+    /* the view keeps the mapping and the file open once both handles are closed */
+    HANDLE mapping = CreateFileMappingA(handle, NULL, PAGE_READONLY, 0, 0, NULL);
+    CloseHandle(handle);
+    if (!mapping) {
+        free(mctx);
+        return NULL;
+    }
+    mctx->view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+    CloseHandle(mapping);
+    if (!mctx->view) {
+        free(mctx);
+        return NULL;
+    }
+    mctx->memory.data = mctx->view;
+    mctx->memory.size = (size_t)file_size.QuadPart;
+#else
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        free(mctx);
+        return NULL;
+    }
+
+    struct stat st;
+    if (fstat(fd, &st) < 0 || st.st_size <= 0 || (uint64_t)st.st_size > SIZE_MAX) {
+        close(fd);
+        free(mctx);
+        return NULL;
+    }
+
+    /* the mapping stays valid after the descriptor is closed */
+    mctx->map_size = (size_t)st.st_size;
+    mctx->map = mmap(NULL, mctx->map_size, PROT_READ, MAP_PRIVATE, fd, 0);
+    close(fd);
+    if (mctx->map == MAP_FAILED) {
+        free(mctx);
+        return NULL;
+    }
+    mctx->memory.data = mctx->map;
+    mctx->memory.size = mctx->map_size;
+#endif
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        mmap_close(mctx);
+        return NULL;
+    }
+
+    io->ctx = mctx;
+    io->read = memory_read;
+    io->get_size = memory_size;
+    io->close = mmap_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = memory_read_buffer;
+    io->mapped = mctx->memory.data;
+    io->capabilities = NULL;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* mutex for backends with shared state */
+#ifdef _WIN32
+typedef CRITICAL_SECTION io_lock_t;
+#else
+typedef pthread_mutex_t io_lock_t;
+#endif
+
+static int io_lock_init(io_lock_t* lock)
+{
+#ifdef _WIN32
+    InitializeCriticalSection(lock);
+    return 0;
+#else
+    return pthread_mutex_init(lock, NULL);
+#endif
+}
+
+static void io_lock(io_lock_t* lock)
+{
+#ifdef _WIN32
+    EnterCriticalSection(lock);
+#else
+    pthread_mutex_lock(lock);
+#endif
+}
+
+static void io_unlock(io_lock_t* lock)
+{
+#ifdef _WIN32
+    LeaveCriticalSection(lock);
+#else
+    pthread_mutex_unlock(lock);
+#endif
+}
+
+static void io_lock_destroy(io_lock_t* lock)
+{
+#ifdef _WIN32
+    DeleteCriticalSection(lock);
+#else
+    pthread_mutex_destroy(lock);
+#endif
+}
+
+static void io_sleep_ms(unsigned long delay_ms)
+{
+#ifdef _WIN32
+    Sleep(delay_ms);
+#else
+    struct timespec delay = {(time_t)(delay_ms / 1000), (long)(delay_ms % 1000) * 1000000L};
+    nanosleep(&delay, NULL);
+#endif
+}
+
+/* monotonic clock in microseconds */
+static uint64_t io_now_us(void)
+{
+#ifdef _WIN32
+    LARGE_INTEGER frequency, counter;
+    QueryPerformanceFrequency(&frequency);
+    QueryPerformanceCounter(&counter);
+    return (uint64_t)(counter.QuadPart / frequency.QuadPart * 1000000 +
+                      counter.QuadPart % frequency.QuadPart * 1000000 / frequency.QuadPart);
+#else
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    return (uint64_t)now.tv_sec * 1000000 + (uint64_t)now.tv_nsec / 1000;
+#endif
+}
+
+/* seekable stdio stream I/O, serialized by a lock */
+typedef struct {
+    FILE* stream;
+    io_lock_t lock;
+} stream_io_ctx_t;
+
+static int stream_io_seek(FILE* stream, uint64_t offset, int whence)
+{
+#ifdef _WIN32
+    return _fseeki64(stream, (__int64)offset, whence);
+#else
+    return fseeko(stream, (off_t)offset, whence);
+#endif
+}
+
+static int64_t stream_io_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    stream_io_ctx_t* sctx = ctx;
+    if (offset > INT64_MAX)
+        return 0;
+
+    /* the position is shared state, seek and read must happen as one step */
+    io_lock(&sctx->lock);
+    int64_t result = -1;
+    if (stream_io_seek(sctx->stream, offset, SEEK_SET) == 0) {
+        size_t bytes_read = fread(buffer, 1, size, sctx->stream);
+        if (bytes_read == size || !ferror(sctx->stream)) {
+            result = (int64_t)bytes_read;
+        }
+        clearerr(sctx->stream);
+    }
+    io_unlock(&sctx->lock);
+    return result;
+}
+
+static int64_t stream_io_size(void* ctx)
+{
+    stream_io_ctx_t* sctx = ctx;
+
+    io_lock(&sctx->lock);
+    int64_t size = -1;
+    if (stream_io_seek(sctx->stream, 0, SEEK_END) == 0) {
+#ifdef _WIN32
+        size = _ftelli64(sctx->stream);
+#else
+        size = ftello(sctx->stream);
+#endif
+    }
+    io_unlock(&sctx->lock);
+    return size;
+}
+
+/* reads are serialized, so parallel readers gain nothing */
+static void stream_io_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    (void)ctx;
+    caps->max_concurrency = 1;
+}
+
+static void stream_io_close(void* ctx)
+{
+    stream_io_ctx_t* sctx = ctx;
+    io_lock_destroy(&sctx->lock);
+    free(sctx);
+}
+
+ziprand_io_t* ziprand_io_stream(FILE* stream)
+{
+    if (!stream)
+        return NULL;
+
+    stream_io_ctx_t* sctx = malloc(sizeof(stream_io_ctx_t));
+    if (!sctx)
+        return NULL;
+
+    sctx->stream = stream;
+    if (io_lock_init(&sctx->lock) != 0) {
+        free(sctx);
+        return NULL;
+    }
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        stream_io_close(sctx);
+        return NULL;
+    }
+
+    io->ctx = sctx;
+    io->read = stream_io_read;
+    io->get_size = stream_io_size;
+    io->close = stream_io_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = stream_io_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* HTTP(S) I/O with range requests, over libcurl or, in WebAssembly builds, fetch() */
+#if defined(ZIPRAND_HAVE_CURL) || defined(__EMSCRIPTEN__)
+#define HTTP_ATTEMPTS 3
+
+#define HTTP_VALIDATOR_SIZE 128
+
+/* value of a header line if its name matches (case-insensitive), NULL otherwise */
+static const char* http_header_value(const char* line, const char* name)
+{
+    size_t i = 0;
+    for (; name[i]; i++) {
+        char c = line[i];
+        if (c >= 'A' && c <= 'Z') {
+            c = (char)(c - 'A' + 'a');
+        }
+        if (c != name[i]) {
+            return NULL;
+        }
+    }
+    if (line[i] != ':') {
+        return NULL;
+    }
+    const char* value = line + i + 1;
+    while (*value == ' ' || *value == '\t') {
+        value++;
+    }
+    return value;
+}
+
+/* parse "bytes <first>-<last>/<total>", leaving -1 for the parts that are absent */
+static void
+http_parse_content_range(const char* value, int64_t* first, int64_t* last, int64_t* total)
+{
+    *first = -1;
+    *last = -1;
+    *total = -1;
+    if (strncmp(value, "bytes ", 6) == 0 && value[6] >= '0' && value[6] <= '9') {
+        char* dash;
+        unsigned long long start = strtoull(value + 6, &dash, 10);
+        if (*dash == '-' && dash[1] >= '0' && dash[1] <= '9') {
+            unsigned long long end = strtoull(dash + 1, NULL, 10);
+            if (start <= end && end <= INT64_MAX) {
+                *first = (int64_t)start;
+                *last = (int64_t)end;
+            }
+        }
+    }
+    const char* slash = strchr(value, '/');
+    if (slash && slash[1] >= '0' && slash[1] <= '9') {
+        unsigned long long size = strtoull(slash + 1, NULL, 10);
+        *total = size <= INT64_MAX ? (int64_t)size : -1;
+    }
+}
+
+/* copy a header value without its line ending, left empty if it doesn't fit */
+static void http_copy_value(char* dest, const char* value)
+{
+    size_t len = strcspn(value, "\r\n");
+    if (len >= HTTP_VALIDATOR_SIZE) {
+        len = 0;
+    }
+    memcpy(dest, value, len);
+    dest[len] = '\0';
+}
+
+#endif
+
+#ifdef ZIPRAND_HAVE_CURL
+#define HTTP_POOL_SIZE 8
+
+/* ranges per multi-range request, servers commonly cap how many they serve */
+#define HTTP_MAX_RANGES 32
+/* room for the boundary and part headers of a multipart/byteranges response, per range */
+#define HTTP_PART_OVERHEAD 256
+
+/* Azure Blob Storage service version sent with every request to it */
+#define AZURE_VERSION "2021-08-06"
+
+/* curl signs requests with AWS Signature Version 4 since 7.75.0 */
+#if LIBCURL_VERSION_NUM >= 0x074b00
+#define HTTP_HAVE_SIGV4 1
+#endif
+
+typedef struct {
+    char* url;
+    char* userpwd;      /* credentials for signing, or NULL */
+    char* sigv4;        /* CURLOPT_AWS_SIGV4 provider string, or NULL */
+    char* token_header; /* extra header carrying a session token, or NULL */
+    char* account;      /* Azure storage account signing with Shared Key, or NULL */
+    uint8_t* account_key;
+    size_t account_key_size;
+    char* resource; /* Azure canonicalized resource: /<account>/<url path> */
+    ziprand_http_options_t options;
+    int64_t size;
+    char etag[HTTP_VALIDATOR_SIZE];          /* strong ETag of the first response, or empty */
+    char last_modified[HTTP_VALIDATOR_SIZE]; /* Last-Modified of the first response, or empty */
+    io_lock_t lock;
+    CURL* idle[HTTP_POOL_SIZE]; /* handles kept for connection reuse */
+    size_t idle_count;
+} http_io_ctx_t;
+
+typedef struct {
+    uint8_t* buffer;
+    size_t capacity;
+    size_t length;
+    int64_t first; /* from Content-Range, -1 if absent */
+    int64_t total; /* from Content-Range, -1 if absent */
+    char etag[HTTP_VALIDATOR_SIZE];
+    char last_modified[HTTP_VALIDATOR_SIZE];
+    char boundary[HTTP_VALIDATOR_SIZE]; /* of a multipart/byteranges response, or empty */
+} http_transfer_t;
+
+static size_t http_write(char* data, size_t size, size_t nmemb, void* userdata)
+{
+    http_transfer_t* transfer = userdata;
+    size_t bytes = size * nmemb;
+
+    /* more than asked for means the range was ignored, abort instead of downloading it all */
+    if (bytes > transfer->capacity - transfer->length) {
+        return 0;
+    }
+    memcpy(transfer->buffer + transfer->length, data, bytes);
+    transfer->length += bytes;
+    return bytes;
+}
+
+static size_t http_header(char* data, size_t size, size_t nmemb, void* userdata)
+{
+    http_transfer_t* transfer = userdata;
+    size_t bytes = size * nmemb;
+
+    char line[512];
+    if (bytes >= sizeof(line)) {
+        return bytes;
+    }
+    memcpy(line, data, bytes);
+    line[bytes] = '\0';
+
+    /* every response of a redirect chain starts over */
+    if (strncmp(line, "HTTP/", 5) == 0) {
+        transfer->first = -1;
+        transfer->total = -1;
+        transfer->etag[0] = '\0';
+        transfer->last_modified[0] = '\0';
+        transfer->boundary[0] = '\0';
+        return bytes;
+    }
+
+    const char* value = http_header_value(line, "content-range");
+    if (value) {
+        int64_t last;
+        http_parse_content_range(value, &transfer->first, &last, &transfer->total);
+    }
+
+    /* Content-Type: multipart/byteranges; boundary=<boundary> */
+    value = http_header_value(line, "content-type");
+    if (value && strncmp(value, "multipart/byteranges", 20) == 0) {
+        const char* boundary = strstr(value, "boundary=");
+        if (boundary) {
+            boundary += 9;
+            if (*boundary == '"') {
+                boundary++;
+            }
+            http_copy_value(transfer->boundary, boundary);
+            transfer->boundary[strcspn(transfer->boundary, "\";")] = '\0';
+        }
+    }
+
+    /* weak ETags can't be used with If-Match, Last-Modified takes over then */
+    value = http_header_value(line, "etag");
+    if (value && strncmp(value, "W/", 2) != 0) {
+        http_copy_value(transfer->etag, value);
+    }
+    value = http_header_value(line, "last-modified");
+    if (value) {
+        http_copy_value(transfer->last_modified, value);
+    }
+    return bytes;
+}
+
+static CURL* http_acquire(http_io_ctx_t* hctx)
+{
+    CURL* curl = NULL;
+    io_lock(&hctx->lock);
+    if (hctx->idle_count > 0) {
+        curl = hctx->idle[--hctx->idle_count];
+    }
+    io_unlock(&hctx->lock);
+
+    if (!curl) {
+        curl = curl_easy_init();
+    }
+    return curl;
+}
+
+static void http_release(http_io_ctx_t* hctx, CURL* curl)
+{
+    io_lock(&hctx->lock);
+    if (hctx->idle_count < HTTP_POOL_SIZE) {
+        hctx->idle[hctx->idle_count++] = curl;
+        curl = NULL;
+    }
+    io_unlock(&hctx->lock);
+
+    if (curl) {
+        curl_easy_cleanup(curl);
+    }
+}
+
+/* transient failures that a busy or flaky service recovers from */
+static int http_retryable(CURLcode code, long status)
+{
+    switch (code) {
+    case CURLE_COULDNT_CONNECT:
+    case CURLE_OPERATION_TIMEDOUT:
+    case CURLE_SEND_ERROR:
+    case CURLE_RECV_ERROR:
+    case CURLE_GOT_NOTHING:
+    case CURLE_PARTIAL_FILE:
+        return 1;
+    case CURLE_HTTP_RETURNED_ERROR:
+        return status == 429 || status >= 500;
+    default:
+        return 0;
+    }
+}
+
+/* allocate a formatted string */
+static char* http_format(const char* format, ...)
+{
+    va_list args;
+    va_start(args, format);
+    int len = vsnprintf(NULL, 0, format, args);
+    va_end(args);
+    if (len < 0) {
+        return NULL;
+    }
+
+    char* result = malloc((size_t)len + 1);
+    if (result) {
+        va_start(args, format);
+        vsnprintf(result, (size_t)len + 1, format, args);
+        va_end(args);
+    }
+    return result;
+}
+
+#ifdef ZIPRAND_HAVE_OPENSSL
+/* x-ms-date and Authorization headers signing a GET with an Azure Shared Key; the signed
+ * string lists the conditional and Range headers curl sends, then the x-ms-* headers */
+static struct curl_slist* http_shared_key_sign(http_io_ctx_t* hctx,
+                                               struct curl_slist* headers,
+                                               const char* range,
+                                               int* ok)
+{
+    char date[64];
+    time_t now = time(NULL);
+    struct tm tm;
+#ifdef _WIN32
+    gmtime_s(&tm, &now);
+#else
+    gmtime_r(&now, &tm);
+#endif
+    strftime(date, sizeof(date), "%a, %d %b %Y %H:%M:%S GMT", &tm);
+
+    const char* if_match = hctx->etag[0] ? hctx->etag : "";
+    const char* if_unmodified = !hctx->etag[0] ? hctx->last_modified : "";
+    char* text = http_format("GET\n\n\n\n\n\n\n\n%s\n\n%s\nbytes=%s\nx-ms-date:%s\n"
+                             "x-ms-version:%s\n%s",
+                             if_match, if_unmodified, range, date, AZURE_VERSION,
+                             hctx->resource);
+    if (!text) {
+        *ok = 0;
+        return headers;
+    }
+
+    uint8_t mac[EVP_MAX_MD_SIZE];
+    unsigned int mac_size = 0;
+    int signed_ok = HMAC(EVP_sha256(), hctx->account_key, (int)hctx->account_key_size,
+                         (const uint8_t*)text, strlen(text), mac, &mac_size) != NULL;
+    free(text);
+
+    char signature[4 * ((EVP_MAX_MD_SIZE + 2) / 3) + 1];
+    if (signed_ok) {
+        EVP_EncodeBlock((uint8_t*)signature, mac, (int)mac_size);
+    }
+
+    char* date_header = http_format("x-ms-date: %s", date);
+    char* authorization =
+        signed_ok ? http_format("Authorization: SharedKey %s:%s", hctx->account, signature)
+                  : NULL;
+    *ok = date_header && authorization;
+    if (*ok) {
+        struct curl_slist* list = curl_slist_append(headers, date_header);
+        headers = list ? list : headers;
+        list = list ? curl_slist_append(headers, authorization) : NULL;
+        headers = list ? list : headers;
+        *ok = list != NULL;
+    }
+    free(date_header);
+    free(authorization);
+    return headers;
+}
+#endif
+
+/* fetch a range ("<first>-<last>", or several separated by commas) into transfer->buffer, the
+ * server must answer 206; once the validators of the first response are known, a replaced
+ * archive fails the request with ZIPRAND_ERR_SOURCE_CHANGED instead of returning bytes of the
+ * new one */
+static int64_t http_fetch(http_io_ctx_t* hctx, const char* range, http_transfer_t* transfer)
+{
+    CURL* curl = http_acquire(hctx);
+    if (!curl) {
+        return -1;
+    }
+
+    char condition[HTTP_VALIDATOR_SIZE + 32];
+    condition[0] = '\0';
+    if (hctx->etag[0]) {
+        snprintf(condition, sizeof(condition), "If-Match: %s", hctx->etag);
+    } else if (hctx->last_modified[0]) {
+        snprintf(condition, sizeof(condition), "If-Unmodified-Since: %s", hctx->last_modified);
+    }
+
+    struct curl_slist* headers = NULL;
+    int headers_ok = 1;
+    if (condition[0]) {
+        struct curl_slist* list = curl_slist_append(headers, condition);
+        headers_ok = list != NULL;
+        headers = list ? list : headers;
+    }
+    if (headers_ok && hctx->token_header) {
+        struct curl_slist* list = curl_slist_append(headers, hctx->token_header);
+        headers_ok = list != NULL;
+        headers = list ? list : headers;
+    }
+#ifdef ZIPRAND_HAVE_OPENSSL
+    if (headers_ok && hctx->account) {
+        headers = http_shared_key_sign(hctx, headers, range, &headers_ok);
+    }
+#endif
+    if (!headers_ok) {
+        curl_slist_free_all(headers);
+        http_release(hctx, curl);
+        return -1;
+    }
+
+    CURLcode code = CURLE_OK;
+    long status = 0;
+    for (int attempt = 0; attempt < HTTP_ATTEMPTS; attempt++) {
+        if (attempt > 0) {
+            io_sleep_ms(100UL << attempt);
+        }
+        transfer->length = 0;
+        transfer->first = -1;
+        transfer->total = -1;
+        transfer->boundary[0] = '\0';
+
+        /* reset keeps the connection cache, only the options go */
+        curl_easy_reset(curl);
+        curl_easy_setopt(curl, CURLOPT_URL, hctx->url);
+        curl_easy_setopt(curl, CURLOPT_RANGE, range);
+        curl_easy_setopt(curl, CURLOPT_HTTPHEADER, headers);
+        curl_easy_setopt(curl, CURLOPT_FOLLOWLOCATION, 1L);
+        curl_easy_setopt(curl, CURLOPT_FAILONERROR, 1L);
+        curl_easy_setopt(curl, CURLOPT_NOSIGNAL, 1L);
+        curl_easy_setopt(curl, CURLOPT_WRITEFUNCTION, http_write);
+        curl_easy_setopt(curl, CURLOPT_WRITEDATA, transfer);
+        curl_easy_setopt(curl, CURLOPT_HEADERFUNCTION, http_header);
+        curl_easy_setopt(curl, CURLOPT_HEADERDATA, transfer);
+        curl_easy_setopt(curl, CURLOPT_TIMEOUT_MS, (long)hctx->options.timeout_ms);
+        curl_easy_setopt(curl, CURLOPT_CONNECTTIMEOUT_MS, (long)hctx->options.connect_timeout_ms);
+        /* a transfer that stays below 1 byte/s for the stall timeout is given up */
+        curl_easy_setopt(curl, CURLOPT_LOW_SPEED_LIMIT, 1L);
+        curl_easy_setopt(curl, CURLOPT_LOW_SPEED_TIME,
+                         (long)((hctx->options.stall_timeout_ms + 999) / 1000));
+        if (hctx->userpwd) {
+            curl_easy_setopt(curl, CURLOPT_USERPWD, hctx->userpwd);
+        }
+#ifdef HTTP_HAVE_SIGV4
+        if (hctx->sigv4) {
+            curl_easy_setopt(curl, CURLOPT_AWS_SIGV4, hctx->sigv4);
+        }
+#endif
+
+        code = curl_easy_perform(curl);
+        status = 0;
+        curl_easy_getinfo(curl, CURLINFO_RESPONSE_CODE, &status);
+        if (!http_retryable(code, status)) {
+            break;
+        }
+    }
+    http_release(hctx, curl);
+    curl_slist_free_all(headers);
+
+    if (status == 412) {
+        return ZIPRAND_ERR_SOURCE_CHANGED;
+    }
+    if (code == CURLE_OPERATION_TIMEDOUT) {
+        return ZIPRAND_ERR_TIMEOUT;
+    }
+    if (code != CURLE_OK || status != 206) {
+        return -1;
+    }
+
+    /* servers that ignore preconditions still give away a replacement by its size or ETag;
+     * the parts of a multipart response carry their own sizes */
+    if (hctx->size > 0 && !transfer->boundary[0] && transfer->total != hctx->size) {
+        return ZIPRAND_ERR_SOURCE_CHANGED;
+    }
+    if (hctx->etag[0] && transfer->etag[0] && strcmp(hctx->etag, transfer->etag) != 0) {
+        return ZIPRAND_ERR_SOURCE_CHANGED;
+    }
+    return (int64_t)transfer->length;
+}
+
+static int64_t http_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    http_io_ctx_t* hctx = ctx;
+
+    if (size == 0 || offset >= (uint64_t)hctx->size) {
+        return 0;
+    }
+    uint64_t remaining = (uint64_t)hctx->size - offset;
+    if (size > remaining) {
+        size = (size_t)remaining;
+    }
+
+    char range[48];
+    snprintf(range, sizeof(range), "%llu-%llu", (unsigned long long)offset,
+             (unsigned long long)(offset + size - 1));
+
+    http_transfer_t transfer = {0};
+    transfer.buffer = buffer;
+    transfer.capacity = size;
+    int64_t result = http_fetch(hctx, range, &transfer);
+
+    /* a multipart answer to a single range isn't plain data, and a proxy that moved or
+     * dropped the range would hand over bytes from somewhere else */
+    if (result >= 0 && (transfer.boundary[0] || transfer.first != (int64_t)offset)) {
+        return -1;
+    }
+    return result;
+}
+
+/* find needle in data, NULL if absent */
+static const uint8_t* http_find(const uint8_t* data, size_t len, const char* needle)
+{
+    size_t needle_len = strlen(needle);
+    for (size_t i = 0; needle_len <= len && i <= len - needle_len; i++) {
+        if (memcmp(data + i, needle, needle_len) == 0) {
+            return data + i;
+        }
+    }
+    return NULL;
+}
+
+/* copy what a response part holding [first, first + len) has for each range not yet filled */
+static void http_fill_ranges(const ziprand_read_range_t* ranges,
+                             size_t count,
+                             uint8_t* filled,
+                             uint64_t first,
+                             const uint8_t* data,
+                             size_t len)
+{
+    for (size_t i = 0; i < count; i++) {
+        if (!filled[i] && ranges[i].offset >= first &&
+            ranges[i].offset + ranges[i].size <= first + len) {
+            memcpy(ranges[i].buffer, data + (ranges[i].offset - first), ranges[i].size);
+            filled[i] = 1;
+        }
+    }
+}
+
+/* split a multipart/byteranges body into its parts and fill the ranges from them */
+static ziprand_error_t http_parse_multipart(http_io_ctx_t* hctx,
+                                            const http_transfer_t* transfer,
+                                            const ziprand_read_range_t* ranges,
+                                            size_t count,
+                                            uint8_t* filled)
+{
+    char delimiter[HTTP_VALIDATOR_SIZE + 2];
+    snprintf(delimiter, sizeof(delimiter), "--%s", transfer->boundary);
+    size_t delimiter_len = strlen(delimiter);
+
+    const uint8_t* pos = transfer->buffer;
+    const uint8_t* end = transfer->buffer + transfer->length;
+    for (;;) {
+        /* each part starts with a delimiter line, the last delimiter ends in "--" */
+        pos = http_find(pos, (size_t)(end - pos), delimiter);
+        if (!pos || (size_t)(end - pos) < delimiter_len + 2 ||
+            memcmp(pos + delimiter_len, "--", 2) == 0) {
+            return ZIPRAND_OK;
+        }
+        const uint8_t* line_end = http_find(pos, (size_t)(end - pos), "\r\n");
+        if (!line_end) {
+            return ZIPRAND_ERR_IO;
+        }
+        pos = line_end + 2;
+
+        /* part headers, up to an empty line */
+        int64_t first = -1;
+        int64_t last = -1;
+        int64_t total = -1;
+        for (;;) {
+            line_end = http_find(pos, (size_t)(end - pos), "\r\n");
+            if (!line_end) {
+                return ZIPRAND_ERR_IO;
+            }
+            size_t line_len = (size_t)(line_end - pos);
+            char line[512];
+            if (line_len > 0 && line_len < sizeof(line)) {
+                memcpy(line, pos, line_len);
+                line[line_len] = '\0';
+                const char* value = http_header_value(line, "content-range");
+                if (value) {
+                    http_parse_content_range(value, &first, &last, &total);
+                }
+            }
+            pos = line_end + 2;
+            if (line_len == 0) {
+                break;
+            }
+        }
+        if (first < 0) {
+            return ZIPRAND_ERR_IO;
+        }
+        if (total != hctx->size) {
+            return ZIPRAND_ERR_SOURCE_CHANGED;
+        }
+
+        uint64_t len = (uint64_t)(last - first) + 1;
+        if (len > (uint64_t)(end - pos)) {
+            return ZIPRAND_ERR_IO;
+        }
+        http_fill_ranges(ranges, count, filled, (uint64_t)first, pos, (size_t)len);
+        pos += len;
+    }
+}
+
+/* fill ranges with one multipart range request; ranges the answer leaves out (servers may
+ * serve only some ranges, or ignore all but the first) are read one by one */
+static ziprand_error_t
+http_fetch_ranges(http_io_ctx_t* hctx, const ziprand_read_range_t* ranges, size_t count)
+{
+    uint8_t* filled = calloc(count, 1);
+    char* range = malloc(count * 48);
+    if (!filled || !range) {
+        free(filled);
+        free(range);
+        return ZIPRAND_ERR_NOMEM;
+    }
+
+    size_t range_len = 0;
+    uint64_t wanted = 0;
+    for (size_t i = 0; i < count; i++) {
+        if (ranges[i].size == 0) {
+            filled[i] = 1;
+            continue;
+        }
+        range_len += (size_t)snprintf(range + range_len, 48, "%s%llu-%llu", range_len ? "," : "",
+                                      (unsigned long long)ranges[i].offset,
+                                      (unsigned long long)(ranges[i].offset + ranges[i].size - 1));
+        wanted += ranges[i].size;
+    }
+
+    ziprand_error_t err = ZIPRAND_OK;
+    http_transfer_t transfer = {0};
+    transfer.capacity = (size_t)wanted + (count + 1) * HTTP_PART_OVERHEAD;
+    transfer.buffer = range_len > 0 ? malloc(transfer.capacity) : NULL;
+    if (transfer.buffer) {
+        int64_t result = http_fetch(hctx, range, &transfer);
+        if (result == ZIPRAND_ERR_SOURCE_CHANGED || result == ZIPRAND_ERR_TIMEOUT) {
+            err = (ziprand_error_t)result;
+        } else if (result >= 0 && transfer.boundary[0]) {
+            err = http_parse_multipart(hctx, &transfer, ranges, count, filled);
+        } else if (result >= 0 && transfer.first >= 0) {
+            http_fill_ranges(ranges, count, filled, (uint64_t)transfer.first, transfer.buffer,
+                             transfer.length);
+        }
+        free(transfer.buffer);
+    }
+    free(range);
+
+    for (size_t i = 0; i < count && err == ZIPRAND_OK; i++) {
+        uint8_t* buffer = ranges[i].buffer;
+        size_t done = 0;
+        while (!filled[i] && done < ranges[i].size) {
+            int64_t got = http_read(hctx, ranges[i].offset + done, buffer + done,
+                                    ranges[i].size - done);
+            if (got <= 0) {
+                err = got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+                break;
+            }
+            done += (size_t)got;
+        }
+    }
+    free(filled);
+    return err;
+}
+
+static ziprand_error_t
+http_read_ranges(void* ctx, const ziprand_read_range_t* ranges, size_t count)
+{
+    http_io_ctx_t* hctx = ctx;
+    for (size_t first = 0; first < count; first += HTTP_MAX_RANGES) {
+        size_t batch = count - first < HTTP_MAX_RANGES ? count - first : HTTP_MAX_RANGES;
+        ziprand_error_t err = http_fetch_ranges(hctx, ranges + first, batch);
+        if (err != ZIPRAND_OK) {
+            return err;
+        }
+    }
+    return ZIPRAND_OK;
+}
+
+static int64_t http_size(void* ctx)
+{
+    http_io_ctx_t* hctx = ctx;
+    return hctx->size;
+}
+
+static void http_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    (void)ctx;
+    caps->latency = ZIPRAND_LATENCY_REMOTE;
+    caps->max_concurrency = HTTP_POOL_SIZE;
+}
+
+static void http_close(void* ctx)
+{
+    http_io_ctx_t* hctx = ctx;
+    for (size_t i = 0; i < hctx->idle_count; i++) {
+        curl_easy_cleanup(hctx->idle[i]);
+    }
+    io_lock_destroy(&hctx->lock);
+    free(hctx->url);
+    free(hctx->userpwd);
+    free(hctx->sigv4);
+    free(hctx->token_header);
+    free(hctx->account);
+    free(hctx->account_key);
+    free(hctx->resource);
+    free(hctx);
+}
+
+/* percent-encode an object key, keeping the slashes of its path */
+static char* http_encode_path(const char* path)
+{
+    static const char hex[] = "0123456789ABCDEF";
+    size_t len = strlen(path);
+    char* result = malloc(len * 3 + 1);
+    if (!result) {
+        return NULL;
+    }
+
+    char* out = result;
+    for (const unsigned char* in = (const unsigned char*)path; *in; in++) {
+        unsigned char c = *in;
+        if ((c >= 'A' && c <= 'Z') || (c >= 'a' && c <= 'z') || (c >= '0' && c <= '9') ||
+            c == '-' || c == '_' || c == '.' || c == '~' || c == '/') {
+            *out++ = (char)c;
+        } else {
+            *out++ = '%';
+            *out++ = hex[c >> 4];
+            *out++ = hex[c & 15];
+        }
+    }
+    *out = '\0';
+    return result;
+}
+
+/* probe the server and wrap the context, takes ownership of hctx */
+static ziprand_io_t* http_io_create(http_io_ctx_t* hctx, const ziprand_http_options_t* options)
+{
+    if (options) {
+        hctx->options = *options;
+    }
+    if (hctx->options.connect_timeout_ms == 0) {
+        hctx->options.connect_timeout_ms = 10000;
+    }
+    if (hctx->options.stall_timeout_ms == 0) {
+        hctx->options.stall_timeout_ms = 30000;
+    }
+
+    if (io_lock_init(&hctx->lock) != 0) {
+        free(hctx->url);
+        free(hctx->userpwd);
+        free(hctx->sigv4);
+        free(hctx->token_header);
+        free(hctx->account);
+        free(hctx->account_key);
+        free(hctx->resource);
+        free(hctx);
+        return NULL;
+    }
+
+    /* probe with a one-byte range: proves range support, reports the total size and the
+     * validators that later reads are made conditional on */
+    uint8_t probe;
+    http_transfer_t transfer = {0};
+    transfer.buffer = &probe;
+    transfer.capacity = 1;
+    if (http_fetch(hctx, "0-0", &transfer) != 1 || transfer.total <= 0) {
+        http_close(hctx);
+        return NULL;
+    }
+    hctx->size = transfer.total;
+    memcpy(hctx->etag, transfer.etag, sizeof(hctx->etag));
+    memcpy(hctx->last_modified, transfer.last_modified, sizeof(hctx->last_modified));
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        http_close(hctx);
+        return NULL;
+    }
+
+    io->ctx = hctx;
+    io->read = http_read;
+    io->get_size = http_size;
+    io->close = http_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = http_capabilities;
+    io->read_ranges = http_read_ranges;
+
+    return io;
+}
+#elif defined(__EMSCRIPTEN__)
+typedef struct {
+    char* url;
+    ziprand_http_options_t options;
+    int64_t size;
+    char etag[HTTP_VALIDATOR_SIZE]; /* strong ETag of the first response, or empty */
+} fetch_io_ctx_t;
+
+/* what a response says about the range and the archive it came from */
+typedef struct {
+    int64_t first; /* from Content-Range, -1 if absent */
+    int64_t total; /* from Content-Range, -1 if absent */
+    char etag[HTTP_VALIDATOR_SIZE];
+} fetch_response_t;
+
+static void fetch_parse_headers(emscripten_fetch_t* fetch, fetch_response_t* response)
+{
+    response->first = -1;
+    response->total = -1;
+    response->etag[0] = '\0';
+
+    size_t len = emscripten_fetch_get_response_headers_length(fetch);
+    char* headers = malloc(len + 1);
+    if (!headers) {
+        return;
+    }
+    emscripten_fetch_get_response_headers(fetch, headers, len + 1);
+
+    /* one "name: value" per line */
+    char* line = headers;
+    while (*line) {
+        char* end = line + strcspn(line, "\r\n");
+        char saved = *end;
+        *end = '\0';
+
+        int64_t last;
+        const char* value = http_header_value(line, "content-range");
+        if (value) {
+            http_parse_content_range(value, &response->first, &last, &response->total);
+        }
+        value = http_header_value(line, "etag");
+        if (value && strncmp(value, "W/", 2) != 0) {
+            http_copy_value(response->etag, value);
+        }
+
+        *end = saved;
+        line = end + strspn(end, "\r\n");
+    }
+    free(headers);
+}
+
+/* status 0 is a network error */
+static int fetch_retryable(unsigned short status)
+{
+    return status == 0 || status == 429 || status >= 500;
+}
+
+/* fetch [offset, offset + size) with a synchronous range request, the server must answer 206;
+ * browsers don't let fetch() abort a response that ignores the range, and a conditional
+ * request would need a CORS preflight, so a replaced archive is recognized by its size or
+ * ETag instead */
+static int64_t fetch_range(
+    fetch_io_ctx_t* fctx, uint64_t offset, void* buffer, size_t size, fetch_response_t* response)
+{
+    char range[64];
+    snprintf(range, sizeof(range), "bytes=%llu-%llu", (unsigned long long)offset,
+             (unsigned long long)(offset + size - 1));
+    const char* headers[] = {"Range", range, NULL};
+
+    emscripten_fetch_attr_t attr;
+    emscripten_fetch_attr_init(&attr);
+    strcpy(attr.requestMethod, "GET");
+    attr.attributes = EMSCRIPTEN_FETCH_LOAD_TO_MEMORY | EMSCRIPTEN_FETCH_SYNCHRONOUS;
+    attr.requestHeaders = headers;
+    attr.timeoutMSecs = fctx->options.timeout_ms;
+
+    emscripten_fetch_t* fetch = emscripten_fetch(&attr, fctx->url);
+    for (int attempt = 1; fetch && attempt < HTTP_ATTEMPTS && fetch_retryable(fetch->status);
+         attempt++) {
+        emscripten_fetch_close(fetch);
+        io_sleep_ms(100UL << attempt);
+        fetch = emscripten_fetch(&attr, fctx->url);
+    }
+    if (!fetch) {
+        return -1;
+    }
+
+    int64_t result = -1;
+    if (fetch->status == 206 && fetch->numBytes <= size) {
+        fetch_parse_headers(fetch, response);
+        if (response->first == (int64_t)offset) {
+            memcpy(buffer, fetch->data, (size_t)fetch->numBytes);
+            result = (int64_t)fetch->numBytes;
+        }
+    }
+    emscripten_fetch_close(fetch);
+
+    if (result >= 0 && fctx->size > 0 &&
+        (response->total != fctx->size ||
+         (fctx->etag[0] && response->etag[0] && strcmp(fctx->etag, response->etag) != 0))) {
+        return ZIPRAND_ERR_SOURCE_CHANGED;
+    }
+    return result;
+}
+
+static int64_t fetch_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    fetch_io_ctx_t* fctx = ctx;
+
+    if (size == 0 || offset >= (uint64_t)fctx->size) {
+        return 0;
+    }
+    uint64_t remaining = (uint64_t)fctx->size - offset;
+    if (size > remaining) {
+        size = (size_t)remaining;
+    }
+
+    fetch_response_t response;
+    return fetch_range(fctx, offset, buffer, size, &response);
+}
+
+static int64_t fetch_size(void* ctx)
+{
+    fetch_io_ctx_t* fctx = ctx;
+    return fctx->size;
+}
+
+static void fetch_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    (void)ctx;
+    caps->latency = ZIPRAND_LATENCY_REMOTE;
+}
+
+static void fetch_close(void* ctx)
+{
+    fetch_io_ctx_t* fctx = ctx;
+    free(fctx->url);
+    free(fctx);
+}
+
+static ziprand_io_t* fetch_io_create(const char* url, const ziprand_http_options_t* options)
+{
+    fetch_io_ctx_t* fctx = calloc(1, sizeof(fetch_io_ctx_t));
+    if (!fctx) {
+        return NULL;
+    }
+
+    size_t url_len = strlen(url);
+    fctx->url = malloc(url_len + 1);
+    if (!fctx->url) {
+        free(fctx);
+        return NULL;
+    }
+    memcpy(fctx->url, url, url_len + 1);
+    if (options) {
+        fctx->options = *options;
+    }
+
+    /* probe with a one-byte range: proves range support and reports the total size */
+    uint8_t probe;
+    fetch_response_t response;
+    if (fetch_range(fctx, 0, &probe, 1, &response) != 1 || response.total <= 0) {
+        fetch_close(fctx);
+        return NULL;
+    }
+    fctx->size = response.total;
+    memcpy(fctx->etag, response.etag, sizeof(fctx->etag));
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        fetch_close(fctx);
+        return NULL;
+    }
+
+    io->ctx = fctx;
+    io->read = fetch_read;
+    io->get_size = fetch_size;
+    io->close = fetch_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = fetch_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+#endif
+
+ziprand_io_t* ziprand_io_http(const char* url)
+{
+    return ziprand_io_http_ex(url, NULL);
+}
+
+ziprand_io_t* ziprand_io_http_ex(const char* url, const ziprand_http_options_t* options)
+{
+#ifdef ZIPRAND_HAVE_CURL
+    if (!url)
+        return NULL;
+
+    http_io_ctx_t* hctx = calloc(1, sizeof(http_io_ctx_t));
+    if (!hctx)
+        return NULL;
+
+    hctx->url = http_format("%s", url);
+    if (!hctx->url) {
+        free(hctx);
+        return NULL;
+    }
+    return http_io_create(hctx, options);
+#elif defined(__EMSCRIPTEN__)
+    if (!url)
+        return NULL;
+    return fetch_io_create(url, options);
+#else
+    (void)url;
+    (void)options;
+    return NULL;
+#endif
+}
+
+ziprand_io_t* ziprand_io_s3(const ziprand_s3_options_t* options)
+{
+#if defined(ZIPRAND_HAVE_CURL) && defined(HTTP_HAVE_SIGV4)
+    if (!options || !options->bucket || !options->key || !options->region)
+        return NULL;
+    if (!options->access_key_id != !options->secret_access_key)
+        return NULL;
+
+    http_io_ctx_t* hctx = calloc(1, sizeof(http_io_ctx_t));
+    if (!hctx)
+        return NULL;
+
+    char* key = http_encode_path(options->key);
+    int ok = key != NULL;
+    if (ok && options->endpoint) {
+        /* path-style for S3-compatible services (MinIO, R2, GCS interoperability) */
+        hctx->url = http_format("%s/%s/%s", options->endpoint, options->bucket, key);
+    } else if (ok) {
+        hctx->url = http_format("https://%s.s3.%s.amazonaws.com/%s", options->bucket,
+                                options->region, key);
+    }
+    ok = ok && hctx->url;
+    free(key);
+
+    /* without credentials the bucket must allow anonymous reads */
+    if (ok && options->access_key_id) {
+        hctx->userpwd = http_format("%s:%s", options->access_key_id, options->secret_access_key);
+        hctx->sigv4 = http_format("aws:amz:%s:s3", options->region);
+        ok = hctx->userpwd && hctx->sigv4;
+    }
+    if (ok && options->session_token) {
+        hctx->token_header = http_format("x-amz-security-token: %s", options->session_token);
+        ok = hctx->token_header != NULL;
+    }
+    if (!ok) {
+        free(hctx->url);
+        free(hctx->userpwd);
+        free(hctx->sigv4);
+        free(hctx->token_header);
+        free(hctx);
+        return NULL;
+    }
+    return http_io_create(hctx, &options->http);
+#else
+    (void)options;
+    return NULL;
+#endif
+}
+
+#if defined(ZIPRAND_HAVE_CURL) && defined(ZIPRAND_HAVE_OPENSSL)
+/* decode a base64 account key, NULL if it isn't valid base64 */
+static uint8_t* azure_decode_key(const char* text, size_t* size)
+{
+    size_t len = strlen(text);
+    if (len == 0 || len % 4 != 0 || len > INT32_MAX) {
+        return NULL;
+    }
+    uint8_t* key = malloc(len / 4 * 3);
+    if (!key) {
+        return NULL;
+    }
+    int decoded = EVP_DecodeBlock(key, (const uint8_t*)text, (int)len);
+    if (decoded < 0) {
+        free(key);
+        return NULL;
+    }
+    /* EVP_DecodeBlock counts the padding as zero bytes */
+    size_t padding = text[len - 1] == '=' ? (text[len - 2] == '=' ? 2 : 1) : 0;
+    *size = (size_t)decoded - padding;
+    return key;
+}
+#endif
+
+ziprand_io_t* ziprand_io_azure(const ziprand_azure_options_t* options)
+{
+#ifdef ZIPRAND_HAVE_CURL
+    if (!options || !options->account || !options->container || !options->blob)
+        return NULL;
+    /* a SAS and Shared Key are alternatives */
+    if (options->account_key && options->sas_token)
+        return NULL;
+#ifndef ZIPRAND_HAVE_OPENSSL
+    if (options->account_key)
+        return NULL;
+#endif
+
+    http_io_ctx_t* hctx = calloc(1, sizeof(http_io_ctx_t));
+    if (!hctx)
+        return NULL;
+
+    char* blob = http_encode_path(options->blob);
+    int ok = blob != NULL;
+    if (ok && options->endpoint) {
+        /* path-style for the Azurite emulator and custom domains */
+        hctx->url = http_format("%s/%s/%s", options->endpoint, options->container, blob);
+    } else if (ok) {
+        hctx->url = http_format("https://%s.blob.core.windows.net/%s/%s", options->account,
+                                options->container, blob);
+    }
+    ok = ok && hctx->url;
+    free(blob);
+
+    hctx->token_header = http_format("x-ms-version: %s", AZURE_VERSION);
+    ok = ok && hctx->token_header;
+
+#ifdef ZIPRAND_HAVE_OPENSSL
+    /* Shared Key signs the URL path, prefixed with the account */
+    if (ok && options->account_key) {
+        const char* scheme = strstr(hctx->url, "://");
+        const char* path = scheme ? strchr(scheme + 3, '/') : NULL;
+        hctx->account = http_format("%s", options->account);
+        hctx->resource = path ? http_format("/%s%s", options->account, path) : NULL;
+        hctx->account_key = azure_decode_key(options->account_key, &hctx->account_key_size);
+        ok = hctx->account && hctx->resource && hctx->account_key;
+    }
+#endif
+
+    /* without a key or SAS the container must allow anonymous reads */
+    if (ok && options->sas_token) {
+        const char* sas = options->sas_token[0] == '?' ? options->sas_token + 1
+                                                        : options->sas_token;
+        char* url = http_format("%s?%s", hctx->url, sas);
+        ok = url != NULL;
+        if (ok) {
+            free(hctx->url);
+            hctx->url = url;
+        }
+    }
+    if (!ok) {
+        free(hctx->url);
+        free(hctx->token_header);
+        free(hctx->account);
+        free(hctx->account_key);
+        free(hctx->resource);
+        free(hctx);
+        return NULL;
+    }
+    return http_io_create(hctx, &options->http);
+#else
+    (void)options;
+    return NULL;
+#endif
+}
+
+/* aligned-read adapter for O_DIRECT handles and raw block devices */
+typedef struct {
+    ziprand_io_t* inner;
+    size_t alignment;
+} aligned_io_ctx_t;
+
+static void* aligned_buffer_alloc(size_t alignment, size_t size)
+{
+#ifdef _WIN32
+    return _aligned_malloc(size, alignment);
+#else
+    void* buffer;
+    return posix_memalign(&buffer, alignment, size) == 0 ? buffer : NULL;
+#endif
+}
+
+static void aligned_buffer_free(void* buffer)
+{
+#ifdef _WIN32
+    _aligned_free(buffer);
+#else
+    free(buffer);
+#endif
+}
+
+static int64_t aligned_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    aligned_io_ctx_t* actx = ctx;
+    ziprand_io_t* inner = actx->inner;
+    size_t mask = actx->alignment - 1;
+
+    if (size == 0) {
+        return 0;
+    }
+
+    /* already aligned requests go straight through */
+    if ((offset & mask) == 0 && (size & mask) == 0 && ((uintptr_t)buffer & mask) == 0) {
+        return inner->read(inner->ctx, offset, buffer, size);
+    }
+
+    /* round out to whole blocks and read them into an aligned bounce buffer */
+    uint64_t start = offset & ~(uint64_t)mask;
+    size_t lead = (size_t)(offset - start);
+    if (size > SIZE_MAX - lead - mask) {
+        return -1;
+    }
+    size_t span = (lead + size + mask) & ~mask;
+
+    uint8_t* bounce = aligned_buffer_alloc(actx->alignment, span);
+    if (!bounce) {
+        return -1;
+    }
+
+    int64_t result = inner->read(inner->ctx, start, bounce, span);
+    if (result >= 0) {
+        size_t available = (uint64_t)result > lead ? (size_t)result - lead : 0;
+        size_t copied = available < size ? available : size;
+        memcpy(buffer, bounce + lead, copied);
+        result = (int64_t)copied;
+    }
+    aligned_buffer_free(bounce);
+    return result;
+}
+
+static int64_t aligned_size(void* ctx)
+{
+    aligned_io_ctx_t* actx = ctx;
+    return actx->inner->get_size(actx->inner->ctx);
+}
+
+static void aligned_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    aligned_io_ctx_t* actx = ctx;
+    ziprand_io_capabilities(actx->inner, caps);
+    if (caps->block_size % actx->alignment != 0) {
+        caps->block_size = (caps->block_size / actx->alignment + 1) * actx->alignment;
+    }
+}
+
+static void aligned_close(void* ctx)
+{
+    aligned_io_ctx_t* actx = ctx;
+    ziprand_io_free(actx->inner);
+    free(actx);
+}
+
+ziprand_io_t* ziprand_io_aligned(ziprand_io_t* inner, size_t alignment)
+{
+    if (alignment == 0)
+        alignment = 4096;
+    if (!inner || !inner->read || !inner->get_size || (alignment & (alignment - 1)) != 0 ||
+        alignment < sizeof(void*))
+        return NULL;
+
+    aligned_io_ctx_t* actx = malloc(sizeof(aligned_io_ctx_t));
+    if (!actx)
+        return NULL;
+
+    actx->inner = inner;
+    actx->alignment = alignment;
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        free(actx);
+        return NULL;
+    }
+
+    io->ctx = actx;
+    io->read = aligned_read;
+    io->get_size = aligned_size;
+    io->close = aligned_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = aligned_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* several sources presented as one, e.g. an archive stored as fixed-size parts */
+typedef struct {
+    ziprand_io_t** parts;
+    uint64_t* starts; /* count + 1 offsets, the last one is the total size */
+    size_t count;
+} chain_io_ctx_t;
+
+static int64_t chain_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    chain_io_ctx_t* cctx = ctx;
+    uint64_t total_size = cctx->starts[cctx->count];
+
+    if (offset >= total_size) {
+        return 0;
+    }
+    if (size > total_size - offset) {
+        size = (size_t)(total_size - offset);
+    }
+
+    /* last part starting at or before offset */
+    size_t lo = 0;
+    size_t hi = cctx->count;
+    while (hi - lo > 1) {
+        size_t mid = lo + (hi - lo) / 2;
+        if (cctx->starts[mid] <= offset) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    size_t done = 0;
+    for (size_t i = lo; i < cctx->count && done < size; i++) {
+        uint64_t part_offset = offset + done - cctx->starts[i];
+        uint64_t part_size = cctx->starts[i + 1] - cctx->starts[i];
+        if (part_offset >= part_size) {
+            continue;
+        }
+        size_t want = size - done;
+        if (want > part_size - part_offset) {
+            want = (size_t)(part_size - part_offset);
+        }
+
+        ziprand_io_t* part = cctx->parts[i];
+        int64_t got = part->read(part->ctx, part_offset, (uint8_t*)buffer + done, want);
+        if (got < 0) {
+            return done > 0 ? (int64_t)done : got;
+        }
+        done += (size_t)got;
+        if ((size_t)got < want) {
+            break;
+        }
+    }
+    return (int64_t)done;
+}
+
+static int64_t chain_size(void* ctx)
+{
+    chain_io_ctx_t* cctx = ctx;
+    return (int64_t)cctx->starts[cctx->count];
+}
+
+/* remote if any part is, with the largest block size and concurrency of the parts */
+static void chain_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    chain_io_ctx_t* cctx = ctx;
+    int all_memory = 1;
+    for (size_t i = 0; i < cctx->count; i++) {
+        ziprand_io_caps_t part;
+        ziprand_io_capabilities(cctx->parts[i], &part);
+        if (part.latency == ZIPRAND_LATENCY_REMOTE) {
+            caps->latency = ZIPRAND_LATENCY_REMOTE;
+        }
+        all_memory &= part.latency == ZIPRAND_LATENCY_MEMORY;
+        if (part.block_size > caps->block_size) {
+            caps->block_size = part.block_size;
+        }
+        if (part.max_concurrency > caps->max_concurrency) {
+            caps->max_concurrency = part.max_concurrency;
+        }
+    }
+    if (all_memory) {
+        caps->latency = ZIPRAND_LATENCY_MEMORY;
+    }
+}
+
+static void chain_close(void* ctx)
+{
+    chain_io_ctx_t* cctx = ctx;
+    for (size_t i = 0; i < cctx->count; i++) {
+        ziprand_io_free(cctx->parts[i]);
+    }
+    free(cctx->parts);
+    free(cctx->starts);
+    free(cctx);
+}
+
+ziprand_io_t* ziprand_io_chain(ziprand_io_t* const* parts, size_t count)
+{
+    if (!parts || count == 0)
+        return NULL;
+
+    chain_io_ctx_t* cctx = malloc(sizeof(chain_io_ctx_t));
+    if (!cctx)
+        return NULL;
+
+    cctx->count = count;
+    cctx->parts = malloc(count * sizeof(ziprand_io_t*));
+    cctx->starts = malloc((count + 1) * sizeof(uint64_t));
+    if (!cctx->parts || !cctx->starts) {
+        free(cctx->parts);
+        free(cctx->starts);
+        free(cctx);
+        return NULL;
+    }
+
+    /* part sizes are fixed once the chain is built */
+    uint64_t offset = 0;
+    for (size_t i = 0; i < count; i++) {
+        int64_t part_size = parts[i] && parts[i]->read && parts[i]->get_size
+                                ? parts[i]->get_size(parts[i]->ctx)
+                                : -1;
+        if (part_size < 0 || (uint64_t)part_size > (uint64_t)INT64_MAX - offset) {
+            free(cctx->parts);
+            free(cctx->starts);
+            free(cctx);
+            return NULL;
+        }
+        cctx->parts[i] = parts[i];
+        cctx->starts[i] = offset;
+        offset += (uint64_t)part_size;
+    }
+    cctx->starts[count] = offset;
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        free(cctx->parts);
+        free(cctx->starts);
+        free(cctx);
+        return NULL;
+    }
+
+    io->ctx = cctx;
+    io->read = chain_read;
+    io->get_size = chain_size;
+    io->close = chain_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = chain_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* byte window of another source, e.g. a zip inside an installer or firmware image */
+typedef struct {
+    ziprand_io_t* inner;
+    uint64_t offset;
+    uint64_t size;
+} window_io_ctx_t;
+
+static int64_t window_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    window_io_ctx_t* wctx = ctx;
+
+    if (offset >= wctx->size) {
+        return 0;
+    }
+    if (size > wctx->size - offset) {
+        size = (size_t)(wctx->size - offset);
+    }
+    return wctx->inner->read(wctx->inner->ctx, wctx->offset + offset, buffer, size);
+}
+
+static int64_t window_size(void* ctx)
+{
+    window_io_ctx_t* wctx = ctx;
+    return (int64_t)wctx->size;
+}
+
+static void window_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    window_io_ctx_t* wctx = ctx;
+    ziprand_io_capabilities(wctx->inner, caps);
+}
+
+static void window_close(void* ctx)
+{
+    window_io_ctx_t* wctx = ctx;
+    ziprand_io_free(wctx->inner);
+    free(wctx);
+}
+
+ziprand_io_t* ziprand_io_window(ziprand_io_t* inner, uint64_t offset, uint64_t size)
+{
+    if (!inner || !inner->read || !inner->get_size)
+        return NULL;
+
+    int64_t inner_size = inner->get_size(inner->ctx);
+    if (inner_size < 0 || offset > (uint64_t)inner_size)
+        return NULL;
+    if (size == 0)
+        size = (uint64_t)inner_size - offset;
+    if (size == 0 || size > (uint64_t)inner_size - offset)
+        return NULL;
+
+    window_io_ctx_t* wctx = malloc(sizeof(window_io_ctx_t));
+    if (!wctx)
+        return NULL;
+
+    wctx->inner = inner;
+    wctx->offset = offset;
+    wctx->size = size;
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        free(wctx);
+        return NULL;
+    }
+
+    io->ctx = wctx;
+    io->read = window_read;
+    io->get_size = window_size;
+    io->close = window_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = inner->mapped ? (const uint8_t*)inner->mapped + offset : NULL;
+    io->capabilities = window_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* LRU cache of fixed-size blocks, keyed by source and block index */
+typedef struct cache_node {
+    uint64_t source;
+    uint64_t block;
+    size_t len;
+    struct cache_node* lru_prev; /* towards the most recently used */
+    struct cache_node* lru_next;
+    struct cache_node* hash_next;
+    uint8_t data[];
+} cache_node_t;
+
+struct ziprand_cache {
+    io_lock_t lock;
+    size_t refs; /* the owner's reference and one per cached I/O interface */
+    uint64_t next_source;
+    size_t block_size;
+    size_t max_blocks;
+    size_t count;
+    cache_node_t** buckets;
+    size_t bucket_mask;
+    cache_node_t* lru_head; /* most recently used */
+    cache_node_t* lru_tail;
+};
+
+static size_t cache_bucket(const ziprand_cache_t* cache, uint64_t source, uint64_t block)
+{
+    uint64_t hash = (block ^ (source << 40) ^ (source >> 24)) * 0x9E3779B97F4A7C15ULL;
+    return (size_t)(hash >> 32) & cache->bucket_mask;
+}
+
+ziprand_cache_t* ziprand_cache_new(size_t block_size, size_t capacity)
+{
+    if (block_size == 0) {
+        block_size = 64 * 1024;
+    }
+    if (capacity == 0) {
+        capacity = 16 * 1024 * 1024;
+    }
+
+    ziprand_cache_t* cache = calloc(1, sizeof(ziprand_cache_t));
+    if (!cache) {
+        return NULL;
+    }
+
+    cache->refs = 1;
+    cache->block_size = block_size;
+    cache->max_blocks = capacity / block_size > 0 ? capacity / block_size : 1;
+
+    size_t buckets = 16;
+    while (buckets < cache->max_blocks * 2 && buckets < ((size_t)1 << 30)) {
+        buckets <<= 1;
+    }
+    cache->buckets = calloc(buckets, sizeof(cache_node_t*));
+    cache->bucket_mask = buckets - 1;
+    if (!cache->buckets || io_lock_init(&cache->lock) != 0) {
+        free(cache->buckets);
+        free(cache);
+        return NULL;
+    }
+    return cache;
+}
+
+static void cache_destroy(ziprand_cache_t* cache)
+{
+    cache_node_t* node = cache->lru_head;
+    while (node) {
+        cache_node_t* next = node->lru_next;
+        free(node);
+        node = next;
+    }
+    io_lock_destroy(&cache->lock);
+    free(cache->buckets);
+    free(cache);
+}
+
+static void cache_unlink_lru(ziprand_cache_t* cache, cache_node_t* node)
+{
+    if (node->lru_prev) {
+        node->lru_prev->lru_next = node->lru_next;
+    } else {
+        cache->lru_head = node->lru_next;
+    }
+    if (node->lru_next) {
+        node->lru_next->lru_prev = node->lru_prev;
+    } else {
+        cache->lru_tail = node->lru_prev;
+    }
+}
+
+static void cache_push_front(ziprand_cache_t* cache, cache_node_t* node)
+{
+    node->lru_prev = NULL;
+    node->lru_next = cache->lru_head;
+    if (cache->lru_head) {
+        cache->lru_head->lru_prev = node;
+    } else {
+        cache->lru_tail = node;
+    }
+    cache->lru_head = node;
+}
+
+/* must hold the lock; touch marks the block as most recently used */
+static cache_node_t*
+cache_lookup(ziprand_cache_t* cache, uint64_t source, uint64_t block, int touch)
+{
+    cache_node_t* node = cache->buckets[cache_bucket(cache, source, block)];
+    while (node && (node->source != source || node->block != block)) {
+        node = node->hash_next;
+    }
+    if (node && touch && node != cache->lru_head) {
+        cache_unlink_lru(cache, node);
+        cache_push_front(cache, node);
+    }
+    return node;
+}
+
+static void cache_remove(ziprand_cache_t* cache, cache_node_t* node)
+{
+    cache_node_t** link = &cache->buckets[cache_bucket(cache, node->source, node->block)];
+    while (*link != node) {
+        link = &(*link)->hash_next;
+    }
+    *link = node->hash_next;
+    cache_unlink_lru(cache, node);
+    cache->count--;
+    free(node);
+}
+
+/* drop a reference, the last one frees the cache */
+static void cache_release(ziprand_cache_t* cache)
+{
+    io_lock(&cache->lock);
+    int last = --cache->refs == 0;
+    io_unlock(&cache->lock);
+
+    if (last) {
+        cache_destroy(cache);
+    }
+}
+
+void ziprand_cache_free(ziprand_cache_t* cache)
+{
+    if (cache) {
+        cache_release(cache);
+    }
+}
+
+/* must hold the lock; a block that is already cached is left alone */
+static void
+cache_insert(ziprand_cache_t* cache, uint64_t source, uint64_t block, const void* data, size_t len)
+{
+    if (cache_lookup(cache, source, block, 1)) {
+        return;
+    }
+    cache_node_t* node = malloc(sizeof(cache_node_t) + len);
+    if (!node) {
+        return;
+    }
+    if (cache->count >= cache->max_blocks) {
+        cache_remove(cache, cache->lru_tail);
+    }
+
+    node->source = source;
+    node->block = block;
+    node->len = len;
+    memcpy(node->data, data, len);
+
+    size_t bucket = cache_bucket(cache, source, block);
+    node->hash_next = cache->buckets[bucket];
+    cache->buckets[bucket] = node;
+    cache_push_front(cache, node);
+    cache->count++;
+}
+
+/* block-cached I/O */
+typedef struct {
+    ziprand_io_t* inner;
+    ziprand_cache_t* cache;
+    uint64_t source;
+    uint64_t size;
+} cached_io_ctx_t;
+
+/* read blocks [first, first + count) from the source, cache them and copy out the requested
+ * part; returns the bytes copied, which is short if the source came up short */
+static int64_t cached_fetch(cached_io_ctx_t* cctx,
+                            uint64_t first,
+                            uint64_t count,
+                            uint64_t offset,
+                            uint8_t* buffer,
+                            size_t size)
+{
+    ziprand_cache_t* cache = cctx->cache;
+    size_t block_size = cache->block_size;
+    uint64_t start = first * block_size;
+    uint64_t span = count * block_size;
+    if (span > cctx->size - start) {
+        span = cctx->size - start;
+    }
+
+    uint8_t* run = malloc((size_t)span);
+    if (!run) {
+        return -1;
+    }
+    int64_t got = cctx->inner->read(cctx->inner->ctx, start, run, (size_t)span);
+    if (got < 0) {
+        free(run);
+        return got;
+    }
+
+    /* only blocks that were read completely go into the cache */
+    io_lock(&cache->lock);
+    for (uint64_t i = 0; i < count; i++) {
+        uint64_t block_start = i * block_size;
+        uint64_t block_len = span - block_start < block_size ? span - block_start : block_size;
+        if (block_start + block_len > (uint64_t)got) {
+            break;
+        }
+        cache_insert(cache, cctx->source, first + i, run + block_start, (size_t)block_len);
+    }
+    io_unlock(&cache->lock);
+
+    uint64_t within = offset - start;
+    size_t copied = 0;
+    if ((uint64_t)got > within) {
+        copied = (uint64_t)got - within < size ? (size_t)((uint64_t)got - within) : size;
+        memcpy(buffer, run + within, copied);
+    }
+    free(run);
+    return (int64_t)copied;
+}
+
+static int64_t cached_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    cached_io_ctx_t* cctx = ctx;
+    ziprand_cache_t* cache = cctx->cache;
+    size_t block_size = cache->block_size;
+
+    if (offset >= cctx->size) {
+        return 0;
+    }
+    if (size > cctx->size - offset) {
+        size = (size_t)(cctx->size - offset);
+    }
+
+    /* reads as large as the cache would only evict everything else */
+    if (size / block_size >= cache->max_blocks) {
+        return cctx->inner->read(cctx->inner->ctx, offset, buffer, size);
+    }
+
+    uint8_t* out = buffer;
+    uint64_t last = size > 0 ? (offset + size - 1) / block_size : 0;
+    size_t done = 0;
+    while (done < size) {
+        uint64_t position = offset + done;
+        uint64_t block = position / block_size;
+
+        io_lock(&cache->lock);
+        cache_node_t* node = cache_lookup(cache, cctx->source, block, 1);
+        if (node) {
+            size_t within = (size_t)(position - block * block_size);
+            size_t n = node->len > within ? node->len - within : 0;
+            if (n > size - done) {
+                n = size - done;
+            }
+            memcpy(out + done, node->data + within, n);
+            io_unlock(&cache->lock);
+            if (n == 0) {
+                break;
+            }
+            done += n;
+            continue;
+        }
+
+        /* fetch the whole run of missing blocks with one read */
+        uint64_t end = block + 1;
+        while (end <= last && !cache_lookup(cache, cctx->source, end, 0)) {
+            end++;
+        }
+        io_unlock(&cache->lock);
+
+        uint64_t run_end = end * block_size < offset + size ? end * block_size : offset + size;
+        size_t want = (size_t)(run_end - position);
+        int64_t got = cached_fetch(cctx, block, end - block, position, out + done, want);
+        if (got < 0) {
+            return done > 0 ? (int64_t)done : got;
+        }
+        done += (size_t)got;
+        if ((size_t)got < want) {
+            break;
+        }
+    }
+    return (int64_t)done;
+}
+
+static int64_t cached_size(void* ctx)
+{
+    cached_io_ctx_t* cctx = ctx;
+    return (int64_t)cctx->size;
+}
+
+static void cached_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    cached_io_ctx_t* cctx = ctx;
+    ziprand_io_capabilities(cctx->inner, caps);
+    if (cctx->cache->block_size > caps->block_size) {
+        caps->block_size = cctx->cache->block_size;
+    }
+}
+
+static void cached_close(void* ctx)
+{
+    cached_io_ctx_t* cctx = ctx;
+    ziprand_cache_t* cache = cctx->cache;
+
+    /* the blocks of a closed source can never be hit again */
+    io_lock(&cache->lock);
+    cache_node_t* node = cache->lru_head;
+    while (node) {
+        cache_node_t* next = node->lru_next;
+        if (node->source == cctx->source) {
+            cache_remove(cache, node);
+        }
+        node = next;
+    }
+    io_unlock(&cache->lock);
+
+    cache_release(cache);
+    ziprand_io_free(cctx->inner);
+    free(cctx);
+}
+
+ziprand_io_t* ziprand_io_cached_shared(ziprand_io_t* inner, ziprand_cache_t* cache)
+{
+    if (!inner || !inner->read || !inner->get_size || !cache)
+        return NULL;
+
+    int64_t inner_size = inner->get_size(inner->ctx);
+    if (inner_size < 0)
+        return NULL;
+
+    cached_io_ctx_t* cctx = malloc(sizeof(cached_io_ctx_t));
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!cctx || !io) {
+        free(cctx);
+        free(io);
+        return NULL;
+    }
+
+    cctx->inner = inner;
+    cctx->cache = cache;
+    cctx->size = (uint64_t)inner_size;
+
+    io_lock(&cache->lock);
+    cache->refs++;
+    cctx->source = cache->next_source++;
+    io_unlock(&cache->lock);
+
+    io->ctx = cctx;
+    io->read = cached_read;
+    io->get_size = cached_size;
+    io->close = cached_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = cached_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+ziprand_io_t* ziprand_io_cached(ziprand_io_t* inner, size_t block_size, size_t capacity)
+{
+    if (!inner)
+        return NULL;
+
+    ziprand_cache_t* cache = ziprand_cache_new(block_size, capacity);
+    if (!cache)
+        return NULL;
+
+    /* the interface keeps the cache alive on its own */
+    ziprand_io_t* io = ziprand_io_cached_shared(inner, cache);
+    ziprand_cache_free(cache);
+    return io;
+}
+
+/* retrying I/O with exponential backoff */
+typedef struct {
+    ziprand_io_t* inner;
+    ziprand_retry_policy_t policy;
+} retry_io_ctx_t;
+
+static int retry_default(void* ctx, int64_t result, unsigned attempt)
+{
+    (void)ctx;
+    (void)attempt;
+    /* a replaced archive stays replaced */
+    return result != ZIPRAND_ERR_SOURCE_CHANGED;
+}
+
+/* sleep before the next attempt: the delay doubles each time, with jitter so that many
+ * readers failing together don't come back together */
+static void retry_backoff(const ziprand_retry_policy_t* policy, uint64_t seed, unsigned attempt)
+{
+    uint64_t delay = policy->initial_delay_ms;
+    for (unsigned i = 1; i < attempt && delay < policy->max_delay_ms; i++) {
+        delay *= 2;
+    }
+    if (delay > policy->max_delay_ms) {
+        delay = policy->max_delay_ms;
+    }
+
+    uint64_t mix = (seed ^ ((uint64_t)attempt << 56) ^ (uint64_t)clock()) * 0x9E3779B97F4A7C15ULL;
+    mix ^= mix >> 31;
+    uint64_t half = delay / 2;
+    io_sleep_ms((unsigned long)(half + (half > 0 ? mix % (half + 1) : 0)));
+}
+
+static int64_t retry_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    retry_io_ctx_t* rctx = ctx;
+    ziprand_io_t* inner = rctx->inner;
+    const ziprand_retry_policy_t* policy = &rctx->policy;
+
+    int64_t result = inner->read(inner->ctx, offset, buffer, size);
+    for (unsigned attempt = 1; result < 0 && attempt < policy->max_attempts; attempt++) {
+        if (!policy->should_retry(policy->ctx, result, attempt)) {
+            break;
+        }
+        retry_backoff(policy, offset, attempt);
+        result = inner->read(inner->ctx, offset, buffer, size);
+    }
+    return result;
+}
+
+static int64_t retry_size(void* ctx)
+{
+    retry_io_ctx_t* rctx = ctx;
+    ziprand_io_t* inner = rctx->inner;
+    const ziprand_retry_policy_t* policy = &rctx->policy;
+
+    int64_t result = inner->get_size(inner->ctx);
+    for (unsigned attempt = 1; result < 0 && attempt < policy->max_attempts; attempt++) {
+        if (!policy->should_retry(policy->ctx, result, attempt)) {
+            break;
+        }
+        retry_backoff(policy, UINT64_MAX, attempt);
+        result = inner->get_size(inner->ctx);
+    }
+    return result;
+}
+
+static void retry_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    retry_io_ctx_t* rctx = ctx;
+    ziprand_io_capabilities(rctx->inner, caps);
+}
+
+static void retry_close(void* ctx)
+{
+    retry_io_ctx_t* rctx = ctx;
+    ziprand_io_free(rctx->inner);
+    free(rctx);
+}
+
+ziprand_io_t* ziprand_io_retry(ziprand_io_t* inner, const ziprand_retry_policy_t* policy)
+{
+    if (!inner || !inner->read || !inner->get_size)
+        return NULL;
+
+    retry_io_ctx_t* rctx = malloc(sizeof(retry_io_ctx_t));
+    if (!rctx)
+        return NULL;
+
+    rctx->inner = inner;
+    if (policy) {
+        rctx->policy = *policy;
+    } else {
+        memset(&rctx->policy, 0, sizeof(rctx->policy));
+    }
+    if (rctx->policy.max_attempts == 0)
+        rctx->policy.max_attempts = 4;
+    if (rctx->policy.initial_delay_ms == 0)
+        rctx->policy.initial_delay_ms = 100;
+    if (rctx->policy.max_delay_ms == 0)
+        rctx->policy.max_delay_ms = 5000;
+    if (!rctx->policy.should_retry)
+        rctx->policy.should_retry = retry_default;
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        free(rctx);
+        return NULL;
+    }
+
+    io->ctx = rctx;
+    io->read = retry_read;
+    io->get_size = retry_size;
+    io->close = retry_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = retry_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* rate-limited I/O: token buckets for bytes and requests, allowing one second of burst */
+#define THROTTLE_BURST_US 1000000
+
+typedef struct {
+    ziprand_io_t* inner;
+    uint64_t bytes_per_sec;
+    uint32_t ops_per_sec;
+    io_lock_t lock;
+    uint64_t bytes_tat; /* theoretical arrival time of the bytes bucket */
+    uint64_t ops_tat;
+} throttle_io_ctx_t;
+
+/* reserve cost_us on a bucket, returns how long the caller has to wait for it */
+static uint64_t throttle_reserve(uint64_t* tat, uint64_t now, uint64_t cost_us)
+{
+    if (*tat < now) {
+        *tat = now;
+    }
+    *tat += cost_us;
+    return *tat > now + THROTTLE_BURST_US ? *tat - now - THROTTLE_BURST_US : 0;
+}
+
+static int64_t throttle_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    throttle_io_ctx_t* tctx = ctx;
+
+    uint64_t now = io_now_us();
+    uint64_t wait = 0;
+    io_lock(&tctx->lock);
+    if (tctx->bytes_per_sec > 0) {
+        uint64_t cost = (uint64_t)((double)size * 1e6 / (double)tctx->bytes_per_sec);
+        uint64_t bytes_wait = throttle_reserve(&tctx->bytes_tat, now, cost);
+        wait = bytes_wait > wait ? bytes_wait : wait;
+    }
+    if (tctx->ops_per_sec > 0) {
+        uint64_t ops_wait = throttle_reserve(&tctx->ops_tat, now, 1000000 / tctx->ops_per_sec);
+        wait = ops_wait > wait ? ops_wait : wait;
+    }
+    io_unlock(&tctx->lock);
+
+    if (wait > 0) {
+        io_sleep_ms((unsigned long)((wait + 999) / 1000));
+    }
+    return tctx->inner->read(tctx->inner->ctx, offset, buffer, size);
+}
+
+static int64_t throttle_size(void* ctx)
+{
+    throttle_io_ctx_t* tctx = ctx;
+    return tctx->inner->get_size(tctx->inner->ctx);
+}
+
+static void throttle_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    throttle_io_ctx_t* tctx = ctx;
+    ziprand_io_capabilities(tctx->inner, caps);
+}
+
+static void throttle_close(void* ctx)
+{
+    throttle_io_ctx_t* tctx = ctx;
+    ziprand_io_free(tctx->inner);
+    io_lock_destroy(&tctx->lock);
+    free(tctx);
+}
+
+ziprand_io_t*
+ziprand_io_throttled(ziprand_io_t* inner, uint64_t bytes_per_sec, uint32_t ops_per_sec)
+{
+    if (!inner || !inner->read || !inner->get_size)
+        return NULL;
+
+    throttle_io_ctx_t* tctx = calloc(1, sizeof(throttle_io_ctx_t));
+    if (!tctx)
+        return NULL;
+
+    tctx->inner = inner;
+    tctx->bytes_per_sec = bytes_per_sec;
+    tctx->ops_per_sec = ops_per_sec;
+    if (io_lock_init(&tctx->lock) != 0) {
+        free(tctx);
+        return NULL;
+    }
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        io_lock_destroy(&tctx->lock);
+        free(tctx);
+        return NULL;
+    }
+
+    io->ctx = tctx;
+    io->read = throttle_read;
+    io->get_size = throttle_size;
+    io->close = throttle_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = throttle_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+/* I/O that records call counts, bytes and latency */
+typedef struct {
+    ziprand_io_t* inner;
+    io_lock_t lock;
+    ziprand_io_metrics_t metrics;
+} metered_io_ctx_t;
+
+static int64_t metered_read(void* ctx, uint64_t offset, void* buffer, size_t size)
+{
+    metered_io_ctx_t* mctx = ctx;
+
+    uint64_t start = io_now_us();
+    int64_t result = mctx->inner->read(mctx->inner->ctx, offset, buffer, size);
+    uint64_t elapsed = io_now_us() - start;
+
+    size_t bucket = 0;
+    while (bucket < ZIPRAND_LATENCY_BUCKETS - 1 && elapsed >> (bucket + 1) != 0) {
+        bucket++;
+    }
+
+    io_lock(&mctx->lock);
+    ziprand_io_metrics_t* metrics = &mctx->metrics;
+    metrics->reads++;
+    if (result < 0) {
+        metrics->errors++;
+    } else {
+        metrics->bytes += (uint64_t)result;
+    }
+    metrics->total_us += elapsed;
+    if (elapsed > metrics->max_us) {
+        metrics->max_us = elapsed;
+    }
+    metrics->latency_histogram[bucket]++;
+    io_unlock(&mctx->lock);
+
+    return result;
+}
+
+static int64_t metered_size(void* ctx)
+{
+    metered_io_ctx_t* mctx = ctx;
+    return mctx->inner->get_size(mctx->inner->ctx);
+}
+
+static void metered_capabilities(void* ctx, ziprand_io_caps_t* caps)
+{
+    metered_io_ctx_t* mctx = ctx;
+    ziprand_io_capabilities(mctx->inner, caps);
+}
+
+static void metered_close(void* ctx)
+{
+    metered_io_ctx_t* mctx = ctx;
+    ziprand_io_free(mctx->inner);
+    io_lock_destroy(&mctx->lock);
+    free(mctx);
+}
+
+ziprand_io_t* ziprand_io_metered(ziprand_io_t* inner)
+{
+    if (!inner || !inner->read || !inner->get_size)
+        return NULL;
+
+    metered_io_ctx_t* mctx = calloc(1, sizeof(metered_io_ctx_t));
+    if (!mctx)
+        return NULL;
+
+    mctx->inner = inner;
+    if (io_lock_init(&mctx->lock) != 0) {
+        free(mctx);
+        return NULL;
+    }
+
+    ziprand_io_t* io = malloc(sizeof(ziprand_io_t));
+    if (!io) {
+        io_lock_destroy(&mctx->lock);
+        free(mctx);
+        return NULL;
+    }
+
+    io->ctx = mctx;
+    io->read = metered_read;
+    io->get_size = metered_size;
+    io->close = metered_close;
+    io->prefetch = NULL;
+    io->read_vectored = NULL;
+    io->read_buffer = NULL;
+    io->mapped = NULL;
+    io->capabilities = metered_capabilities;
+    io->read_ranges = NULL;
+
+    return io;
+}
+
+ziprand_error_t ziprand_io_metrics(const ziprand_io_t* io, ziprand_io_metrics_t* metrics)
+{
+    if (!io || io->read != metered_read || !metrics)
+        return ZIPRAND_ERR_INVALID_PARAM;
+
+    metered_io_ctx_t* mctx = io->ctx;
+    io_lock(&mctx->lock);
+    *metrics = mctx->metrics;
+    io_unlock(&mctx->lock);
+    return ZIPRAND_OK;
+}
+
+void ziprand_io_free(ziprand_io_t* io)
+{
+    if (!io)
+        return;
+    if (io->close)
+        io->close(io->ctx);
+    free(io);
+}
+
+/* stdio stream adapter */
+#if defined(__linux__)
+static ssize_t stream_read(void* cookie, char* buffer, size_t size)
+{
+    return ziprand_fread(cookie, buffer, size);
+}
+
+static int stream_seek(void* cookie, off64_t* offset, int whence)
+{
+    int64_t position = ziprand_fseek(cookie, *offset, whence);
+    if (position < 0) {
+        return -1;
+    }
+    *offset = position;
+    return 0;
+}
+
+static int stream_close(void* cookie)
+{
+    ziprand_fclose(cookie);
+    return 0;
+}
+
+FILE* ziprand_fdopen(ziprand_file_t* file)
+{
+    if (!file)
+        return NULL;
+
+    cookie_io_functions_t functions = {
+        .read = stream_read,
+        .write = NULL,
+        .seek = stream_seek,
+        .close = stream_close,
+    };
+    return fopencookie(file, "rb", functions);
+}
+#elif defined(__APPLE__)
+static int stream_read(void* cookie, char* buffer, int size)
+{
+    return (int)ziprand_fread(cookie, buffer, (size_t)size);
+}
+
+static fpos_t stream_seek(void* cookie, fpos_t offset, int whence)
+{
+    return (fpos_t)ziprand_fseek(cookie, (int64_t)offset, whence);
+}
+
+static int stream_close(void* cookie)
+{
+    ziprand_fclose(cookie);
+    return 0;
+}
+
+FILE* ziprand_fdopen(ziprand_file_t* file)
+{
+    if (!file)
+        return NULL;
+    return funopen(file, stream_read, NULL, stream_seek, stream_close);
+}
+#else
+FILE* ziprand_fdopen(ziprand_file_t* file)
+{
+    (void)file;
+    return NULL;
+}
+#endif
+
+/* extraction to file descriptors */
+static int64_t fd_write(void* ctx, const void* buffer, size_t size)
+{
+    int fd = *(int*)ctx;
+#ifdef _WIN32
+    unsigned int chunk = size > 0x40000000 ? 0x40000000 : (unsigned int)size;
+    return _write(fd, buffer, chunk);
+#else
+    return write(fd, buffer, size);
+#endif
+}
+
+#if defined(__linux__)
+/* copy a source range to fd inside the kernel, returns the number of bytes copied; stops
+ * early when neither copy_file_range nor sendfile can handle the descriptors */
+static uint64_t copy_range_kernel(int source_fd, uint64_t offset, uint64_t size, int fd)
+{
+    uint64_t done = 0;
+    int use_sendfile = 0;
+    while (done < size) {
+        size_t chunk = size - done > 0x40000000 ? 0x40000000 : (size_t)(size - done);
+        ssize_t copied;
+        if (!use_sendfile) {
+            loff_t source_offset = offset + done;
+            copied = copy_file_range(source_fd, &source_offset, fd, NULL, chunk, 0);
+            /* e.g. EXDEV across filesystems on older kernels */
+            if (copied < 0 && errno != EINTR) {
+                use_sendfile = 1;
+                continue;
+            }
+        } else {
+            off_t source_offset = offset + done;
+            copied = sendfile(fd, source_fd, &source_offset, chunk);
+        }
+
+        if (copied < 0 && errno == EINTR) {
+            continue;
+        }
+        if (copied <= 0) {
+            break;
+        }
+        done += copied;
+    }
+    return done;
+}
+#endif
+
+int64_t ziprand_fextract_fd(ziprand_file_t* file, int fd)
+{
+    const ziprand_io_t* io = NULL;
+    uint64_t offset = 0;
+    uint64_t size = 0;
+    if (fd < 0 || !file) {
+        return ZIPRAND_ERR_INVALID_PARAM;
+    }
+    /* compressed entries have no source range to hand to the kernel */
+    int raw = ziprand_fsource_range(file, &io, &offset, &size) == ZIPRAND_OK;
+
+    int64_t copied = 0;
+#if defined(__linux__)
+    uint64_t position = ziprand_ftell(file);
+    if (raw && io->read == file_read && position < size) {
+        file_io_ctx_t* fctx = io->ctx;
+        copied = copy_range_kernel(fctx->fd, offset + position, size - position, fd);
+        ziprand_fseek(file, position + copied, SEEK_SET);
+    }
+#else
+    (void)raw;
+    (void)io;
+    (void)offset;
+    (void)size;
+#endif
+
+    /* whatever the kernel did not copy goes through user space */
+    int64_t rest = ziprand_fcopy(file, fd_write, &fd, 0);
+    if (rest < 0) {
+        return rest;
+    }
+    return copied + rest;
+}
+
+int64_t ziprand_fclone_fd(ziprand_file_t* file, int fd)
+{
+    const ziprand_io_t* io = NULL;
+    uint64_t offset = 0;
+    uint64_t size = 0;
+    if (fd < 0 || !file) {
+        return ZIPRAND_ERR_INVALID_PARAM;
+    }
+    /* compressed entries have no source range to hand to the kernel */
+    int raw = ziprand_fsource_range(file, &io, &offset, &size) == ZIPRAND_OK;
+
+    int64_t cloned = 0;
+#if defined(__linux__) && defined(FICLONERANGE)
+    uint64_t position = ziprand_ftell(file);
+    struct stat st;
+    off_t dest_offset = lseek(fd, 0, SEEK_CUR);
+    if (raw && io->read == file_read && position < size && dest_offset >= 0 &&
+        fstat(fd, &st) == 0 && st.st_blksize > 0) {
+        /* only whole blocks can be cloned, the unaligned tail is copied */
+        uint64_t block = st.st_blksize;
+        uint64_t source_offset = offset + position;
+        uint64_t length = (size - position) / block * block;
+        if (length > 0 && source_offset % block == 0 && (uint64_t)dest_offset % block == 0) {
+            file_io_ctx_t* fctx = io->ctx;
+            struct file_clone_range range = {
+                .src_fd = fctx->fd,
+                .src_offset = source_offset,
+                .src_length = length,
+                .dest_offset = dest_offset,
+            };
+            if (ioctl(fd, FICLONERANGE, &range) == 0 &&
+                lseek(fd, dest_offset + length, SEEK_SET) >= 0) {
+                ziprand_fseek(file, position + length, SEEK_SET);
+                cloned = length;
+            }
+        }
+    }
+#else
+    (void)raw;
+    (void)io;
+    (void)offset;
+    (void)size;
+#endif
+
+    int64_t rest = ziprand_fextract_fd(file, fd);
+    if (rest < 0) {
+        return rest;
+    }
+    return cloned + rest;
+}
+
+/* parallel whole-file reads */
+#define PARALLEL_DEFAULT_CHUNK_SIZE (4 * 1024 * 1024)
+
+typedef struct {
+    ziprand_file_t* file;
+    uint8_t* buffer;
+    uint64_t size;
+    uint64_t chunk_size;
+    uint64_t first_chunk; /* reads chunks first_chunk, first_chunk + stride, ... */
+    uint64_t stride;
+    ziprand_error_t error; /* first failed read, or ZIPRAND_OK */
+#ifdef _WIN32
+    HANDLE thread;
+#else
+    pthread_t thread;
+#endif
+    int started;
+} parallel_worker_t;
+
+static void parallel_read_chunks(parallel_worker_t* worker)
+{
+    uint64_t step = worker->stride * worker->chunk_size;
+    for (uint64_t start = worker->first_chunk * worker->chunk_size; start < worker->size;
+         start += step) {
+        uint64_t end = start + worker->chunk_size;
+        if (end > worker->size) {
+            end = worker->size;
+        }
+
+        /* the I/O callback may return short reads */
+        uint64_t pos = start;
+        while (pos < end) {
+            int64_t got = ziprand_fread_at(worker->file, pos, worker->buffer + pos, end - pos);
+            if (got <= 0) {
+                worker->error = got < 0 ? (ziprand_error_t)got : ZIPRAND_ERR_IO;
+                return;
+            }
+            pos += got;
+        }
+    }
+}
+
+#ifdef _WIN32
+static DWORD WINAPI parallel_thread(LPVOID arg)
+{
+    parallel_read_chunks(arg);
+    return 0;
+}
+#else
+static void* parallel_thread(void* arg)
+{
+    parallel_read_chunks(arg);
+    return NULL;
+}
+#endif
+
+void* ziprand_fread_all_parallel(ziprand_file_t* file,
+                                 size_t concurrency,
+                                 size_t chunk_size,
+                                 ziprand_error_t* error)
+{
+    ziprand_error_t err;
+    if (!error)
+        error = &err;
+
+    *error = ziprand_fcheck_size(file, 0);
+    if (*error != ZIPRAND_OK) {
+        return NULL;
+    }
+    int64_t size = ziprand_fsize(file);
+
+    /* a compressed entry decodes as one stream, chunks can't be read independently */
+    const ziprand_io_t* io;
+    ziprand_io_caps_t caps;
+    if (ziprand_fsource_range(file, &io, NULL, NULL) != ZIPRAND_OK) {
+        return ziprand_fread_all(file, 0, error);
+    }
+    ziprand_io_capabilities(io, &caps);
+    if (concurrency == 0) {
+        concurrency = caps.max_concurrency;
+    }
+    if (chunk_size == 0) {
+        chunk_size = PARALLEL_DEFAULT_CHUNK_SIZE;
+        if (chunk_size % caps.block_size != 0) {
+            chunk_size = (chunk_size / caps.block_size + 1) * caps.block_size;
+        }
+    }
+
+    uint64_t chunk_count = ((uint64_t)size + chunk_size - 1) / chunk_size;
+    if (concurrency > chunk_count) {
+        concurrency = chunk_count ? (size_t)chunk_count : 1;
+    }
+
+    uint8_t* buffer = malloc(size ? (size_t)size : 1);
+    parallel_worker_t* workers = calloc(concurrency, sizeof(parallel_worker_t));
+    if (!buffer || !workers) {
+        free(buffer);
+        free(workers);
+        *error = ZIPRAND_ERR_NOMEM;
+        return NULL;
+    }
+
+    for (size_t i = 0; i < concurrency; i++) {
+        workers[i].file = file;
+        workers[i].buffer = buffer;
+        workers[i].size = size;
+        workers[i].chunk_size = chunk_size;
+        workers[i].first_chunk = i;
+        workers[i].stride = concurrency;
+    }
+
+    /* worker 0 runs on the calling thread, as does any worker whose thread fails to start */
+    for (size_t i = 1; i < concurrency; i++) {
+#ifdef _WIN32
+        workers[i].thread = CreateThread(NULL, 0, parallel_thread, &workers[i], 0, NULL);
+        workers[i].started = workers[i].thread != NULL;
+#else
+        workers[i].started =
+            pthread_create(&workers[i].thread, NULL, parallel_thread, &workers[i]) == 0;
+#endif
+    }
+
+    ziprand_error_t failed = ZIPRAND_OK;
+    for (size_t i = 0; i < concurrency; i++) {
+        if (!workers[i].started) {
+            parallel_read_chunks(&workers[i]);
+        } else {
+#ifdef _WIN32
+            WaitForSingleObject(workers[i].thread, INFINITE);
+            CloseHandle(workers[i].thread);
+#else
+            pthread_join(workers[i].thread, NULL);
+#endif
+        }
+        if (failed == ZIPRAND_OK) {
+            failed = workers[i].error;
+        }
+    }
+
+    free(workers);
+    if (failed != ZIPRAND_OK) {
+        free(buffer);
+        *error = failed;
+        return NULL;
+    }
+
+    *error = ZIPRAND_OK;
+    return buffer;
 }